@@ -0,0 +1,131 @@
+// copy and sync of a large synthetic profile, run with `cargo bench`
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fs_extra::dir::CopyOptions;
+use rusqlite::types::ToSql;
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+use fftemplates::profiles;
+
+const FILES: usize = 5_000;
+const LINKS: usize = 20_000;
+// sqlite limit on the number of parameters of a statement
+const MAX_PARAMS: usize = 999;
+
+// profile with many small files and links, links are added after the
+// returned baseline bookmark, so all of them are new for a sync
+fn create_profile() -> (TempDir, bookmarks::Bookmark) {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    let folder = profile.path().to_str().unwrap();
+    let baseline = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+    common::insert_many_links(&conn, LINKS);
+    common::create_many_files(profile.path(), FILES);
+
+    (profile, baseline)
+}
+
+fn copy_profile(c: &mut Criterion) {
+    let (profile, _) = create_profile();
+    let entries = profiles::plan_copy(profile.path(), &HashSet::new()).unwrap();
+
+    let mut group = c.benchmark_group("copy");
+    group.sample_size(10);
+    group.bench_function("copy_profile", |b| {
+        b.iter_batched(
+            || TempDir::new().unwrap(),
+            |to| {
+                fs_extra::copy_items(&entries, to.path(), &CopyOptions::new()).unwrap();
+                // removing the copy isn't measured
+                to
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn sync_bookmarks(c: &mut Criterion) {
+    let (profile, baseline) = create_profile();
+    let folder = profile.path().to_str().unwrap();
+
+    let mut group = c.benchmark_group("sync");
+    group.sample_size(10);
+    group.bench_function("full_sync", |b| {
+        b.iter_batched(
+            || {
+                let target = TempDir::new().unwrap();
+                common::create_places(target.path());
+                target
+            },
+            |target| {
+                let (new_bookmarks, new_places, new_origins) =
+                    bookmarks::get_new_entries(folder, &baseline, &EntryFilter::default()).unwrap();
+                bookmarks::insert_new_entries(
+                    target.path().to_str().unwrap(),
+                    new_bookmarks.as_deref(),
+                    new_places.as_ref(),
+                    new_origins.as_ref(),
+                )
+                .unwrap();
+                target
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+// same rows as `bookmarks::get_places`, with a single query per chunk of ids
+fn get_places_batched(conn: &Connection, ids: &[i64]) -> HashMap<i64, (i64, Option<String>)> {
+    let mut places = HashMap::new();
+    for chunk in ids.chunks(MAX_PARAMS) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let mut statement = conn
+            .prepare(&format!(
+                "select id, url from moz_places where id in ({})",
+                placeholders
+            ))
+            .unwrap();
+        let params: Vec<&dyn ToSql> = chunk.iter().map(|id| id as &dyn ToSql).collect();
+        let rows = statement
+            .query_map(&params, |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        for row in rows {
+            let (id, url) = row.unwrap();
+            places.insert(id, (id, url));
+        }
+    }
+
+    places
+}
+
+fn place_lookup(c: &mut Criterion) {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_many_links(&conn, LINKS);
+    let folder = profile.path().to_str().unwrap();
+    let ids: Vec<i64> = (1..=LINKS as i64).collect();
+
+    let mut group = c.benchmark_group("place_lookup");
+    group.sample_size(10);
+    group.bench_function("per_row", |b| {
+        b.iter(|| bookmarks::get_places(folder, &ids).unwrap().unwrap().len())
+    });
+    group.bench_function("batch_in", |b| {
+        b.iter(|| get_places_batched(&conn, &ids).len())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, copy_profile, sync_bookmarks, place_lookup);
+criterion_main!(benches);
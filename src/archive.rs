@@ -0,0 +1,79 @@
+use flate2::read::GzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use tar::Archive;
+
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::FfError;
+
+const GZIP_ARCHIVE_EXTENSION: &str = ".tar.gz";
+const ZSTD_ARCHIVE_EXTENSION: &str = ".tar.zst";
+
+/// Where a template profile comes from.
+#[derive(Debug, PartialEq)]
+pub enum ProfileSource {
+    // regular profile folder
+    Directory(PathBuf),
+    // `<name>.tar.gz` or `<name>.tar.zst` file in the firefox root
+    Archive(PathBuf),
+}
+
+impl ProfileSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            ProfileSource::Directory(path) => path,
+            ProfileSource::Archive(path) => path,
+        }
+    }
+}
+
+/// Returns profile name for an archive file name, `None` if it's not an archive.
+pub fn archive_profile_name(file_name: &str) -> Option<&str> {
+    [GZIP_ARCHIVE_EXTENSION, ZSTD_ARCHIVE_EXTENSION]
+        .iter()
+        .filter_map(|extension| file_name.strip_suffix(extension))
+        .find(|name| !name.is_empty())
+}
+
+/// Extracts archived profile into the folder.
+///
+/// Archives with the profile folder itself at the top level are supported
+/// as well as archives of profile content, either way content of the profile
+/// ends up directly in `to`.
+pub fn extract_archive<P: AsRef<Path>>(archive: P, to: P) -> Result<(), FfError> {
+    let archive = archive.as_ref();
+    let to = to.as_ref();
+    let file = BufReader::new(File::open(archive)?);
+    let file_name = archive.to_string_lossy();
+
+    let reader: Box<dyn Read> = if file_name.ends_with(GZIP_ARCHIVE_EXTENSION) {
+        Box::new(GzDecoder::new(file))
+    } else if file_name.ends_with(ZSTD_ARCHIVE_EXTENSION) {
+        match StreamingDecoder::new(file) {
+            Err(e) => Err(format!("Unable to read `{}` : {}", file_name, e))?,
+            Ok(decoder) => Box::new(decoder),
+        }
+    } else {
+        Err(format!("`{}` is not a supported archive", file_name))?
+    };
+    fs::create_dir_all(to)?;
+    Archive::new(reader).unpack(to)?;
+
+    // move content of a single top level folder up
+    let entries = fs::read_dir(to)?.collect::<Result<Vec<_>, _>>()?;
+    if entries.len() == 1 && entries[0].file_type()?.is_dir() {
+        let inner = entries[0].path();
+        for entry in fs::read_dir(&inner)? {
+            let entry = entry?;
+            fs::rename(entry.path(), to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(inner)?;
+    }
+
+    Ok(())
+}
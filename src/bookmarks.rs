@@ -1,11 +1,82 @@
+use log::{info, warn};
 use rusqlite;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, ErrorCode, OpenFlags, OptionalExtension, Row};
 
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::error::Error;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
 use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
 
-#[derive(Debug, PartialEq)]
+use crate::cli::ConflictPolicy;
+use crate::error::FfError;
+use crate::store::{PlacesStore, SqlitePlacesStore};
+use crate::sync;
+use crate::sync::SyncPlan;
+use crate::timestamps;
+
+const PLACES_FILE_NAME: &str = "places.sqlite";
+/// Lock file in the target profile held while bookmarks or history are synced into it,
+/// so syncs of several sessions at once take turns instead of interleaving.
+pub const SYNC_LOCK_FILE_NAME: &str = "fftemplates-sync.lock";
+
+// types of entries in moz_bookmarks
+pub const TYPE_BOOKMARK: i64 = 1;
+pub const TYPE_FOLDER: i64 = 2;
+pub const TYPE_SEPARATOR: i64 = 3;
+// sync status of entries that were never uploaded by firefox sync
+const SYNC_STATUS_NEW: i64 = 1;
+
+// fixed guids of the built-in root folders
+pub const ROOT_GUID: &str = "root________";
+pub const MENU_GUID: &str = "menu________";
+pub const TOOLBAR_GUID: &str = "toolbar_____";
+pub const TAGS_GUID: &str = "tags________";
+pub const UNFILED_GUID: &str = "unfiled_____";
+pub const MOBILE_GUID: &str = "mobile______";
+const ROOT_GUIDS: [&str; 6] = [
+    ROOT_GUID,
+    MENU_GUID,
+    TOOLBAR_GUID,
+    TAGS_GUID,
+    UNFILED_GUID,
+    MOBILE_GUID,
+];
+
+// how long sqlite itself waits for a lock before returning busy
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+// retries on top of the busy timeout, with delay doubling every attempt
+pub(crate) const BUSY_RETRY_ATTEMPTS: u32 = 5;
+const BUSY_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+// number of rows fetched from moz_bookmarks at once by BookmarkIter
+const BOOKMARKS_PAGE_SIZE: i64 = 512;
+
+pub type NewEntries = (
+    Option<Vec<Bookmark>>,
+    Option<HashMap<i64, Place>>,
+    Option<HashMap<i64, Origin>>,
+);
+
+/// Limits which of the new entries are synced back.
+#[derive(Debug, Default, Clone)]
+pub struct EntryFilter {
+    // title of the folder new bookmarks have to be under
+    pub folder: Option<String>,
+    // firefox timestamp new bookmarks have to be added at or after
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bookmark {
     pub id: i64,
     pub r#type: Option<i64>,
@@ -20,9 +91,11 @@ pub struct Bookmark {
     pub guid: Option<String>,
     pub sync_status: i64,
     pub sync_change_counter: i64,
+    // guid of the parent folder, ids of the same folder differ between profiles
+    pub parent_guid: Option<String>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Place {
     pub id: i64,
     pub url: Option<String>,
@@ -40,9 +113,19 @@ pub struct Place {
     pub description: Option<String>,
     pub preview_image_url: Option<String>,
     pub origin_id: Option<i64>,
+    // keywords of moz_keywords pointing to the place, like of search bookmarks
+    pub keywords: Vec<Keyword>,
 }
 
-#[derive(Debug, PartialEq)]
+/// Keyword that opens its place, `%s` in the url of the place and in
+/// `post_data` of a search form is replaced with what follows the keyword.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyword {
+    pub keyword: String,
+    pub post_data: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Origin {
     pub id: i64,
     pub prefix: String,
@@ -50,167 +133,887 @@ pub struct Origin {
     pub frecency: i64,
 }
 
-pub fn get_latest_bookmark(profile_folder: &str) -> Result<Option<Bookmark>, Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+#[derive(Debug, Default, PartialEq)]
+pub struct ProfileCounts {
+    pub folders: i64,
+    pub links: i64,
+    pub separators: i64,
+    pub places: i64,
+    pub origins: i64,
+}
+
+/// What `repair_places` has fixed.
+#[derive(Debug, Default, PartialEq)]
+pub struct RepairReport {
+    // places counted as referenced, but no bookmark or keyword points to them
+    pub orphaned_places: usize,
+    pub foreign_counts_fixed: usize,
+    pub removed_origins: usize,
+}
 
-    let mut statement = conn.prepare(
+#[derive(Debug, PartialEq)]
+pub enum BookmarkNode {
+    Folder {
+        title: Option<String>,
+        children: Vec<BookmarkNode>,
+    },
+    Link {
+        title: Option<String>,
+        url: Option<String>,
+    },
+    Separator,
+}
+
+fn places_db_path(profile_folder: &str) -> PathBuf {
+    Path::new(profile_folder).join(Path::new(PLACES_FILE_NAME))
+}
+
+/// Whether the profile has a places database, profiles that were
+/// created but never started don't.
+pub fn has_places(profile_folder: &Path) -> bool {
+    profile_folder.join(PLACES_FILE_NAME).is_file()
+}
+
+// every access to places database of a profile goes through here
+pub fn open_places(profile_folder: &str, read_only: bool) -> Result<Connection, FfError> {
+    let database_file = places_db_path(profile_folder);
+    // opening would create an empty database, leaving it behind in the profile
+    if !database_file.exists() {
+        return Err(FfError::PlacesDbMissing(database_file));
+    }
+    let conn = if read_only {
+        Connection::open_with_flags(database_file, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+    } else {
+        Connection::open(database_file)?
+    };
+    // firefox might be using the database at the same time
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    Ok(conn)
+}
+
+/// Runs `f`, retrying it with exponential backoff while database is busy or locked.
+///
+/// Gives up after `max_attempts` attempts, returning the last error.
+pub fn with_busy_retry<T, F>(max_attempts: u32, mut f: F) -> Result<T, FfError>
+where
+    F: FnMut() -> Result<T, rusqlite::Error>,
+{
+    let mut delay = BUSY_RETRY_INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(result) => return Ok(result),
+            Err(e) if is_busy(&e) => {
+                if attempt >= max_attempts {
+                    return Err(FfError::Message(format!(
+                        "Database is still busy after {} attempts : {}",
+                        attempt, e
+                    )));
+                }
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(FfError::Sqlite(e)),
+        }
+    }
+}
+
+fn is_busy(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(e, _) => {
+            e.code == ErrorCode::DatabaseBusy || e.code == ErrorCode::DatabaseLocked
+        }
+        _ => false,
+    }
+}
+
+// runs `f` in a single immediate transaction, rolled back when `f` fails,
+// starting and ending it are retried as firefox may hold the lock
+pub(crate) fn in_transaction<T, F>(conn: &Connection, f: F) -> Result<T, FfError>
+where
+    F: FnOnce() -> Result<T, FfError>,
+{
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        conn.execute_batch("begin immediate")
+    })?;
+    match f() {
+        Ok(result) => {
+            with_busy_retry(BUSY_RETRY_ATTEMPTS, || conn.execute_batch("commit"))?;
+            Ok(result)
+        }
+        Err(e) => {
+            with_busy_retry(BUSY_RETRY_ATTEMPTS, || conn.execute_batch("rollback"))?;
+            Err(e)
+        }
+    }
+}
+
+pub fn get_latest_bookmark(profile_folder: &str) -> Result<Option<Bookmark>, FfError> {
+    let conn = open_places(profile_folder, true)?;
+
+    SqlitePlacesStore::new(&conn).latest_bookmark()
+}
+
+/// Lazily iterates over every bookmark of the database ordered by id,
+/// fetching rows page by page instead of loading the whole table in memory.
+pub fn iter_bookmarks(
+    conn: &Connection,
+) -> Result<impl Iterator<Item = Result<Bookmark, FfError>> + '_, FfError> {
+    BookmarkIter::new(conn, i64::MIN, i64::MAX)
+}
+
+/// Same as `iter_bookmarks`, but only yields bookmarks with
+/// `low_id < id <= high_id`.
+pub fn iter_bookmarks_between(
+    conn: &Connection,
+    low_id: i64,
+    high_id: i64,
+) -> Result<impl Iterator<Item = Result<Bookmark, FfError>> + '_, FfError> {
+    BookmarkIter::new(conn, low_id, high_id)
+}
+
+struct BookmarkIter<'conn> {
+    conn: &'conn Connection,
+    last_id: i64,
+    high_id: i64,
+    page: VecDeque<Bookmark>,
+    done: bool,
+}
+
+impl<'conn> BookmarkIter<'conn> {
+    fn new(conn: &'conn Connection, low_id: i64, high_id: i64) -> Result<Self, FfError> {
+        let mut iter = BookmarkIter {
+            conn,
+            last_id: low_id,
+            high_id,
+            page: VecDeque::new(),
+            done: false,
+        };
+        // fetch first page right away, so that errors like a missing table
+        // are reported on creation rather than on first iteration
+        iter.fetch_page()?;
+        Ok(iter)
+    }
+
+    fn fetch_page(&mut self) -> Result<(), FfError> {
+        let mut statement = self.conn.prepare_cached(
+            "
+                select
+                    id, type, fk, parent, position, title, keyword_id,
+                    folder_type, dateAdded, lastModified, guid, syncStatus, syncChangeCounter,
+                    (select p.guid from moz_bookmarks p where p.id = moz_bookmarks.parent)
+                from moz_bookmarks
+                where 1=1
+                and id > :low_id
+                and id <= :high_id
+                order by id
+                limit :page_size",
+        )?;
+        let page = with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+            statement
+                .query_map_named(
+                    &[
+                        (":low_id", &self.last_id),
+                        (":high_id", &self.high_id),
+                        (":page_size", &BOOKMARKS_PAGE_SIZE),
+                    ],
+                    bookmark_from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        self.page.extend(page);
+
+        match self.page.back() {
+            Some(bookmark) => self.last_id = bookmark.id,
+            None => self.done = true,
+        }
+        if (self.page.len() as i64) < BOOKMARKS_PAGE_SIZE {
+            self.done = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'conn> Iterator for BookmarkIter<'conn> {
+    type Item = Result<Bookmark, FfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            if let Err(e) = self.fetch_page() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
+        self.page.pop_front().map(Ok)
+    }
+}
+
+pub(crate) fn bookmark_from_row(row: &Row) -> rusqlite::Result<Bookmark> {
+    Ok(Bookmark {
+        id: row.get(0)?,
+        r#type: row.get(1)?,
+        fk: row.get(2)?,
+        parent: row.get(3)?,
+        position: row.get(4)?,
+        title: row.get(5)?,
+        keyword_id: row.get(6)?,
+        folder_type: row.get(7)?,
+        date_added: row.get(8)?,
+        last_modified: row.get(9)?,
+        guid: row.get(10)?,
+        sync_status: row.get(11)?,
+        sync_change_counter: row.get(12)?,
+        parent_guid: row.get(13)?,
+    })
+}
+
+pub(crate) fn place_from_row(row: &Row) -> rusqlite::Result<Place> {
+    Ok(Place {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        title: row.get(2)?,
+        rev_host: row.get(3)?,
+        visit_count: row.get(4)?,
+        hidden: row.get(5)?,
+        typed: row.get(6)?,
+        favicon_id: row.get(7)?,
+        frecency: row.get(8)?,
+        last_visit_date: row.get(9)?,
+        guid: row.get(10)?,
+        foreign_count: row.get(11)?,
+        url_hash: row.get(12)?,
+        description: row.get(13)?,
+        preview_image_url: row.get(14)?,
+        origin_id: row.get(15)?,
+        keywords: vec![],
+    })
+}
+
+/// Bookmark with the guid, `None` if the profile has no such bookmark.
+pub fn get_bookmark_by_guid(conn: &Connection, guid: &str) -> Result<Option<Bookmark>, FfError> {
+    let mut statement = conn.prepare_cached(
         "
             select
                 id, type, fk, parent, position, title, keyword_id,
-                folder_type, dateAdded, lastModified, guid, syncStatus, syncChangeCounter
+                folder_type, dateAdded, lastModified, guid, syncStatus, syncChangeCounter,
+                (select p.guid from moz_bookmarks p where p.id = moz_bookmarks.parent)
+            from moz_bookmarks
+            where guid = ?1
+        ",
+    )?;
+    let mut bookmarks = statement.query_map(params![guid], bookmark_from_row)?;
+
+    Ok(bookmarks.next().transpose()?)
+}
+
+/// Place with the guid, `None` if the profile has no such place.
+pub fn get_place_by_guid(conn: &Connection, guid: &str) -> Result<Option<Place>, FfError> {
+    let mut statement = conn.prepare_cached(
+        "
+            select
+                id, url, title, rev_host, visit_count, hidden,
+                typed, favicon_id, frecency, last_visit_date,
+                guid, foreign_count, url_hash, description, preview_image_url, origin_id
+            from moz_places
+            where guid = ?1
+        ",
+    )?;
+    let mut places = statement.query_map(params![guid], place_from_row)?;
+
+    Ok(places.next().transpose()?)
+}
+
+/// Builds the whole bookmark hierarchy, starting from the places root.
+///
+/// Children of every folder are ordered by their position, top level children
+/// are the standard roots: menu, toolbar, unfiled and mobile.
+/// Tags folder is left out, since it is not a part of the user hierarchy.
+pub fn build_bookmark_tree(conn: &Connection) -> Result<BookmarkNode, FfError> {
+    let mut root = None;
+    let mut children: HashMap<i64, Vec<Bookmark>> = HashMap::new();
+    for bookmark in iter_bookmarks(conn)? {
+        let bookmark = bookmark?;
+        if bookmark.guid.as_deref() == Some(ROOT_GUID) {
+            root = Some(bookmark);
+            continue;
+        }
+        if bookmark.guid.as_deref() == Some(TAGS_GUID) {
+            continue;
+        }
+        if let Some(parent) = bookmark.parent {
+            children.entry(parent).or_default().push(bookmark);
+        }
+    }
+    let root = match root {
+        None => return Err("Unable to find places root folder")?,
+        Some(root) => root,
+    };
+
+    let mut urls = HashMap::new();
+    let mut statement = conn.prepare(
+        "
+            select p.id, p.url
+            from moz_places p
+            where p.id in (select fk from moz_bookmarks)
+        ",
+    )?;
+    let url_iter = statement.query_map(params![], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    for url in url_iter {
+        let (id, url) = url?;
+        urls.insert(id, url);
+    }
+
+    Ok(build_node(root, &mut children, &urls))
+}
+
+/// Counts bookmarks by type, places and origins.
+pub fn profile_counts(conn: &Connection) -> Result<ProfileCounts, FfError> {
+    let mut counts = ProfileCounts::default();
+
+    let mut statement = conn.prepare(
+        "
+            select type, count(*)
             from moz_bookmarks
-            order by id desc
-            limit 1",
+            group by type
+        ",
     )?;
-    let bookmark_iter = statement.query_map(params![], |row| {
-        Ok(Bookmark {
-            id: row.get(0)?,
-            r#type: row.get(1)?,
-            fk: row.get(2)?,
-            parent: row.get(3)?,
-            position: row.get(4)?,
-            title: row.get(5)?,
-            keyword_id: row.get(6)?,
-            folder_type: row.get(7)?,
-            date_added: row.get(8)?,
-            last_modified: row.get(9)?,
-            guid: row.get(10)?,
-            sync_status: row.get(11)?,
-            sync_change_counter: row.get(12)?,
-        })
+    let type_iter = statement.query_map(params![], |row| {
+        Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, i64>(1)?))
     })?;
-    let mut last_bookmark = None;
-    for bookmark in bookmark_iter {
-        match bookmark {
-            Ok(bookmark) => last_bookmark = Some(bookmark),
-            Err(e) => return Err(e)?,
-        };
+    for type_count in type_iter {
+        match type_count? {
+            (Some(TYPE_FOLDER), count) => counts.folders = count,
+            (Some(TYPE_SEPARATOR), count) => counts.separators = count,
+            (_, count) => counts.links += count,
+        }
+    }
+    counts.places = conn.query_row("select count(*) from moz_places", params![], |row| {
+        row.get(0)
+    })?;
+    counts.origins = conn.query_row("select count(*) from moz_origins", params![], |row| {
+        row.get(0)
+    })?;
+
+    Ok(counts)
+}
+
+/// Fixes leftovers of partially failed syncs.
+///
+/// Recomputes `foreign_count` of places from bookmarks and keywords
+/// referencing them and removes origins without any places.
+/// Changes are made in a single transaction.
+pub fn repair_places(conn: &Connection) -> Result<RepairReport, FfError> {
+    in_transaction(conn, || repair_places_in_transaction(conn))
+}
+
+fn repair_places_in_transaction(conn: &Connection) -> Result<RepairReport, FfError> {
+    let references = foreign_references(conn)?;
+
+    // a place with only a keyword pointing to it is still in use
+    let orphaned_places = conn.query_row(
+        &format!(
+            "select count(*) from moz_places where foreign_count > 0 and {} = 0",
+            references
+        ),
+        params![],
+        |row| row.get::<_, i64>(0),
+    )? as usize;
+    let foreign_counts_fixed = conn.execute(
+        &format!(
+            "update moz_places set foreign_count = {0} where foreign_count != {0}",
+            references
+        ),
+        params![],
+    )?;
+    let removed_origins = conn.execute(
+        "
+            delete from moz_origins
+            where not exists (select 1 from moz_places p where p.origin_id = moz_origins.id)
+        ",
+        params![],
+    )?;
+
+    Ok(RepairReport {
+        orphaned_places,
+        foreign_counts_fixed,
+        removed_origins,
+    })
+}
+
+// expression counting rows that point to a place in `moz_places`, which
+// is what firefox keeps in its foreign_count, profiles may lack keywords
+pub(crate) fn foreign_references(conn: &Connection) -> Result<&'static str, FfError> {
+    Ok(if has_keywords(conn)? {
+        "(select count(*) from moz_bookmarks b where b.fk = moz_places.id)
+        + (select count(*) from moz_keywords k where k.place_id = moz_places.id)"
+    } else {
+        "(select count(*) from moz_bookmarks b where b.fk = moz_places.id)"
+    })
+}
+
+pub(crate) fn has_keywords(conn: &Connection) -> Result<bool, FfError> {
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        conn.query_row(
+            "select count(*) > 0 from sqlite_master where type = 'table' and name = 'moz_keywords'",
+            params![],
+            |row| row.get(0),
+        )
+    })
+}
+
+/// Guids of every bookmark of the profile but the root folders.
+pub fn get_bookmark_guids(profile_folder: &str) -> Result<Vec<String>, FfError> {
+    let conn = open_places(profile_folder, true)?;
+    let mut statement = conn.prepare(
+        "
+            select guid
+            from moz_bookmarks
+            where guid not in (?1, ?2, ?3, ?4, ?5, ?6)
+            order by id
+        ",
+    )?;
+
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        statement
+            .query_map(&ROOT_GUIDS, |row| row.get(0))?
+            .collect()
+    })
+}
+
+/// Whether every bookmark but the root folders is gone from the profile,
+/// while there were others up to `baseline`, the latest bookmark before launch.
+pub fn all_bookmarks_removed(profile_folder: &str, baseline: &Bookmark) -> Result<bool, FfError> {
+    // root folders come first, latest of them means there was nothing else
+    if baseline.guid.as_deref().is_none_or(is_root_guid) {
+        return Ok(false);
+    }
+
+    Ok(get_bookmark_guids(profile_folder)?.is_empty())
+}
+
+/// Removes bookmarks with the guids from the profile, root folders are kept.
+///
+/// Foreign counts of their places and positions of the bookmarks left in
+/// their folders are updated. Returns number of removed bookmarks.
+pub fn remove_bookmarks(profile_folder: &str, guids: &[String]) -> Result<usize, FfError> {
+    let conn = open_places(profile_folder, false)?;
+    in_transaction(&conn, || remove_bookmarks_in_transaction(&conn, guids))
+}
+
+fn remove_bookmarks_in_transaction(conn: &Connection, guids: &[String]) -> Result<usize, FfError> {
+    let mut removed = 0;
+    let mut places = vec![];
+    let mut parents = vec![];
+    // folders go once they are empty, so bookmarks added to them since are kept
+    let mut pending: Vec<_> = guids.iter().filter(|guid| !is_root_guid(guid)).collect();
+    loop {
+        let before = pending.len();
+        let mut left = vec![];
+        for guid in pending {
+            let row = conn
+                .query_row(
+                    "
+                        select id, fk, parent,
+                            (select count(*) from moz_bookmarks c where c.parent = b.id)
+                        from moz_bookmarks b
+                        where guid = ?1
+                    ",
+                    params![guid],
+                    |row| {
+                        Ok((
+                            row.get::<_, i64>(0)?,
+                            row.get::<_, Option<i64>>(1)?,
+                            row.get::<_, Option<i64>>(2)?,
+                            row.get::<_, i64>(3)?,
+                        ))
+                    },
+                )
+                .optional()?;
+            match row {
+                None => {}
+                Some((_, _, _, children)) if children > 0 => left.push(guid),
+                Some((id, fk, parent, _)) => {
+                    conn.execute("delete from moz_bookmarks where id = ?1", params![id])?;
+                    removed += 1;
+                    places.extend(fk);
+                    parents.extend(parent);
+                }
+            }
+        }
+        pending = left;
+        if pending.len() == before {
+            break;
+        }
+    }
+
+    let references = foreign_references(conn)?;
+    places.sort_unstable();
+    places.dedup();
+    for place in places {
+        conn.execute(
+            &format!(
+                "update moz_places set foreign_count = {} where id = ?1",
+                references
+            ),
+            params![place],
+        )?;
+    }
+    parents.sort_unstable();
+    parents.dedup();
+    for parent in parents {
+        let mut statement =
+            conn.prepare("select id from moz_bookmarks where parent = ?1 order by position, id")?;
+        let children = statement
+            .query_map(params![parent], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (position, id) in children.into_iter().enumerate() {
+            conn.execute(
+                "update moz_bookmarks set position = ?1 where id = ?2",
+                params![position as i64, id],
+            )?;
+        }
+    }
+
+    Ok(removed)
+}
+
+// frecency of the origin is the total of positive frecencies of its places
+pub(crate) const ORIGIN_FRECENCY_SQL: &str = "
+    update moz_origins
+    set frecency = (
+        select cast(total(p.frecency) as integer)
+        from moz_places p
+        where p.origin_id = moz_origins.id and p.frecency > 0
+    )
+    where id = ?1
+";
+
+/// Sets frecency of the origins to the sum of positive frecencies of their
+/// places, the way firefox derives it, so origins synced from another
+/// profile rank by the places they have in this one.
+pub fn recompute_origin_frecency(conn: &Connection, origin_ids: &[i64]) -> Result<(), FfError> {
+    let mut statement = conn.prepare_cached(ORIGIN_FRECENCY_SQL)?;
+    for origin_id in origin_ids {
+        with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+            statement.execute(params![origin_id])
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Runs sqlite quick check on the database, returns the problems it found.
+pub fn check_integrity(conn: &Connection) -> Result<Vec<String>, FfError> {
+    let mut statement = conn.prepare("pragma quick_check")?;
+    let rows = with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        statement
+            .query_map(params![], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    Ok(rows.into_iter().filter(|row| row != "ok").collect())
+}
+
+/// Moves everything of the WAL file into the database and truncates the WAL.
+pub fn checkpoint_wal(database: &Path) -> Result<(), FfError> {
+    let conn = Connection::open(database)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // returns busy flag and page counts, nothing to use here
+    conn.query_row("pragma wal_checkpoint(TRUNCATE)", params![], |_| Ok(()))?;
+
+    Ok(())
+}
+
+/// Copies places database of the profile with its WAL into `to`, and checkpoints
+/// the copy into a single file.
+///
+/// Firefox keeps the database of a running profile locked, a copy can still be read.
+pub fn snapshot_places(profile_folder: &Path, to: &Path) -> Result<(), FfError> {
+    let database_file = profile_folder.join(PLACES_FILE_NAME);
+    if !database_file.exists() {
+        return Err(FfError::PlacesDbMissing(database_file));
+    }
+    let wal_name = format!("{}-wal", PLACES_FILE_NAME);
+    let wal_file = profile_folder.join(&wal_name);
+    fs::copy(&database_file, to.join(PLACES_FILE_NAME))?;
+    if wal_file.exists() {
+        fs::copy(&wal_file, to.join(&wal_name))?;
+    }
+    checkpoint_wal(&to.join(PLACES_FILE_NAME))?;
+
+    Ok(())
+}
+
+/// Copies places database of the profile next to it, returns path of the copy.
+pub fn backup_places(profile_folder: &str) -> Result<PathBuf, FfError> {
+    let database_file = places_db_path(profile_folder);
+    let backup_file = database_file.with_extension("sqlite.fftemplates-backup");
+    fs::copy(&database_file, &backup_file)?;
+    // changes not yet checkpointed live in the write-ahead log
+    let wal_file = database_file.with_extension("sqlite-wal");
+    if wal_file.exists() {
+        fs::copy(
+            &wal_file,
+            backup_file.with_extension("fftemplates-backup-wal"),
+        )?;
+    }
+
+    Ok(backup_file)
+}
+
+fn build_node(
+    bookmark: Bookmark,
+    children: &mut HashMap<i64, Vec<Bookmark>>,
+    urls: &HashMap<i64, Option<String>>,
+) -> BookmarkNode {
+    match bookmark.r#type {
+        Some(TYPE_FOLDER) => {
+            // removing children from the map makes sure every folder is visited once,
+            // even if parents are messed up in the database
+            let mut folder_children = children.remove(&bookmark.id).unwrap_or_default();
+            folder_children.sort_by_key(|b| (b.position.is_none(), b.position, b.id));
+            BookmarkNode::Folder {
+                title: bookmark.title,
+                children: folder_children
+                    .into_iter()
+                    .map(|child| build_node(child, children, urls))
+                    .collect(),
+            }
+        }
+        Some(TYPE_SEPARATOR) => BookmarkNode::Separator,
+        _ => BookmarkNode::Link {
+            title: bookmark.title,
+            url: bookmark.fk.and_then(|fk| urls.get(&fk).cloned().flatten()),
+        },
     }
+}
 
-    Ok(last_bookmark)
+/// Every bookmark of the profile with places and origins they use,
+/// for merging whole profiles instead of entries added since a baseline.
+pub fn get_all_entries(profile_folder: &str, filter: &EntryFilter) -> Result<NewEntries, FfError> {
+    let conn = open_places(profile_folder, true)?;
+
+    get_all_entries_from(&SqlitePlacesStore::new(&conn), filter)
+}
+
+/// Same as `get_all_entries`, reading from any places store.
+pub fn get_all_entries_from<S: PlacesStore>(
+    store: &S,
+    filter: &EntryFilter,
+) -> Result<NewEntries, FfError> {
+    let all_bookmarks = match filter_bookmarks(store, store.all_bookmarks()?, filter)? {
+        None => return Ok((None, None, None)),
+        Some(all_bookmarks) => all_bookmarks,
+    };
+
+    let places = places_of(store, &all_bookmarks)?;
+    let origins = match &places {
+        None => None,
+        Some(places) => origins_of(store, places)?,
+    };
+
+    Ok((Some(all_bookmarks), places, origins))
 }
 
 pub fn get_new_entries(
     profile_folder: &str,
     first_bookmark: &Bookmark,
-) -> Result<
-    (
-        Option<Vec<Bookmark>>,
-        Option<HashMap<i64, Place>>,
-        Option<HashMap<i64, Origin>>,
-    ),
-    Box<dyn Error>,
-> {
-    let new_bookmarks = match get_bookmarks_between_two(profile_folder, first_bookmark) {
+    filter: &EntryFilter,
+) -> Result<NewEntries, FfError> {
+    let conn = open_places(profile_folder, true)?;
+
+    get_new_entries_from(&SqlitePlacesStore::new(&conn), first_bookmark, filter)
+}
+
+/// Same as `get_new_entries`, reading from any places store.
+pub fn get_new_entries_from<S: PlacesStore>(
+    store: &S,
+    first_bookmark: &Bookmark,
+    filter: &EntryFilter,
+) -> Result<NewEntries, FfError> {
+    let new_bookmarks = match bookmarks_between_two(store, first_bookmark) {
         Err(e) => {
-            return Err(format!("Error during get bookmarks between two : {}", e))?;
+            return Err(FfError::context(
+                "Error during get bookmarks between two",
+                e,
+            ));
         }
         Ok(new_bookmarks) => new_bookmarks,
     };
+    let new_bookmarks = match new_bookmarks {
+        None => None,
+        Some(new_bookmarks) => filter_bookmarks(store, new_bookmarks, filter)?,
+    };
     match new_bookmarks {
-        None => return Ok((None, None, None)),
+        None => Ok((None, None, None)),
         Some(new_bookmarks) => {
-            let new_places = match get_new_places(profile_folder, &new_bookmarks) {
+            let new_places = match places_of(store, &new_bookmarks) {
                 Err(e) => {
-                    return Err(format!("Error during get new places : {}", e))?;
+                    return Err(FfError::context("Error during get new places", e));
                 }
                 Ok(new_places) => new_places,
             };
 
             match new_places {
-                None => return Ok((Some(new_bookmarks), None, None)),
+                None => Ok((Some(new_bookmarks), None, None)),
                 Some(new_places) => {
-                    let new_origins = match get_new_origins(profile_folder, &new_places) {
+                    let new_origins = match origins_of(store, &new_places) {
                         Err(e) => {
-                            return Err(format!("Error during get new origins : {}", e))?;
+                            return Err(FfError::context("Error during get new origins", e));
                         }
                         Ok(new_origins) => new_origins,
                     };
 
                     match new_origins {
-                        None => return Ok((Some(new_bookmarks), Some(new_places), None)),
+                        None => Ok((Some(new_bookmarks), Some(new_places), None)),
                         Some(new_origins) => {
-                            return Ok((Some(new_bookmarks), Some(new_places), Some(new_origins)))
+                            Ok((Some(new_bookmarks), Some(new_places), Some(new_origins)))
                         }
-                    };
+                    }
                 }
-            };
+            }
         }
+    }
+}
+
+fn filter_bookmarks<S: PlacesStore>(
+    store: &S,
+    bookmarks: Vec<Bookmark>,
+    filter: &EntryFilter,
+) -> Result<Option<Vec<Bookmark>>, FfError> {
+    let bookmarks = match &filter.folder {
+        None => bookmarks,
+        Some(folder) => match filter_bookmarks_in_folder(store, bookmarks, folder) {
+            Err(e) => {
+                return Err(FfError::context(
+                    "Error during filter bookmarks by folder",
+                    e,
+                ));
+            }
+            Ok(None) => return Ok(None),
+            Ok(Some(bookmarks)) => bookmarks,
+        },
+    };
+    match filter.since {
+        None if bookmarks.is_empty() => Ok(None),
+        None => Ok(Some(bookmarks)),
+        Some(since) => Ok(filter_bookmarks_since(bookmarks, since)),
+    }
+}
+
+/// Keeps bookmarks added at or after the firefox timestamp, folders
+/// among the bookmarks stay too when something kept is inside them
+/// so the synced entries keep their structure.
+pub fn filter_bookmarks_since(bookmarks: Vec<Bookmark>, since: i64) -> Option<Vec<Bookmark>> {
+    let parents: HashMap<i64, Option<i64>> = bookmarks.iter().map(|b| (b.id, b.parent)).collect();
+    let mut keep = HashSet::new();
+    for bookmark in &bookmarks {
+        if bookmark
+            .date_added
+            .is_none_or(|date_added| date_added < since)
+        {
+            continue;
+        }
+        keep.insert(bookmark.id);
+        // limited by number of bookmarks like in is_in_folder
+        let mut current = bookmark.parent;
+        for _ in 0..parents.len() {
+            match current {
+                Some(id) if parents.contains_key(&id) && keep.insert(id) => {
+                    current = parents[&id];
+                }
+                _ => break,
+            }
+        }
+    }
+
+    let bookmarks: Vec<Bookmark> = bookmarks
+        .into_iter()
+        .filter(|bookmark| keep.contains(&bookmark.id))
+        .collect();
+    if bookmarks.is_empty() {
+        None
+    } else {
+        Some(bookmarks)
+    }
+}
+
+// keeps only bookmarks that have folder with the given title among their parents
+fn filter_bookmarks_in_folder<S: PlacesStore>(
+    store: &S,
+    bookmarks: Vec<Bookmark>,
+    folder_title: &str,
+) -> Result<Option<Vec<Bookmark>>, FfError> {
+    let folder_id = match store.find_folder_by_title(folder_title)? {
+        None => return Err(format!("No folder with title `{}` found", folder_title).into()),
+        Some(id) => id,
     };
+    let parents: HashMap<i64, Option<i64>> = store
+        .all_bookmarks()?
+        .into_iter()
+        .map(|bookmark| (bookmark.id, bookmark.parent))
+        .collect();
+
+    let bookmarks: Vec<Bookmark> = bookmarks
+        .into_iter()
+        .filter(|bookmark| is_in_folder(&parents, bookmark.parent, folder_id))
+        .collect();
+
+    if bookmarks.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(bookmarks))
+    }
+}
+
+// walks up the parent chain, number of steps is limited by number of bookmarks
+// so a cycle in a broken database doesn't loop forever
+fn is_in_folder(parents: &HashMap<i64, Option<i64>>, parent: Option<i64>, folder_id: i64) -> bool {
+    let mut current = parent;
+    for _ in 0..=parents.len() {
+        match current {
+            None => return false,
+            Some(id) if id == folder_id => return true,
+            Some(id) => current = parents.get(&id).cloned().flatten(),
+        }
+    }
+    false
 }
 
 pub fn get_bookmarks_between_two(
     profile_folder: &str,
     first_bookmark: &Bookmark,
-) -> Result<Option<Vec<Bookmark>>, Box<dyn Error>> {
-    let latest_bookmark = match get_latest_bookmark(profile_folder) {
-        Err(e) => return Err(e)?,
-        Ok(bookmark) => match bookmark {
-            // no bookmarks exist
-            // might be a case that all got deleted
-            // TODO: add deleted case
-            None => return Ok(None),
-            Some(bookmark) => bookmark,
-        },
+) -> Result<Option<Vec<Bookmark>>, FfError> {
+    let conn = open_places(profile_folder, true)?;
+
+    bookmarks_between_two(&SqlitePlacesStore::new(&conn), first_bookmark)
+}
+
+fn bookmarks_between_two<S: PlacesStore>(
+    store: &S,
+    first_bookmark: &Bookmark,
+) -> Result<Option<Vec<Bookmark>>, FfError> {
+    let latest_bookmark = match store.latest_bookmark()? {
+        // every bookmark is gone, see `all_bookmarks_removed`
+        None => return Ok(None),
+        Some(bookmark) => bookmark,
     };
 
     if first_bookmark.id >= latest_bookmark.id {
-        // either no new bookmarks, or bookmarks were deleted,
-        // which is not supported for now
-        // TODO: add deleted case
+        // either no new bookmarks, or bookmarks were removed,
+        // which only matters when all of them are
         return Ok(None);
     }
 
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+    let bookmarks = store.bookmarks_after(first_bookmark.id, latest_bookmark.id)?;
 
-    let mut statement = conn.prepare(
-        "
-            select
-                id, type, fk, parent, position, title, keyword_id,
-                folder_type, dateAdded, lastModified, guid, syncStatus, syncChangeCounter
-            from moz_bookmarks
-            where 1=1
-            and id > :low_id
-            and id <= :high_id
-            order by id",
-    )?;
-    let bookmark_iter = statement.query_map_named(
-        &[
-            (":low_id", &first_bookmark.id),
-            (":high_id", &latest_bookmark.id),
-        ],
-        |row| {
-            Ok(Bookmark {
-                id: row.get(0)?,
-                r#type: row.get(1)?,
-                fk: row.get(2)?,
-                parent: row.get(3)?,
-                position: row.get(4)?,
-                title: row.get(5)?,
-                keyword_id: row.get(6)?,
-                folder_type: row.get(7)?,
-                date_added: row.get(8)?,
-                last_modified: row.get(9)?,
-                guid: row.get(10)?,
-                sync_status: row.get(11)?,
-                sync_change_counter: row.get(12)?,
-            })
-        },
-    )?;
-
-    let mut bookmarks = vec![];
-    for bookmark in bookmark_iter {
-        match bookmark {
-            Ok(bookmark) => {
-                bookmarks.push(bookmark);
-            }
-            Err(e) => return Err(e)?,
-        };
-    }
-
-    if bookmarks.len() == 0 {
+    if bookmarks.is_empty() {
         Ok(None)
     } else {
         Ok(Some(bookmarks))
@@ -220,61 +1023,36 @@ pub fn get_bookmarks_between_two(
 pub fn get_new_places(
     profile_folder: &str,
     bookmarks: &[Bookmark],
-) -> Result<Option<HashMap<i64, Place>>, Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+) -> Result<Option<HashMap<i64, Place>>, FfError> {
+    let conn = open_places(profile_folder, true)?;
 
-    let mut statement = conn.prepare(
-        "
-            select
-                id, url, title, rev_host, visit_count, hidden,
-                typed, favicon_id, frecency, last_visit_date, 
-                guid, foreign_count, url_hash, description, preview_image_url, origin_id
-            from moz_places
-            where 1=1
-            and id = :places_id
-            order by id desc
-        ",
-    )?;
+    places_of(&SqlitePlacesStore::new(&conn), bookmarks)
+}
 
-    let mut places = HashMap::new();
-    for bookmark in bookmarks {
-        let places_id = match bookmark.fk {
-            None => continue,
-            Some(v) => v,
-        };
+// places the bookmarks point to, `None` if there are none
+fn places_of<S: PlacesStore>(
+    store: &S,
+    bookmarks: &[Bookmark],
+) -> Result<Option<HashMap<i64, Place>>, FfError> {
+    let places_ids: Vec<i64> = bookmarks.iter().filter_map(|b| b.fk).collect();
+    let places = store.places_for(&places_ids)?;
 
-        let places_iter = statement.query_map_named(&[(":places_id", &places_id)], |row| {
-            Ok(Place {
-                id: row.get(0)?,
-                url: row.get(1)?,
-                title: row.get(2)?,
-                rev_host: row.get(3)?,
-                visit_count: row.get(4)?,
-                hidden: row.get(5)?,
-                typed: row.get(6)?,
-                favicon_id: row.get(7)?,
-                frecency: row.get(8)?,
-                last_visit_date: row.get(9)?,
-                guid: row.get(10)?,
-                foreign_count: row.get(11)?,
-                url_hash: row.get(12)?,
-                description: row.get(13)?,
-                preview_image_url: row.get(14)?,
-                origin_id: row.get(15)?,
-            })
-        })?;
-        for place in places_iter {
-            match place {
-                Ok(place) => {
-                    places.insert(places_id, place);
-                }
-                Err(e) => return Err(e)?,
-            };
-        }
+    if places.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(places))
     }
+}
 
-    if places.len() == 0 {
+/// Places of the profile with the given ids, ids without a place are skipped.
+pub fn get_places(
+    profile_folder: &str,
+    places_ids: &[i64],
+) -> Result<Option<HashMap<i64, Place>>, FfError> {
+    let conn = open_places(profile_folder, true)?;
+    let places = SqlitePlacesStore::new(&conn).places_for(places_ids)?;
+
+    if places.is_empty() {
         Ok(None)
     } else {
         Ok(Some(places))
@@ -284,304 +1062,237 @@ pub fn get_new_places(
 pub fn get_new_origins(
     profile_folder: &str,
     places: &HashMap<i64, Place>,
-) -> Result<Option<HashMap<i64, Origin>>, Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
-
-    let mut statement = conn.prepare(
-        "
-            select
-                id, prefix, host, frecency
-            from moz_origins
-            where 1=1
-            and id = :origin_id
-            order by id desc
-        ",
-    )?;
+) -> Result<Option<HashMap<i64, Origin>>, FfError> {
+    let conn = open_places(profile_folder, true)?;
 
-    let mut origins = HashMap::new();
-    for place in places.values() {
-        let origin_id = match place.origin_id {
-            None => continue,
-            Some(v) => v,
-        };
+    origins_of(&SqlitePlacesStore::new(&conn), places)
+}
 
-        let origins_iter = statement.query_map_named(&[(":origin_id", &origin_id)], |row| {
-            Ok(Origin {
-                id: row.get(0)?,
-                prefix: row.get(1)?,
-                host: row.get(2)?,
-                frecency: row.get(3)?,
-            })
-        })?;
-        for origin in origins_iter {
-            match origin {
-                Ok(origin) => {
-                    origins.insert(origin_id, origin);
-                }
-                Err(e) => return Err(e)?,
-            };
-        }
-    }
+// origins of the places, `None` if there are none
+fn origins_of<S: PlacesStore>(
+    store: &S,
+    places: &HashMap<i64, Place>,
+) -> Result<Option<HashMap<i64, Origin>>, FfError> {
+    let origin_ids: Vec<i64> = places.values().filter_map(|p| p.origin_id).collect();
+    let origins = store.origins_for(&origin_ids)?;
 
-    if origins.len() == 0 {
+    if origins.is_empty() {
         Ok(None)
     } else {
         Ok(Some(origins))
     }
 }
 
+/// Syncs new entries into the profile.
+///
+/// Changes are planned first and logged before anything is written.
 pub fn insert_new_entries(
     profile_folder: &str,
-    new_bookmarks: Option<&mut Vec<Bookmark>>,
-    mut new_places: Option<&mut HashMap<i64, Place>>,
-    mut new_origins: Option<&mut HashMap<i64, Origin>>,
-) -> Result<(), Box<dyn Error>> {
-    if let Some(ref mut new_origins) = new_origins {
-        if let Err(e) = insert_new_origins(profile_folder, new_origins) {
-            eprintln!("Error during insert new origins : {}", e);
-        }
-    }
-    // hack to transform Option<&mut ...> into Option<&...>
-    let new_origins = match new_origins {
-        None => None,
-        Some(v) => Some(&*v),
-    };
-    if let Some(ref mut new_places) = new_places {
-        if let Err(e) = insert_new_places(profile_folder, new_places, new_origins) {
-            eprintln!("Error during insert new places : {}", e);
-        }
-    }
-    // hack to transform Option<&mut ...> into Option<&...>
-    let new_places = match new_places {
-        None => None,
-        Some(v) => Some(&*v),
-    };
-    if let Some(mut new_bookmarks) = new_bookmarks {
-        if let Err(e) = insert_new_bookmarks(profile_folder, &mut new_bookmarks, new_places) {
-            eprintln!("Error during insert new bookmarks : {}", e);
-        }
-    }
-
-    Ok(())
+    new_bookmarks: Option<&[Bookmark]>,
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+) -> Result<SyncPlan, FfError> {
+    insert_new_entries_into(
+        profile_folder,
+        new_bookmarks,
+        new_places,
+        new_origins,
+        None,
+        ConflictPolicy::Skip,
+    )
 }
 
-pub fn insert_new_bookmarks(
+/// Syncs new entries into the profile, top level ones into `target_folder`
+/// when it is given, conflicts are resolved with `on_conflict`,
+/// see `sync::plan_sync_into`.
+///
+/// Sync is a single transaction, made while holding the sync lock of the profile.
+pub fn insert_new_entries_into(
     profile_folder: &str,
-    new_bookmarks: &mut [Bookmark],
+    new_bookmarks: Option<&[Bookmark]>,
     new_places: Option<&HashMap<i64, Place>>,
-) -> Result<(), Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+    new_origins: Option<&HashMap<i64, Origin>>,
+    target_folder: Option<&str>,
+    on_conflict: ConflictPolicy,
+) -> Result<SyncPlan, FfError> {
+    // released when dropped, after the transaction is over
+    let _lock = lock_for_sync(profile_folder)?;
+    let conn = open_places(profile_folder, false)?;
 
-    // not doing a check for duplicate, assuming this will not happened
+    // ids are planned from the rows in the target, nothing can be added in between
+    in_transaction(&conn, || {
+        insert_new_entries_into_store(
+            &SqlitePlacesStore::new(&conn),
+            new_bookmarks,
+            new_places,
+            new_origins,
+            target_folder,
+            on_conflict,
+        )
+    })
+}
 
-    let mut max_id_statement = conn.prepare(
-        "
-            select max(id) from moz_bookmarks;
-        ",
+/// Statements syncing new entries into the profile would run, in order,
+/// with their values filled in. Nothing is written to the profile.
+pub fn new_entries_sql(
+    profile_folder: &str,
+    new_bookmarks: Option<&[Bookmark]>,
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+    target_folder: Option<&str>,
+    on_conflict: ConflictPolicy,
+) -> Result<Vec<String>, FfError> {
+    let conn = open_places(profile_folder, true)?;
+    let store = SqlitePlacesStore::recording(&conn);
+    insert_new_entries_into_store(
+        &store,
+        new_bookmarks,
+        new_places,
+        new_origins,
+        target_folder,
+        on_conflict,
     )?;
 
-    for bookmark in new_bookmarks.iter_mut() {
-        // get max id in the table just in case something was already inserted
-        let max_id = max_id_statement.query_map(params![], |row| row.get(0))?;
-        for max_id in max_id {
-            let max_id = match max_id {
-                Err(e) => return Err(e)?,
-                Ok(max_id) => max_id,
-            };
-            // check if current max id is not the one
-            // before inserting current entry
-            if max_id != bookmark.id - 1 {
-                bookmark.id = max_id;
-                bookmark.id += 1;
-            }
-        }
-
-        if let Some(new_places) = new_places {
-            if let Some(fk) = bookmark.fk {
-                bookmark.fk = match new_places.get(&fk) {
-                    None => return Err("unable to find fk place from bookmark")?,
-                    Some(v) => Some(v.id),
-                };
-            }
-        }
+    Ok(store.recorded_statements())
+}
 
-        conn.execute(
-            "
-                insert  into moz_bookmarks (
-                    id, type, fk, parent, position,
-                    title, keyword_id, folder_type, dateAdded, lastModified,
-                    guid, syncStatus, syncChangeCounter)
-                values(
-                    ?1, ?2, ?3, ?4, ?5,
-                    ?6, ?7, ?8, ?9, ?10,
-                    ?11, ?12, ?13)
-            ",
-            params![
-                bookmark.id,
-                bookmark.r#type,
-                bookmark.fk,
-                bookmark.parent,
-                bookmark.position,
-                bookmark.title,
-                bookmark.keyword_id,
-                bookmark.folder_type,
-                bookmark.date_added,
-                bookmark.last_modified,
-                bookmark.guid,
-                bookmark.sync_status,
-                bookmark.sync_change_counter
-            ],
-        )?;
-    }
+// waits until no other sync into the profile holds the lock,
+// the file is left in place as removing it would race with the waiting ones
+pub(crate) fn lock_for_sync(profile_folder: &str) -> Result<File, FfError> {
+    let path = Path::new(profile_folder).join(SYNC_LOCK_FILE_NAME);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| FfError::context(&format!("Unable to open `{}`", path.display()), e))?;
+    file.lock()
+        .map_err(|e| FfError::context(&format!("Unable to lock `{}`", path.display()), e))?;
 
-    Ok(())
+    Ok(file)
 }
 
-pub fn insert_new_places(
-    profile_folder: &str,
-    new_places: &mut HashMap<i64, Place>,
+/// Same as `insert_new_entries_into`, writing into any places store.
+pub fn insert_new_entries_into_store<S: PlacesStore>(
+    store: &S,
+    new_bookmarks: Option<&[Bookmark]>,
+    new_places: Option<&HashMap<i64, Place>>,
     new_origins: Option<&HashMap<i64, Origin>>,
-) -> Result<(), Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+    target_folder: Option<&str>,
+    on_conflict: ConflictPolicy,
+) -> Result<SyncPlan, FfError> {
+    let plan = sync::plan_sync_into(
+        store,
+        new_bookmarks.unwrap_or_default(),
+        new_places,
+        new_origins,
+        target_folder,
+        on_conflict,
+    )?;
+    info!("{}", plan);
+    for conflict in &plan.conflicts {
+        let resolution = match on_conflict {
+            ConflictPolicy::Skip => "keeping the one of the target, see --on-conflict",
+            ConflictPolicy::Overwrite => "replacing the one of the target",
+            ConflictPolicy::KeepBoth => "adding it next to the one of the target",
+        };
+        warn!(
+            "Bookmark `{}` was changed in both profiles, `{}` {} in the target, {}",
+            conflict.title.as_deref().unwrap_or_default(),
+            conflict.target_title.as_deref().unwrap_or_default(),
+            conflict.target_url.as_deref().unwrap_or_default(),
+            resolution
+        );
+    }
+    sync::apply_sync_plan(store, &plan)?;
 
-    // not doing a check for duplicate, as it seems unlikely to have duplicate
+    Ok(plan)
+}
 
-    let mut max_id_statement = conn.prepare(
-        "
-            select max(id) from moz_places;
-        ",
+/// Id of the first folder with the title, `None` if there is none.
+pub fn find_folder_by_title(conn: &Connection, title: &str) -> Result<Option<i64>, FfError> {
+    let mut statement = conn.prepare_cached(
+        "select id from moz_bookmarks where type = ?1 and title = ?2 order by id limit 1",
     )?;
-    for place in new_places.values_mut() {
-        // get max id in the table just in case something was already inserted
-        let max_id = max_id_statement.query_map(params![], |row| row.get(0))?;
-        for max_id in max_id {
-            let max_id = match max_id {
-                Err(e) => return Err(e)?,
-                Ok(max_id) => max_id,
-            };
-            // check if current max id is not the one
-            // before inserting current entry
-            if max_id != place.id - 1 {
-                place.id = max_id;
-                place.id += 1;
-            }
-        }
 
-        // check to see if origin had it's id changed
-        // this can happened if a different origin was inserted
-        // with an id of current origin and place needs to match to
-        // the correct origin
-        if let Some(new_origins) = new_origins {
-            if let Some(origin_id) = place.origin_id {
-                place.origin_id = match new_origins.get(&origin_id) {
-                    None => return Err("unable to find origin from place")?,
-                    Some(v) => Some(v.id),
-                };
-            }
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        let mut rows = statement.query(params![TYPE_FOLDER, title])?;
+        match rows.next()? {
+            None => Ok(None),
+            Some(row) => Ok(Some(row.get(0)?)),
         }
+    })
+}
 
-        conn.execute(
-            "insert into moz_places (id, url, title, rev_host,
-                visit_count, hidden, typed, favicon_id,
-                frecency, last_visit_date, guid, foreign_count,
-                url_hash, description, preview_image_url, origin_id)
-            values(?1, ?2, ?3, ?4,
-                ?5, ?6, ?7, ?8,
-                ?9, ?10, ?11, ?12,
-                ?13, ?14, ?15, ?16)",
-            params![
-                place.id,
-                place.url,
-                place.title,
-                place.rev_host,
-                place.visit_count,
-                place.hidden,
-                place.typed,
-                place.favicon_id,
-                place.frecency,
-                place.last_visit_date,
-                place.guid,
-                place.foreign_count,
-                place.url_hash,
-                place.description,
-                place.preview_image_url,
-                place.origin_id
-            ],
-        )?;
+/// Position after the last child of the folder.
+pub fn next_child_position(conn: &Connection, folder_id: i64) -> Result<i64, FfError> {
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        conn.query_row(
+            "select coalesce(max(position), -1) + 1 from moz_bookmarks where parent = ?1",
+            params![folder_id],
+            |row| row.get(0),
+        )
+    })
+}
+
+/// Folder that isn't in any profile yet, with a new guid.
+pub fn new_folder(id: i64, parent: i64, position: i64, title: &str) -> Bookmark {
+    let now = timestamps::to_firefox_timestamp(SystemTime::now());
+    Bookmark {
+        id,
+        r#type: Some(TYPE_FOLDER),
+        fk: None,
+        parent: Some(parent),
+        position: Some(position),
+        title: Some(title.to_string()),
+        keyword_id: None,
+        folder_type: None,
+        date_added: Some(now),
+        last_modified: Some(now),
+        guid: Some(generate_guid()),
+        sync_status: SYNC_STATUS_NEW,
+        sync_change_counter: 1,
+        parent_guid: None,
     }
+}
 
-    Ok(())
+// guids are 12 characters of url safe base64, like firefox makes them
+pub(crate) fn generate_guid() -> String {
+    const GUID_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut guid = String::with_capacity(12);
+    // std has no random generator, but RandomState is seeded randomly,
+    // 64 bits of a hash are enough for 10 characters, so two are used
+    for _ in 0..2 {
+        let mut value = RandomState::new().build_hasher().finish();
+        for _ in 0..6 {
+            guid.push(GUID_CHARS[(value % 64) as usize] as char);
+            value /= 64;
+        }
+    }
+
+    guid
 }
 
-pub fn insert_new_origins(
-    profile_folder: &str,
-    new_origins: &mut HashMap<i64, Origin>,
-) -> Result<(), Box<dyn Error>> {
-    let database_file = Path::new(profile_folder).join(Path::new("places.sqlite"));
-    let conn = Connection::open(database_file)?;
+/// Checks if guid belongs to one of the built-in root folders.
+pub fn is_root_guid(guid: &str) -> bool {
+    ROOT_GUIDS.contains(&guid)
+}
 
+// ids of the root folders of the profile by their guid
+pub(crate) fn root_folder_ids(conn: &Connection) -> Result<HashMap<String, i64>, FfError> {
     let mut statement = conn.prepare(
         "
-            select id
-            from moz_origins
-            where 1=1
-            and prefix = :prefix
-            and host = :host
-            and frecency = :frecency
-        ",
-    )?;
-    let mut max_id_statement = conn.prepare(
-        "
-            select max(id) from moz_origins;
+            select guid, id
+            from moz_bookmarks
+            where guid in (?1, ?2, ?3, ?4, ?5, ?6)
         ",
     )?;
 
-    for origin in new_origins.values_mut() {
-        // get new id for this origin, if it already exists
-        let results = statement.query_map_named(
-            &[
-                (":prefix", &origin.prefix),
-                (":host", &origin.host),
-                (":frecency", &origin.frecency),
-            ],
-            |row| row.get(0),
-        )?;
-        let mut new_id: Option<i64> = None;
-        for result in results {
-            match result {
-                Err(e) => return Err(e)?,
-                Ok(result) => new_id = Some(result),
-            };
-        }
-        if let Some(new_id) = new_id {
-            origin.id = new_id;
-        } else {
-            // get max id in the table just in case something was already inserted
-            let max_id = max_id_statement.query_map(params![], |row| row.get(0))?;
-            for max_id in max_id {
-                let max_id = match max_id {
-                    Err(e) => return Err(e)?,
-                    Ok(max_id) => max_id,
-                };
-                // check if current max id is not the one
-                // before inserting current entry
-                if max_id != origin.id - 1 {
-                    origin.id = max_id;
-                    origin.id += 1;
-                }
-            }
-
-            // insert in the case that origin doesn't exist
-            conn.execute(
-                "insert into moz_origins (id, prefix, host, frecency)
-                values(?1, ?2, ?3, ?4)",
-                params![origin.id, origin.prefix, origin.host, origin.frecency],
-            )?;
-        }
-    }
-
-    Ok(())
+    with_busy_retry(BUSY_RETRY_ATTEMPTS, || {
+        statement
+            .query_map(&ROOT_GUIDS, |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect()
+    })
 }
@@ -0,0 +1,66 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Browser launched when none is given.
+pub const DEFAULT_BROWSER: &str = "firefox";
+
+// id flatpak installs firefox under
+const FLATPAK_FIREFOX_ID: &str = "org.mozilla.firefox";
+// system wide installs of flatpak apps, user installs are in the data dir
+const FLATPAK_SYSTEM_APPS: &str = "/var/lib/flatpak/app";
+
+/// Known installs of firefox, probed in order when `firefox` is not in PATH.
+///
+/// Each one is the start of the command that launches firefox.
+pub const FIREFOX_INSTALLS: &[&[&str]] = &[
+    &["/usr/bin/firefox"],
+    &["/snap/bin/firefox"],
+    &["flatpak", "run", FLATPAK_FIREFOX_ID],
+    &["/opt/firefox/firefox"],
+];
+
+/// Command of the first known firefox install that exists, `None` if there is none.
+pub fn resolve_browser_binary() -> Option<Vec<String>> {
+    resolve_browser_binary_from(FIREFOX_INSTALLS)
+}
+
+/// Same as `resolve_browser_binary`, probing only the given installs.
+pub fn resolve_browser_binary_from(installs: &[&[&str]]) -> Option<Vec<String>> {
+    installs
+        .iter()
+        .find(|install| is_installed(install))
+        .map(|install| install.iter().map(|arg| arg.to_string()).collect())
+}
+
+// flatpak in PATH only means firefox can be installed with it
+fn is_installed(install: &[&str]) -> bool {
+    match install {
+        ["flatpak", "run", app] => {
+            find_in_path("flatpak").is_some()
+                && flatpak_app_dirs().iter().any(|dir| dir.join(app).is_dir())
+        }
+        [program, ..] => find_in_path(program).is_some(),
+        [] => false,
+    }
+}
+
+fn flatpak_app_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from(FLATPAK_SYSTEM_APPS)];
+    if let Some(data) = dirs::data_dir() {
+        dirs.push(data.join("flatpak").join("app"));
+    }
+    dirs
+}
+
+/// Path of the program, paths are checked as is, names are looked up in PATH.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(program);
+        return if path.is_file() { Some(path) } else { None };
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
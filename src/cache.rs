@@ -0,0 +1,136 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::fs::TryLockError;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use crate::error::FfError;
+use crate::paths;
+
+const COPIES_DIR_NAME: &str = "copies";
+const FINGERPRINT_FILE_NAME: &str = "fingerprint";
+const PROFILE_DIR_NAME: &str = "profile";
+const STABLE_DIR_NAME: &str = "stable";
+const STABLE_LOCK_SUFFIX: &str = ".lock";
+
+/// Hashes relative paths, sizes and modification times of every file of the profile.
+///
+/// Top level entries with a name from `ignore` are skipped, just like when copying.
+/// Contents are not read, so this is cheap even for big profiles.
+pub fn profile_fingerprint(profile_dir: &Path, ignore: &HashSet<String>) -> Result<u64, FfError> {
+    let mut files = vec![];
+    for entry in fs::read_dir(profile_dir)? {
+        let entry = entry?;
+        if ignore.contains(entry.file_name().to_string_lossy().as_ref()) {
+            continue;
+        }
+        collect_files(&entry.path(), &mut files)?;
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        let metadata = fs::metadata(&file)?;
+        file.strip_prefix(profile_dir)
+            .unwrap_or(&file)
+            .hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        if let Ok(modified) = metadata.modified() {
+            // times before the epoch are hashed as an error, which is stable as well
+            modified.duration_since(UNIX_EPOCH).ok().hash(&mut hasher);
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), FfError> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        collect_files(&entry?.path(), files)?;
+    }
+
+    Ok(())
+}
+
+/// Folder in the cache dir holding a kept copy of the source profile.
+pub fn copy_dir(source_profile: &Path) -> Result<PathBuf, FfError> {
+    let mut hasher = DefaultHasher::new();
+    source_profile.hash(&mut hasher);
+
+    Ok(paths::cache_dir()?
+        .join(COPIES_DIR_NAME)
+        .join(format!("{:016x}", hasher.finish())))
+}
+
+/// Profile folder of a cached copy.
+pub fn copy_profile_dir(copy_dir: &Path) -> PathBuf {
+    copy_dir.join(PROFILE_DIR_NAME)
+}
+
+/// Fingerprint of the source profile at the time the copy was made,
+/// `None` if there is no usable copy.
+pub fn read_fingerprint(copy_dir: &Path) -> Option<u64> {
+    if !copy_profile_dir(copy_dir).is_dir() {
+        return None;
+    }
+    let content = fs::read_to_string(copy_dir.join(FINGERPRINT_FILE_NAME)).ok()?;
+    content.trim().parse().ok()
+}
+
+pub fn write_fingerprint(copy_dir: &Path, fingerprint: u64) -> Result<(), FfError> {
+    fs::create_dir_all(copy_dir)?;
+    paths::write_atomic(
+        copy_dir.join(FINGERPRINT_FILE_NAME),
+        fingerprint.to_string().as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// Fixed folder in the cache dir the profile with this name is launched
+/// from with `--stable-path`, the same for every launch of the name.
+pub fn stable_profile_dir(profile_name: &str) -> Result<PathBuf, FfError> {
+    let mut name = profile_name.replace(['/', '\\'], "_");
+    // `..` would be the cache dir itself
+    if name.chars().all(|c| c == '.') {
+        name.insert(0, '_');
+    }
+
+    Ok(paths::cache_dir()?.join(STABLE_DIR_NAME).join(name))
+}
+
+/// Stable folder of the profile name with the lock that keeps other launches
+/// of the name out of it until the file is dropped.
+///
+/// `None` when another launch holds the lock.
+pub fn lock_stable_profile_dir(profile_name: &str) -> Result<Option<(PathBuf, File)>, FfError> {
+    let dir = stable_profile_dir(profile_name)?;
+    let mut lock_name = dir.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(STABLE_LOCK_SUFFIX);
+    let lock_path = dir.with_file_name(lock_name);
+    fs::create_dir_all(dir.parent().unwrap_or(&dir))?;
+    // left in place, removing it would race with the next launch
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| FfError::context(&format!("Unable to open `{}`", lock_path.display()), e))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some((dir, file))),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(FfError::context(
+            &format!("Unable to lock `{}`", lock_path.display()),
+            e,
+        )),
+    }
+}
@@ -0,0 +1,1063 @@
+use clap::App;
+use clap::Arg;
+use clap::ArgMatches;
+
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::browser;
+use crate::error::FfError;
+use crate::profiles;
+use crate::session;
+use crate::session::SessionLoadMode;
+use crate::timestamps;
+
+/// Environment variable with the folder containing firefox profiles.
+pub const PROFILE_ROOT_VARIABLE: &str = "FFTEMPLATES_PROFILE_ROOT";
+
+/// Command line arguments as they were given, before any validation.
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    pub base_profile: Option<String>,
+    pub bookmarks_sync: bool,
+    pub sync_deletions: bool,
+    pub dry_run_sql: bool,
+    pub load_session: Option<String>,
+    pub save_session: Option<String>,
+    pub save_load_session: Option<String>,
+    pub session_prompt: bool,
+    pub session_prompt_skip_load: bool,
+    pub session_prompt_skip_save: bool,
+    pub timeout: Option<String>,
+    pub watch: Option<String>,
+    pub sync_containers: bool,
+    pub attach: bool,
+    pub clone: Option<String>,
+    pub tree: bool,
+    pub depth: Option<String>,
+    pub no_crash_session: bool,
+    pub always_save: bool,
+    pub no_session_pref_changes: bool,
+    pub list_sessions: Option<String>,
+    pub count: bool,
+    pub session_target: Option<String>,
+    pub replace_session: bool,
+    pub merge_session: bool,
+    pub append_session: bool,
+    pub sync_folder: Option<String>,
+    pub since: Option<String>,
+    pub target_folder: Option<String>,
+    pub on_conflict: Option<String>,
+    pub keep_temp: bool,
+    pub list_files: bool,
+    pub diff_session: Option<Vec<String>>,
+    pub check_session: Option<String>,
+    pub compare_extensions: Option<String>,
+    pub list_extensions: bool,
+    pub export_session: Option<Vec<String>>,
+    pub import_session: Option<Vec<String>>,
+    pub browser: Option<String>,
+    pub wrapper: Option<String>,
+    pub sync_target: Option<String>,
+    pub cache_copy: bool,
+    pub stage: Option<String>,
+    pub stable_path: bool,
+    pub env: Vec<String>,
+    pub print_command: bool,
+    pub dump_prefs: bool,
+    pub overlay_overrides: bool,
+    pub profile_root: Option<String>,
+    pub profile_path: Option<String>,
+    pub profile_index: Option<String>,
+    pub repair: Option<String>,
+    pub touch_session: bool,
+    pub progress: bool,
+    pub merge_bookmarks: Option<Vec<String>>,
+    pub preview_session: bool,
+    pub list: bool,
+    pub force: bool,
+    pub dry_run: bool,
+    pub session_dir: Option<String>,
+    pub sync_history: bool,
+    pub temp_name: Option<String>,
+    pub no_interactive: bool,
+    pub load_extensions: Vec<String>,
+    pub no_extension_rewrite: bool,
+    pub quiet_firefox: bool,
+    pub scrub: bool,
+    pub init_config: bool,
+}
+
+/// Session files loaded into the profile before and saved from it after the run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionMode {
+    None,
+    Load(String),
+    Save(String),
+    // session is loaded from and saved back to the same file
+    LoadAndSave(String),
+    // session is loaded from the first file and saved to the second one
+    LoadAndSaveTo(String, String),
+}
+
+impl SessionMode {
+    pub fn load_file(&self) -> Option<&str> {
+        match self {
+            SessionMode::Load(file)
+            | SessionMode::LoadAndSave(file)
+            | SessionMode::LoadAndSaveTo(file, _) => Some(file),
+            SessionMode::None | SessionMode::Save(_) => None,
+        }
+    }
+
+    pub fn save_file(&self) -> Option<&str> {
+        match self {
+            SessionMode::Save(file)
+            | SessionMode::LoadAndSave(file)
+            | SessionMode::LoadAndSaveTo(_, file) => Some(file),
+            SessionMode::None | SessionMode::Load(_) => None,
+        }
+    }
+
+    /// Whether a missing file to load is an error.
+    ///
+    /// The shared file of `LoadAndSave` may not exist yet, it is created by the run.
+    pub fn fail_if_load_missing(&self) -> bool {
+        !matches!(self, SessionMode::LoadAndSave(_))
+    }
+}
+
+/// What happens to a synced bookmark that was changed in the target as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    // bookmark of the target stays as it is
+    #[default]
+    Skip,
+    // title and url of the synced bookmark replace the ones of the target
+    Overwrite,
+    // synced bookmark is added next to it as a new one
+    KeepBoth,
+}
+
+/// Settings of a single run, resolved from `Args`.
+pub struct Config {
+    pub profile_name: String,
+    pub profile_folder: PathBuf,
+    // where profiles are looked up by name, in order, `profile_folder` first
+    pub profile_roots: Vec<PathBuf>,
+    pub bookmarks_sync: bool,
+    // bookmarks removed from the temp profile are removed from the target as well
+    pub sync_deletions: bool,
+    // sync and merge print their statements instead of writing to the target
+    pub dry_run_sql: bool,
+    pub session_mode: SessionMode,
+    pub session_prompt: bool,
+    pub session_prompt_load_skip: bool,
+    pub session_prompt_save_skip: bool,
+    pub timeout: Option<Duration>,
+    // how often new bookmarks are synced while firefox runs
+    pub watch: Option<Duration>,
+    pub sync_containers: bool,
+    pub attach: bool,
+    pub clone_name: Option<String>,
+    pub tree: bool,
+    pub tree_depth: Option<usize>,
+    pub no_crash_session: bool,
+    // save session even if it has the same tabs as the saved file
+    pub always_save: bool,
+    // prefs.js is left as is when a session is loaded or saved
+    pub no_session_pref_changes: bool,
+    pub list_sessions: Option<PathBuf>,
+    pub count: bool,
+    pub session_target: Option<String>,
+    pub session_load_mode: SessionLoadMode,
+    pub sync_folder: Option<String>,
+    // firefox timestamp, bookmarks added before it aren't synced
+    pub since: Option<i64>,
+    // folder synced or merged bookmarks are put into in the target
+    pub target_folder: Option<String>,
+    // bookmarks changed in both profiles
+    pub on_conflict: ConflictPolicy,
+    pub keep_temp: bool,
+    pub list_files: bool,
+    pub diff_session: Option<(PathBuf, PathBuf)>,
+    pub check_session: Option<PathBuf>,
+    // profile, by name or path, extensions of the base profile are compared with
+    pub compare_extensions: Option<String>,
+    // print extensions of the base profile
+    pub list_extensions: bool,
+    // session file and json file it is written to as readable json
+    pub export_session: Option<(PathBuf, PathBuf)>,
+    // json file and session file it is compressed into
+    pub import_session: Option<(PathBuf, PathBuf)>,
+    pub browser_binary: String,
+    // command the browser is launched under, with its arguments
+    pub wrapper: Vec<String>,
+    pub sync_target: Option<String>,
+    pub cache_copy: bool,
+    // persistent folder the profile is copied into and launched from
+    pub stage: Option<PathBuf>,
+    // profile is launched from a fixed folder in the cache dir named after it
+    pub stable_path: bool,
+    // environment variables set for the browser
+    pub env: Vec<(String, String)>,
+    // prepare the profile and print the launch command instead of running it
+    pub print_command: bool,
+    // prepare the profile and print how its prefs were adjusted instead of running it
+    pub dump_prefs: bool,
+    // prefs of the overlay file replace the ones set in the profile
+    pub overlay_overrides: bool,
+    // profile folder used directly, without looking it up by name
+    pub profile_path: Option<PathBuf>,
+    // N of the `[ProfileN]` section of profiles.ini to run, instead of the profile name
+    pub profile_index: Option<usize>,
+    // name of the profile to repair places database of
+    pub repair: Option<String>,
+    // loaded session gets current time as mtime, so firefox doesn't see it as stale
+    pub touch_session: bool,
+    // draw copy progress bar even when stdout is not a terminal
+    pub progress: bool,
+    // names of the source and destination profiles
+    pub merge_bookmarks: Option<(String, String)>,
+    // print tabs of the session to load instead of launching
+    pub preview_session: bool,
+    // print profiles of every known browser
+    pub list: bool,
+    // browser the profile belongs to, taken from --browser or the template
+    pub browser_name: Option<String>,
+    // profiles are looked up in known locations of every browser,
+    // unless the profiles folder was given explicitly
+    pub discover_profiles: bool,
+    // failed safety checks are only warnings
+    pub force: bool,
+    // run safety checks and report what would happen, without copying or launching
+    pub dry_run: bool,
+    // latest session of the folder is loaded, when no session file is given
+    pub session_dir: Option<PathBuf>,
+    // visits made in the temp profile are synced back with their places
+    pub sync_history: bool,
+    // name of the temp profile folder instead of a generated one
+    pub temp_name: Option<String>,
+    // arguments passed to the browser after the profile, from the template
+    pub extra_args: Vec<String>,
+    // entries not copied on top of the default ones, from the template
+    pub extra_ignore: Vec<String>,
+    // no profile was given, so it can be chosen from a list in a terminal
+    pub pick_profile: bool,
+    // unpacked extensions or .xpi files put into the temp profile
+    pub load_extensions: Vec<PathBuf>,
+    pub no_extension_rewrite: bool,
+    pub quiet_firefox: bool,
+    // telemetry and crash report state is removed from the temp profile
+    pub scrub: bool,
+    // write config.toml with a template of the detected default profile
+    pub init_config: bool,
+}
+
+fn app() -> App<'static, 'static> {
+    App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::with_name("base_profile")
+                .help("profile to run, or name of a template from config.toml")
+                .index(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bookmarks_sync")
+                .help("sync new bookmarks to original profile")
+                .short("b")
+                .long("--bookmarks"),
+        )
+        .arg(
+            Arg::with_name("load_session")
+                .help("load session file")
+                .takes_value(true)
+                .short("l"),
+        )
+        .arg(
+            Arg::with_name("save_session")
+                .help("save session file after exiting")
+                .takes_value(true)
+                .short("s"),
+        )
+        .arg(
+            Arg::with_name("save_load_session")
+                .conflicts_with("save_session")
+                .conflicts_with("load_session")
+                .help("load session from a file and save session to the same file after exiting")
+                .takes_value(true)
+                .short("L"),
+        )
+        .arg(
+            Arg::with_name("session_file_prompt")
+                .conflicts_with_all(&["load_session", "save_session", "save_load_session"])
+                .help("Show prompt to load/save session file before/after firefox")
+                .short("p"),
+        )
+        .arg(
+            Arg::with_name("session_file_prompt_skip_load")
+                .requires("session_file_prompt")
+                .help("Don't show prompt when starting")
+                .long("prompt-load-skip"),
+        )
+        .arg(
+            Arg::with_name("session_file_prompt_skip_save")
+                .requires("session_file_prompt")
+                .help("Don't show prompt at the end to save session")
+                .long("prompt-save-skip"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .help("Close firefox after given number of seconds")
+                .takes_value(true)
+                .value_name("seconds")
+                .long("timeout"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .requires("bookmarks_sync")
+                .help("Also sync new bookmarks every given number of seconds while firefox runs")
+                .takes_value(true)
+                .value_name("seconds")
+                .long("watch"),
+        )
+        .arg(
+            Arg::with_name("sync_deletions")
+                .requires("bookmarks_sync")
+                .help("When every bookmark was removed in the temp profile, remove them from the original profile too, after confirming in a terminal or with --force")
+                .long("sync-deletions"),
+        )
+        .arg(
+            Arg::with_name("dry_run_sql")
+                .conflicts_with_all(&["watch", "sync_deletions", "sync_history"])
+                .help("Print the sql statements syncing back or merging bookmarks would run, with their values, instead of changing the target profile")
+                .long("dry-run-sql"),
+        )
+        .arg(
+            Arg::with_name("sync_containers")
+                .help("sync new containers to original profile")
+                .long("sync-containers"),
+        )
+        .arg(
+            Arg::with_name("attach")
+                .help("Symlink read-only folders of the profile, like extensions, instead of copying them")
+                .long("attach"),
+        )
+        .arg(
+            Arg::with_name("clone")
+                .help("Create a new permanent profile as a copy of the base profile")
+                .takes_value(true)
+                .value_name("new_name")
+                .long("clone"),
+        )
+        .arg(
+            Arg::with_name("tree")
+                .help("Print bookmarks of the base profile as a tree")
+                .long("tree"),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .requires("tree")
+                .help("Max depth of the printed bookmarks tree")
+                .takes_value(true)
+                .value_name("N")
+                .long("depth"),
+        )
+        .arg(
+            Arg::with_name("no_crash_session")
+                .help("Don't save session if firefox didn't exit cleanly")
+                .long("no-crash-session"),
+        )
+        .arg(
+            Arg::with_name("always_save")
+                .help("Save session even if its tabs didn't change since it was loaded")
+                .long("always-save"),
+        )
+        .arg(
+            Arg::with_name("no_session_pref_changes")
+                .help("Don't change history, sanitize and startup prefs of the profile when a session is loaded or saved, for profiles that already have them set")
+                .long("no-session-pref-changes"),
+        )
+        .arg(
+            Arg::with_name("list_sessions")
+                .help("List session files in the folder with their window and tab counts")
+                .takes_value(true)
+                .value_name("dir")
+                .long("list-sessions"),
+        )
+        .arg(
+            Arg::with_name("count")
+                .help("Print number of bookmarks, places and origins in the base profile")
+                .long("count"),
+        )
+        .arg(
+            Arg::with_name("session_target")
+                .help("Session file of the profile to load session into")
+                .takes_value(true)
+                .possible_values(&["recovery", "sessionstore"])
+                .long("session-target"),
+        )
+        .arg(
+            Arg::with_name("replace_session")
+                .help("Replace session of the profile with the loaded one, the default")
+                .long("replace-session"),
+        )
+        .arg(
+            Arg::with_name("merge_session")
+                .conflicts_with("replace_session")
+                .help("Add windows of the loaded session to the session of the profile, skipping tabs that are already open")
+                .long("merge-session"),
+        )
+        .arg(
+            Arg::with_name("append_session")
+                .conflicts_with_all(&["replace_session", "merge_session"])
+                .help("Add windows of the loaded session to the session of the profile as new windows")
+                .long("append-session"),
+        )
+        .arg(
+            Arg::with_name("sync_folder")
+                .requires("bookmarks_sync")
+                .help("Sync only new bookmarks under the folder with this title")
+                .takes_value(true)
+                .value_name("title")
+                .long("sync-folder"),
+        )
+        .arg(
+            Arg::with_name("since")
+                .help("Sync or merge only bookmarks added at or after this ISO 8601 date, e.g. 2024-01-31 or 2024-01-31T10:00:00+02:00")
+                .takes_value(true)
+                .value_name("date")
+                .long("since"),
+        )
+        .arg(
+            Arg::with_name("target_folder")
+                .help("Put synced or merged bookmarks into the folder with this title in the target profile, keeping folders under it, the folder is created in the bookmarks menu if missing")
+                .takes_value(true)
+                .value_name("title")
+                .long("target-folder"),
+        )
+        .arg(
+            Arg::with_name("on_conflict")
+                .help("What to do with a synced or merged bookmark that was changed in the target profile too, skip keeps the one of the target")
+                .takes_value(true)
+                .possible_values(&["skip", "overwrite", "keep-both"])
+                .long("on-conflict"),
+        )
+        .arg(
+            Arg::with_name("list_files")
+                .help("Print entries of the base profile that would be copied with their sizes, without copying or launching")
+                .long("list-files"),
+        )
+        .arg(
+            Arg::with_name("keep_temp")
+                .help("Keep the temp profile after firefox exits, together with a manifest describing it")
+                .long("keep-temp"),
+        )
+        .arg(
+            Arg::with_name("diff_session")
+                .help("Print tabs added, removed or reordered between two session files")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["a", "b"])
+                .long("diff-session"),
+        )
+        .arg(
+            Arg::with_name("check_session")
+                .help("Check structure of a session file and print what would keep it from being fully restored")
+                .takes_value(true)
+                .value_name("file")
+                .long("check-session"),
+        )
+        .arg(
+            Arg::with_name("compare_extensions")
+                .help("Print extensions installed only in the base profile or only in the other profile, given by name or path, and the ones with different versions")
+                .takes_value(true)
+                .value_name("other")
+                .long("compare-extensions"),
+        )
+        .arg(
+            Arg::with_name("list_extensions")
+                .help("Print extensions installed into the base profile with their ids, names, versions and whether they are enabled")
+                .long("list-extensions"),
+        )
+        .arg(
+            Arg::with_name("export_session")
+                .help("Write session file as pretty printed json")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["in.jsonlz4", "out.json"])
+                .long("export-session"),
+        )
+        .arg(
+            Arg::with_name("import_session")
+                .help("Compress json session into a session file firefox reads")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["in.json", "out.jsonlz4"])
+                .long("import-session"),
+        )
+        .arg(
+            Arg::with_name("browser")
+                .help("Browser binary to launch, looked up in PATH unless it is a path")
+                .takes_value(true)
+                .value_name("binary")
+                .long("browser"),
+        )
+        .arg(
+            Arg::with_name("wrapper")
+                .help("Command to launch the browser under, like \"firejail --private\", split into arguments the way a shell does")
+                .takes_value(true)
+                .value_name("command")
+                .long("wrapper"),
+        )
+        .arg(
+            Arg::with_name("sync_target")
+                .help("Profile to sync bookmarks, containers and session to, instead of the base profile")
+                .takes_value(true)
+                .value_name("profile")
+                .long("sync-target"),
+        )
+        .arg(
+            Arg::with_name("cache_copy")
+                .conflicts_with("attach")
+                .help("Keep the profile copy in the cache dir and reuse it while the base profile is unchanged")
+                .long("cache-copy"),
+        )
+        .arg(
+            Arg::with_name("stage")
+                .conflicts_with_all(&["attach", "cache_copy", "temp_name"])
+                .help("Copy the profile into this folder and launch it from there, the folder is kept and reused while the base profile is unchanged")
+                .takes_value(true)
+                .value_name("dir")
+                .long("stage"),
+        )
+        .arg(
+            Arg::with_name("stable_path")
+                .conflicts_with_all(&["attach", "cache_copy", "stage", "temp_name"])
+                .help("Copy the profile into a folder in the cache dir named after the profile, so it is launched from the same path every time. The folder is recreated on every launch, a launch of the same profile that is already running gets a unique temp folder instead")
+                .long("stable-path"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .help("Environment variable to set for firefox, can be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VALUE")
+                .long("env"),
+        )
+        .arg(
+            Arg::with_name("print_command")
+                .help("Prepare the temp profile and print the command to launch it instead of running firefox")
+                .long("print-command"),
+        )
+        .arg(
+            Arg::with_name("dump_prefs")
+                .conflicts_with_all(&["print_command", "dry_run", "cache_copy", "stage"])
+                .help("Copy the profile, adjust its prefs for session restore and print prefs that were changed in prefs.js, without launching firefox. Prefs needed to save the session are adjusted only with -s, -L or -p")
+                .long("dump-prefs"),
+        )
+        .arg(
+            Arg::with_name("overlay_overrides")
+                .help("Prefs from user-overrides.js in the config dir replace prefs already set in the profile")
+                .long("overlay-overrides"),
+        )
+        .arg(
+            Arg::with_name("profile_root")
+                .help("Folder with firefox profiles, takes precedence over FFTEMPLATES_PROFILE_ROOT and ~/.mozilla/firefox")
+                .takes_value(true)
+                .long("profile-root"),
+        )
+        .arg(
+            Arg::with_name("profile_path")
+                .help("Path to the profile folder or archive to run, takes precedence over the profile name and --profile-root")
+                .takes_value(true)
+                .long("profile-path"),
+        )
+        .arg(
+            Arg::with_name("profile_index")
+                .conflicts_with("profile_path")
+                .help("Run the profile of the [ProfileN] section of profiles.ini, takes precedence over the profile name")
+                .takes_value(true)
+                .value_name("N")
+                .long("profile-index"),
+        )
+        .arg(
+            Arg::with_name("repair")
+                .help("Fix orphaned places and origins left by failed syncs in the profile, places.sqlite is backed up first")
+                .takes_value(true)
+                .value_name("profile")
+                .long("repair"),
+        )
+        .arg(
+            Arg::with_name("touch_session")
+                .help("Update modification time of the loaded session file, so firefox doesn't ignore an old session")
+                .long("touch-session"),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .help("Show a progress bar while copying the profile, by default only when stdout is a terminal")
+                .long("progress"),
+        )
+        .arg(
+            Arg::with_name("merge_bookmarks")
+                .help("Merge every bookmark of the source profile into the destination profile, bookmarks already there are skipped")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["source", "dest"])
+                .long("merge-bookmarks"),
+        )
+        .arg(
+            Arg::with_name("preview_session")
+                .help("Print tabs of the session given with -l or -L instead of launching firefox")
+                .long("preview-session"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .help("List profiles of firefox and its forks found in their usual locations")
+                .long("list"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .help("Proceed with a warning when a safety check fails. Overrides checks that the sync target is not used by a running browser and that its places.sqlite passes integrity check, and replaces an existing config with --init-config")
+                .long("force"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .help("Run safety checks and print what would be launched, without copying or launching anything")
+                .long("dry-run"),
+        )
+        .arg(
+            Arg::with_name("session_dir")
+                .help("Load the most recently modified session of the folder, nothing is loaded if the folder has none")
+                .conflicts_with_all(&["load_session", "save_load_session", "session_file_prompt"])
+                .takes_value(true)
+                .value_name("dir")
+                .long("session-dir"),
+        )
+        .arg(
+            Arg::with_name("sync_history")
+                .help("sync new history visits to original profile, can be a lot of data")
+                .long("sync-history"),
+        )
+        .arg(
+            Arg::with_name("temp_name")
+                .help("Name of the temp profile folder, by default fftemplates-<profile>-<millis>-<pid>")
+                .takes_value(true)
+                .value_name("name")
+                .long("temp-name"),
+        )
+        .arg(
+            Arg::with_name("no_interactive")
+                .help("Use the default profile when no profile is given, instead of asking to choose one")
+                .long("no-interactive"),
+        )
+        .arg(
+            Arg::with_name("load_extensions")
+                .help("Unpacked extension folder or .xpi to install into the temp profile only, can be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("path")
+                .conflicts_with("attach")
+                .long("load-extension"),
+        )
+        .arg(
+            Arg::with_name("no_extension_rewrite")
+                .help("Remove extensions.json from the temp profile instead of rewriting extension paths in it, firefox rebuilds it by scanning the extensions folder, losing per-extension state like disabled extensions")
+                .long("no-extension-rewrite"),
+        )
+        .arg(
+            Arg::with_name("quiet_firefox")
+                .help("Hide output of firefox, its errors are still shown if it doesn't exit cleanly")
+                .long("quiet-firefox"),
+        )
+        .arg(
+            Arg::with_name("scrub")
+                .help("Remove datareporting/, crashes/, saved-telemetry-pings/ and Telemetry.FailedProfileLocks.txt from the temp profile and turn off telemetry and crash report submission in its prefs")
+                .long("scrub"),
+        )
+        .arg(
+            Arg::with_name("init_config")
+                .help("Write config.toml to the config folder with a template of the default profile and the browser that were found, an existing config is replaced only with --force")
+                .long("init-config"),
+        )
+}
+
+/// Name of the browser launched by `browser` binary, like `librewolf`.
+pub fn browser_name(browser: &str) -> Option<String> {
+    Path::new(browser)
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Parses arguments of the current process, exits on invalid or help arguments.
+pub fn parse() -> Args {
+    from_matches(&app().get_matches())
+}
+
+fn from_matches(matches: &ArgMatches) -> Args {
+    let value = |name| matches.value_of(name).map(|v: &str| v.to_string());
+    Args {
+        base_profile: value("base_profile"),
+        bookmarks_sync: matches.is_present("bookmarks_sync"),
+        sync_deletions: matches.is_present("sync_deletions"),
+        dry_run_sql: matches.is_present("dry_run_sql"),
+        load_session: value("load_session"),
+        save_session: value("save_session"),
+        save_load_session: value("save_load_session"),
+        session_prompt: matches.is_present("session_file_prompt"),
+        session_prompt_skip_load: matches.is_present("session_file_prompt_skip_load"),
+        session_prompt_skip_save: matches.is_present("session_file_prompt_skip_save"),
+        timeout: value("timeout"),
+        watch: value("watch"),
+        sync_containers: matches.is_present("sync_containers"),
+        attach: matches.is_present("attach"),
+        clone: value("clone"),
+        tree: matches.is_present("tree"),
+        depth: value("depth"),
+        no_crash_session: matches.is_present("no_crash_session"),
+        always_save: matches.is_present("always_save"),
+        no_session_pref_changes: matches.is_present("no_session_pref_changes"),
+        list_sessions: value("list_sessions"),
+        count: matches.is_present("count"),
+        session_target: value("session_target"),
+        replace_session: matches.is_present("replace_session"),
+        merge_session: matches.is_present("merge_session"),
+        append_session: matches.is_present("append_session"),
+        sync_folder: value("sync_folder"),
+        since: value("since"),
+        target_folder: value("target_folder"),
+        on_conflict: value("on_conflict"),
+        keep_temp: matches.is_present("keep_temp"),
+        list_files: matches.is_present("list_files"),
+        print_command: matches.is_present("print_command"),
+        dump_prefs: matches.is_present("dump_prefs"),
+        overlay_overrides: matches.is_present("overlay_overrides"),
+        profile_root: value("profile_root"),
+        profile_path: value("profile_path"),
+        profile_index: value("profile_index"),
+        repair: value("repair"),
+        touch_session: matches.is_present("touch_session"),
+        progress: matches.is_present("progress"),
+        merge_bookmarks: matches
+            .values_of("merge_bookmarks")
+            .map(|values| values.map(|v| v.to_string()).collect()),
+        compare_extensions: value("compare_extensions"),
+        list_extensions: matches.is_present("list_extensions"),
+        diff_session: matches
+            .values_of("diff_session")
+            .map(|values| values.map(|v| v.to_string()).collect()),
+        check_session: value("check_session"),
+        export_session: matches
+            .values_of("export_session")
+            .map(|values| values.map(|v| v.to_string()).collect()),
+        import_session: matches
+            .values_of("import_session")
+            .map(|values| values.map(|v| v.to_string()).collect()),
+        preview_session: matches.is_present("preview_session"),
+        list: matches.is_present("list"),
+        force: matches.is_present("force"),
+        dry_run: matches.is_present("dry_run"),
+        session_dir: value("session_dir"),
+        sync_history: matches.is_present("sync_history"),
+        temp_name: value("temp_name"),
+        no_interactive: matches.is_present("no_interactive"),
+        load_extensions: matches
+            .values_of("load_extensions")
+            .map(|values| values.map(|v| v.to_string()).collect())
+            .unwrap_or_default(),
+        no_extension_rewrite: matches.is_present("no_extension_rewrite"),
+        quiet_firefox: matches.is_present("quiet_firefox"),
+        scrub: matches.is_present("scrub"),
+        init_config: matches.is_present("init_config"),
+        browser: value("browser"),
+        wrapper: value("wrapper"),
+        sync_target: value("sync_target"),
+        cache_copy: matches.is_present("cache_copy"),
+        stage: value("stage"),
+        stable_path: matches.is_present("stable_path"),
+        env: matches
+            .values_of("env")
+            .map(|values| values.map(|v| v.to_string()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Validates arguments and turns them into `Config`.
+///
+/// Loading and saving the same session file is resolved here,
+/// as well as the firefox profiles folder. Folder is taken from
+/// `--profile-root`, then `FFTEMPLATES_PROFILE_ROOT`, then defaults
+/// to `~/.mozilla/firefox`. `--profile-path` skips the lookup entirely.
+pub fn into_config(args: Args) -> Result<Config, FfError> {
+    let session_mode = match (args.save_load_session, args.load_session, args.save_session) {
+        (Some(file), _, _) => SessionMode::LoadAndSave(file),
+        (None, Some(load), Some(save)) => SessionMode::LoadAndSaveTo(load, save),
+        (None, Some(load), None) => SessionMode::Load(load),
+        (None, None, Some(save)) => SessionMode::Save(save),
+        (None, None, None) => SessionMode::None,
+    };
+    let session_target = args.session_target.map(|target| match target.as_str() {
+        "recovery" => session::SESSIONSTORE_RECOVERY_NAME.to_string(),
+        _ => session::SESSIONSTORE_DEFAULT_NAME.to_string(),
+    });
+    let on_conflict = match args.on_conflict.as_deref() {
+        Some("overwrite") => ConflictPolicy::Overwrite,
+        Some("keep-both") => ConflictPolicy::KeepBoth,
+        _ => ConflictPolicy::Skip,
+    };
+    let session_load_mode = match (args.merge_session, args.append_session) {
+        (true, _) => SessionLoadMode::Merge,
+        (_, true) => SessionLoadMode::Append,
+        _ => SessionLoadMode::Replace,
+    };
+    let diff_session = match args.diff_session.as_deref() {
+        None => None,
+        Some([a, b]) => Some((PathBuf::from(a), PathBuf::from(b))),
+        Some(_) => return Err("Two session files are required to diff".into()),
+    };
+    let export_session = match args.export_session.as_deref() {
+        None => None,
+        Some([from, to]) => Some((PathBuf::from(from), PathBuf::from(to))),
+        Some(_) => return Err("Session file and json file are required to export".into()),
+    };
+    let import_session = match args.import_session.as_deref() {
+        None => None,
+        Some([from, to]) => Some((PathBuf::from(from), PathBuf::from(to))),
+        Some(_) => return Err("Json file and session file are required to import".into()),
+    };
+    let merge_bookmarks = match args.merge_bookmarks.as_deref() {
+        None => None,
+        Some([source, dest]) => Some((source.clone(), dest.clone())),
+        Some(_) => return Err("Source and destination profiles are required to merge".into()),
+    };
+    if let Some(name) = &args.temp_name {
+        // name has to stay a single folder inside the temp dir
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(format!(
+                "Invalid temp profile name `{}`, it can't contain path separators",
+                name
+            )
+            .into());
+        }
+    }
+    let mut env = vec![];
+    for variable in &args.env {
+        match variable.split_once('=') {
+            Some((key, value)) if !key.is_empty() => env.push((key.to_string(), value.to_string())),
+            _ => {
+                return Err(format!(
+                    "Invalid environment variable `{}`, expected KEY=VALUE",
+                    variable
+                )
+                .into())
+            }
+        }
+    }
+    let tree_depth = match args.depth.map(|v| v.parse::<usize>()) {
+        None => None,
+        Some(Ok(depth)) => Some(depth),
+        Some(Err(e)) => return Err(format!("Invalid depth value : {}", e).into()),
+    };
+    let since = match args
+        .since
+        .as_deref()
+        .map(timestamps::parse_firefox_timestamp)
+    {
+        None => None,
+        Some(Ok(since)) => Some(since),
+        Some(Err(e)) => return Err(e),
+    };
+    let profile_index = match args.profile_index.map(|v| v.parse::<usize>()) {
+        None => None,
+        Some(Ok(index)) => Some(index),
+        Some(Err(e)) => return Err(format!("Invalid profile index value : {}", e).into()),
+    };
+    let timeout = match args.timeout.map(|v| v.parse::<u64>()) {
+        None => None,
+        Some(Ok(seconds)) => Some(Duration::from_secs(seconds)),
+        Some(Err(e)) => return Err(format!("Invalid timeout value : {}", e).into()),
+    };
+    // paths in extensions.json are rewritten to the staged folder, they have to be absolute
+    let stage = match args.stage {
+        None => None,
+        Some(stage) => Some(std::path::absolute(stage)?),
+    };
+    let wrapper = match args.wrapper.as_deref().map(split_command) {
+        None => vec![],
+        Some(Ok(wrapper)) if wrapper.is_empty() => return Err("Wrapper command is empty".into()),
+        Some(Ok(wrapper)) => wrapper,
+        Some(Err(e)) => return Err(e),
+    };
+    let watch = match args.watch.map(|v| v.parse::<u64>()) {
+        None => None,
+        Some(Ok(0)) => return Err("Watch interval can't be 0".into()),
+        Some(Ok(seconds)) => Some(Duration::from_secs(seconds)),
+        Some(Err(e)) => return Err(format!("Invalid watch value : {}", e).into()),
+    };
+
+    let discover_profiles = args.profile_root.is_none()
+        && env::var_os(PROFILE_ROOT_VARIABLE).is_none_or(|root| root.is_empty());
+    let env_root = env::var_os(PROFILE_ROOT_VARIABLE)
+        .filter(|root| !root.is_empty())
+        .map(PathBuf::from);
+    let default_root = dirs::home_dir().map(|home| home.join(".mozilla").join("firefox"));
+    // override, then env, then the platform default, then forks of firefox
+    let mut profile_roots: Vec<PathBuf> = vec![];
+    let candidates = args
+        .profile_root
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(env_root)
+        .chain(default_root)
+        .chain(
+            profiles::known_profile_roots()
+                .into_iter()
+                .map(|(_, root)| root),
+        );
+    for root in candidates {
+        if !profile_roots.contains(&root) {
+            profile_roots.push(root);
+        }
+    }
+    let profile_folder = match profile_roots.first() {
+        None => return Err("Unable to find home directory".into()),
+        Some(root) => root.clone(),
+    };
+
+    let pick_profile = args.base_profile.is_none()
+        && args.profile_path.is_none()
+        && profile_index.is_none()
+        && !args.no_interactive;
+
+    Ok(Config {
+        profile_name: args.base_profile.unwrap_or_else(|| "default".to_string()),
+        profile_folder,
+        profile_roots,
+        bookmarks_sync: args.bookmarks_sync,
+        sync_deletions: args.sync_deletions,
+        dry_run_sql: args.dry_run_sql,
+        session_mode,
+        session_prompt: args.session_prompt,
+        session_prompt_load_skip: args.session_prompt_skip_load,
+        session_prompt_save_skip: args.session_prompt_skip_save,
+        timeout,
+        watch,
+        sync_containers: args.sync_containers,
+        attach: args.attach,
+        clone_name: args.clone,
+        tree: args.tree,
+        tree_depth,
+        no_crash_session: args.no_crash_session,
+        always_save: args.always_save,
+        no_session_pref_changes: args.no_session_pref_changes,
+        list_sessions: args.list_sessions.map(PathBuf::from),
+        count: args.count,
+        session_target,
+        session_load_mode,
+        sync_folder: args.sync_folder,
+        since,
+        target_folder: args.target_folder,
+        on_conflict,
+        // profile has to outlive this process to be launched later
+        keep_temp: args.keep_temp || args.print_command,
+        list_files: args.list_files,
+        diff_session,
+        check_session: args.check_session.map(PathBuf::from),
+        compare_extensions: args.compare_extensions,
+        list_extensions: args.list_extensions,
+        export_session,
+        import_session,
+        browser_name: args.browser.as_deref().and_then(browser_name),
+        browser_binary: args
+            .browser
+            .unwrap_or_else(|| browser::DEFAULT_BROWSER.to_string()),
+        wrapper,
+        sync_target: args.sync_target,
+        cache_copy: args.cache_copy,
+        stage,
+        stable_path: args.stable_path,
+        env,
+        print_command: args.print_command,
+        dump_prefs: args.dump_prefs,
+        overlay_overrides: args.overlay_overrides,
+        profile_path: args.profile_path.map(PathBuf::from),
+        profile_index,
+        repair: args.repair,
+        touch_session: args.touch_session,
+        progress: args.progress,
+        merge_bookmarks,
+        preview_session: args.preview_session,
+        list: args.list,
+        discover_profiles,
+        force: args.force,
+        dry_run: args.dry_run,
+        session_dir: args.session_dir.map(PathBuf::from),
+        sync_history: args.sync_history,
+        temp_name: args.temp_name,
+        extra_args: vec![],
+        extra_ignore: vec![],
+        pick_profile,
+        load_extensions: args.load_extensions.iter().map(PathBuf::from).collect(),
+        no_extension_rewrite: args.no_extension_rewrite,
+        quiet_firefox: args.quiet_firefox,
+        scrub: args.scrub,
+        init_config: args.init_config,
+    })
+}
+
+/// Splits a command line into arguments the way a shell does, without
+/// expanding anything.
+///
+/// Arguments are separated by whitespace, single quotes keep everything
+/// as is, double quotes and backslashes escape the next character.
+pub fn split_command(command: &str) -> Result<Vec<String>, FfError> {
+    let mut args = vec![];
+    let mut arg = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        None => return Err(unclosed_quote(command)),
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        None => return Err(unclosed_quote(command)),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            None => return Err(unclosed_quote(command)),
+                            Some(c) => arg.push(c),
+                        },
+                        Some(c) => arg.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = arg.get_or_insert_with(String::new);
+                arg.extend(chars.next());
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+
+    Ok(args)
+}
+
+fn unclosed_quote(command: &str) -> FfError {
+    format!("Unclosed quote in `{}`", command).into()
+}
@@ -0,0 +1,209 @@
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::cli;
+use crate::cli::Config;
+use crate::cli::SessionMode;
+use crate::error::FfError;
+use crate::paths;
+
+/// Name of the config file in the config folder.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Content of `config.toml`.
+///
+/// ```toml
+/// [template.work]
+/// profile = "work"
+/// extra_args = ["--new-window"]
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub template: HashMap<String, TemplateConfig>,
+}
+
+/// Launch settings of a single named template, every field is optional.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateConfig {
+    // profile to copy, name of the template by default
+    pub profile: Option<String>,
+    // top level entries of the profile that are not copied
+    pub ignore: Vec<String>,
+    // arguments passed to the browser after the profile
+    pub extra_args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    // session file loaded before and saved after the run
+    pub session: Option<String>,
+    // browser binary, unless one is given with --browser
+    pub browser: Option<String>,
+}
+
+/// Settings a template name resolves to.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TemplateSettings {
+    pub profile_name: String,
+    pub ignore: Vec<String>,
+    pub extra_args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub session: Option<String>,
+    pub browser: Option<String>,
+}
+
+/// Reads config file, missing file is the same as an empty one.
+pub fn read_file_config(path: &Path) -> Result<FileConfig, FfError> {
+    if !path.exists() {
+        return Ok(FileConfig::default());
+    }
+    let content = fs::read_to_string(path)?;
+
+    Ok(parse_file_config(&content)
+        .map_err(|e| format!("Invalid config `{}` : {}", path.display(), e))?)
+}
+
+pub fn parse_file_config(content: &str) -> Result<FileConfig, FfError> {
+    Ok(toml::from_str(content).map_err(|e| e.to_string())?)
+}
+
+/// Looks up the template by name.
+///
+/// Name without a template is a plain profile name, with nothing else set.
+pub fn resolve_template(name: &str, config: &FileConfig) -> TemplateSettings {
+    match config.template.get(name) {
+        None => TemplateSettings {
+            profile_name: name.to_string(),
+            ..TemplateSettings::default()
+        },
+        Some(template) => TemplateSettings {
+            profile_name: template.profile.clone().unwrap_or_else(|| name.to_string()),
+            ignore: template.ignore.clone(),
+            extra_args: template.extra_args.clone(),
+            env: template
+                .env
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            session: template.session.clone(),
+            browser: template.browser.clone(),
+        },
+    }
+}
+
+/// Merges template settings into the config of the run.
+///
+/// Command line takes precedence, its environment variables override the
+/// ones of the template, and template session is used only when no session
+/// was given, same as the template browser.
+pub fn apply_template(config: &mut Config, settings: TemplateSettings) {
+    config.profile_name = settings.profile_name;
+    if let (Some(browser), None) = (settings.browser, &config.browser_name) {
+        config.browser_name = cli::browser_name(&browser);
+        config.browser_binary = browser;
+    }
+    config.extra_ignore.extend(settings.ignore);
+    config.extra_args.extend(settings.extra_args);
+    let mut env = settings.env;
+    env.append(&mut config.env);
+    config.env = env;
+    if let Some(session) = settings.session {
+        if config.session_mode == SessionMode::None
+            && !config.session_prompt
+            && config.session_dir.is_none()
+        {
+            config.session_mode = SessionMode::LoadAndSave(session);
+        }
+    }
+}
+
+/// Entries of a profile that a new template doesn't copy, crash reports
+/// of the base profile are of no use to the copy.
+pub const DEFAULT_TEMPLATE_IGNORE: &[&str] = &["crashes", "minidumps"];
+
+/// Commented `config.toml` with a single template for `profile`.
+///
+/// `browser` is the command firefox is launched with, it is set in the
+/// template only when it is a single binary.
+pub fn default_config(profile: &str, browser: Option<&[String]>) -> String {
+    let name = toml_key(profile);
+    let mut lines = vec![
+        "# fftemplates config, written by --init-config".to_string(),
+        "#".to_string(),
+        "# `fftemplates <template>` launches a copy of the template profile with".to_string(),
+        "# the settings below, arguments of the command line take precedence.".to_string(),
+        String::new(),
+        format!("[template.{}]", name),
+        "# profile that is copied, name of the template by default".to_string(),
+        format!("profile = {}", toml_string(profile)),
+    ];
+    match browser {
+        Some([binary]) => {
+            lines.push("# browser binary, unless one is given with --browser".to_string());
+            lines.push(format!("browser = {}", toml_string(binary)));
+        }
+        Some(command) => {
+            lines.push(format!(
+                "# firefox is launched with `{}`, found again on every run",
+                command.join(" ")
+            ));
+            lines.push("# browser = \"firefox\"".to_string());
+        }
+        None => {
+            lines.push("# firefox wasn't found, set its binary here or with --browser".to_string());
+            lines.push("# browser = \"/usr/bin/firefox\"".to_string());
+        }
+    }
+    let ignore: Vec<_> = DEFAULT_TEMPLATE_IGNORE
+        .iter()
+        .map(|e| toml_string(e))
+        .collect();
+    lines.extend(vec![
+        "# top level entries of the profile that are not copied".to_string(),
+        format!("ignore = [{}]", ignore.join(", ")),
+        "# arguments passed to the browser after the profile".to_string(),
+        "extra_args = []".to_string(),
+        "# session file loaded before and saved after the run".to_string(),
+        format!(
+            "# session = {}",
+            toml_string(&format!("{}.jsonlz4", profile))
+        ),
+        String::new(),
+        "# environment variables set for the browser".to_string(),
+        format!("# [template.{}.env]", name),
+        "# MOZ_ENABLE_WAYLAND = \"1\"".to_string(),
+    ]);
+
+    lines.join("\n") + "\n"
+}
+
+/// Writes new config file, an existing one is only replaced with `force`.
+pub fn write_config(path: &Path, content: &str, force: bool) -> Result<(), FfError> {
+    if path.exists() && !force {
+        Err(format!(
+            "Config `{}` already exists, use --force to replace it",
+            path.display()
+        ))?;
+    }
+
+    paths::write_atomic(path, content.as_bytes())
+}
+
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+// bare keys can't have dots or spaces, profile names can
+fn toml_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match is_bare {
+        true => key.to_string(),
+        false => toml_string(key),
+    }
+}
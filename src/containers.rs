@@ -0,0 +1,124 @@
+use log::warn;
+use serde_json::Value;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::error::FfError;
+use crate::paths;
+
+const CONTAINERS_FILE_NAME: &str = "containers.json";
+
+/// Adds containers that were created in `from_profile` to `to_profile`.
+///
+/// Existing containers in `to_profile` are never modified. Containers are
+/// matched by id first, since tabs and cookies refer to them: a container
+/// whose id holds another container in `to_profile` is reported as a conflict
+/// and skipped, not renumbered. A container with a free id is added unless
+/// a container with the same name exists in `to_profile`.
+///
+/// Returns number of containers added.
+pub fn sync_containers<P: AsRef<Path>>(from_profile: P, to_profile: P) -> Result<usize, FfError> {
+    let from_file = from_profile.as_ref().join(CONTAINERS_FILE_NAME);
+    let to_file = to_profile.as_ref().join(CONTAINERS_FILE_NAME);
+
+    if !from_file.exists() {
+        return Ok(0);
+    }
+    let from_containers = read_containers(&from_file)?;
+    let from_identities = identities(&from_containers)?;
+    if !to_file.exists() {
+        fs::copy(&from_file, &to_file)?;
+        return Ok(from_identities.len());
+    }
+    let mut to_containers = read_containers(&to_file)?;
+
+    let mut existing_keys = HashSet::new();
+    let mut existing_ids = HashMap::new();
+    for identity in identities(&to_containers)? {
+        let key = identity_key(identity).map(str::to_string);
+        if let Some(key) = &key {
+            existing_keys.insert(key.clone());
+        }
+        if let Some(id) = identity["userContextId"].as_u64() {
+            existing_ids.insert(id, key);
+        }
+    }
+    let mut last_id = to_containers["lastUserContextId"].as_u64().unwrap_or(0);
+
+    let mut new_identities = vec![];
+    for identity in from_identities {
+        let key = match identity_key(identity) {
+            None => continue,
+            Some(key) => key,
+        };
+        let id = identity["userContextId"].as_u64();
+        if let Some(existing_key) = id.and_then(|id| existing_ids.get(&id)) {
+            // renamed in one of the profiles, or created in both with the same id
+            if existing_key.as_deref() != Some(key) {
+                warn!(
+                    "Container `{}` has the id of `{}` in the target profile, it isn't synced",
+                    key,
+                    existing_key.as_deref().unwrap_or_default()
+                );
+            }
+            continue;
+        }
+        if existing_keys.contains(key) {
+            continue;
+        }
+
+        let mut identity = identity.clone();
+        let id = match id {
+            Some(id) => id,
+            None => {
+                last_id += 1;
+                identity["userContextId"] = Value::from(last_id);
+                last_id
+            }
+        };
+        if identity["public"].as_bool().unwrap_or(true) && id > last_id {
+            last_id = id;
+        }
+
+        existing_keys.insert(key.to_string());
+        existing_ids.insert(id, Some(key.to_string()));
+        new_identities.push(identity);
+    }
+
+    let added = new_identities.len();
+    if added == 0 {
+        return Ok(0);
+    }
+
+    match to_containers["identities"].as_array_mut() {
+        None => return Err(format!("`{}` has no identities", to_file.display()))?,
+        Some(identities) => identities.extend(new_identities),
+    }
+    to_containers["lastUserContextId"] = Value::from(last_id);
+
+    paths::write_atomic(&to_file, serde_json::to_string(&to_containers)?.as_bytes())?;
+
+    Ok(added)
+}
+
+fn read_containers(file: &Path) -> Result<Value, FfError> {
+    let content = fs::read_to_string(file)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn identities(containers: &Value) -> Result<&Vec<Value>, FfError> {
+    match containers["identities"].as_array() {
+        None => Err("containers file has no identities")?,
+        Some(identities) => Ok(identities),
+    }
+}
+
+// default containers don't have a name, only a localization id
+fn identity_key(identity: &Value) -> Option<&str> {
+    identity["name"]
+        .as_str()
+        .or_else(|| identity["l10nID"].as_str())
+        .or_else(|| identity["l10nId"].as_str())
+}
@@ -0,0 +1,105 @@
+#[cfg(feature = "bookmarks")]
+use rusqlite;
+use serde_json;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum FfError {
+    Io(io::Error),
+    #[cfg(feature = "bookmarks")]
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+    Message(String),
+    // profile without places.sqlite, e.g. one that was never launched
+    PlacesDbMissing(PathBuf),
+    // message describing what failed, with the error that caused it
+    Context(String, Box<FfError>),
+}
+
+impl FfError {
+    /// Wraps `e` with a message, keeping it as the source of the new error.
+    pub fn context<E: Into<FfError>>(message: &str, e: E) -> Self {
+        FfError::Context(message.to_string(), Box::new(e.into()))
+    }
+}
+
+impl fmt::Display for FfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FfError::Io(e) => write!(f, "{}", e),
+            #[cfg(feature = "bookmarks")]
+            FfError::Sqlite(e) => write!(f, "{}", e),
+            FfError::Json(e) => write!(f, "{}", e),
+            FfError::Message(message) | FfError::Context(message, _) => write!(f, "{}", message),
+            FfError::PlacesDbMissing(path) => write!(
+                f,
+                "Places database `{}` doesn't exist, profile might have never been launched",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for FfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            // wrapped errors are displayed as is, so the chain continues from their source
+            FfError::Io(e) => e.source(),
+            // rusqlite only exposes the sqlite error code through the deprecated `cause`
+            #[cfg(feature = "bookmarks")]
+            FfError::Sqlite(rusqlite::Error::SqliteFailure(e, Some(_))) => Some(e),
+            #[cfg(feature = "bookmarks")]
+            FfError::Sqlite(_) => None,
+            FfError::Json(e) => e.source(),
+            FfError::Message(_) | FfError::PlacesDbMissing(_) => None,
+            FfError::Context(_, e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for FfError {
+    fn from(e: io::Error) -> Self {
+        FfError::Io(e)
+    }
+}
+
+#[cfg(feature = "bookmarks")]
+impl From<rusqlite::Error> for FfError {
+    fn from(e: rusqlite::Error) -> Self {
+        FfError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for FfError {
+    fn from(e: serde_json::Error) -> Self {
+        FfError::Json(e)
+    }
+}
+
+impl From<String> for FfError {
+    fn from(message: String) -> Self {
+        FfError::Message(message)
+    }
+}
+
+impl From<&str> for FfError {
+    fn from(message: &str) -> Self {
+        FfError::Message(message.to_string())
+    }
+}
+
+/// Formats error with all of its sources, separated the same way as other messages.
+pub fn display_chain(e: &dyn Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        message.push_str(&format!(" : {}", e));
+        source = e.source();
+    }
+
+    message
+}
@@ -0,0 +1,350 @@
+use log::{info, warn};
+use regex::Captures;
+use regex::Regex;
+use serde_json::{json, Value};
+use zip::ZipArchive;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::FfError;
+use crate::paths;
+use crate::profiles;
+use crate::session;
+
+pub const EXTENSIONS_JSON: &str = "extensions.json";
+const MANIFEST_NAME: &str = "manifest.json";
+// location of extensions installed into the profile, built-in ones have others
+const PROFILE_LOCATION: &str = "app-profile";
+// used only when the profile has no extensions.json yet, firefox migrates it
+const EXTENSIONS_SCHEMA_VERSION: i64 = 35;
+// real extensions.json is a few hundred KiB at most, anything way bigger
+// is not worth rewriting before every launch
+pub const MAX_EXTENSIONS_JSON_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Rewrites paths of installed extensions in `extensions.json` to point into
+/// the profile folder that contains the file.
+///
+/// Files larger than `MAX_EXTENSIONS_JSON_SIZE` are left unmodified with a warning.
+/// Returns whether the file was rewritten.
+pub fn adjust_extensions_json(extensions: &Path) -> Result<bool, FfError> {
+    let size = fs::metadata(extensions)?.len();
+    if size > MAX_EXTENSIONS_JSON_SIZE {
+        warn!(
+            "`{}` is {} bytes, larger than {} bytes, extension paths are not rewritten",
+            extensions.display(),
+            size,
+            MAX_EXTENSIONS_JSON_SIZE
+        );
+        return Ok(false);
+    }
+    let content = fs::read_to_string(extensions)?;
+
+    let profile_folder = extensions.parent().unwrap_or_else(|| Path::new(""));
+    // regex crate doesn't backtrack, matching is linear in size of the file
+    let re = Regex::new(
+        r#"(?x)
+    ("path":)                       # starting with "path":
+    (")(                            # "
+    ([^"]+)                         # any characters except for " to avoid going over the value in json
+    (extensions[^"]+\.xpi)          # trying to match ending of extenstions/some.extension@name.xpi
+    )(")                            # "
+    "#,
+    )
+    .expect("Invalid extensions path regex");
+
+    let results = re.replace_all(content.as_str(), |caps: &Captures| {
+        format!(
+            "{}{}{}{}",
+            &caps[1],
+            &caps[2],
+            profile_folder.join(&caps[5]).display(),
+            &caps[6]
+        )
+    });
+    paths::write_atomic(extensions, results.as_bytes())?;
+
+    Ok(true)
+}
+
+/// Extension the user installed into a profile, as listed in its `extensions.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledExtension {
+    pub id: String,
+    pub version: String,
+}
+
+/// Extensions that differ between two profiles, ordered by id.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExtensionDiff {
+    pub only_in_a: Vec<InstalledExtension>,
+    pub only_in_b: Vec<InstalledExtension>,
+    // id with its version in a and in b
+    pub different_versions: Vec<(String, String, String)>,
+}
+
+impl ExtensionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.different_versions.is_empty()
+    }
+}
+
+/// Extension of a profile with what its `extensions.json` tells about it,
+/// fields missing in the file, as in older versions of firefox, are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionInfo {
+    pub id: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    // `None` when neither active nor userDisabled is in the file
+    pub enabled: Option<bool>,
+}
+
+/// Extensions installed into the profile, ordered by id.
+///
+/// Built-in addons of firefox are skipped, they come with the browser and not
+/// with the profile. Profile without `extensions.json` has no extensions.
+pub fn list_extensions(profile_folder: &Path) -> Result<Vec<ExtensionInfo>, FfError> {
+    let extensions = profile_folder.join(EXTENSIONS_JSON);
+    if !extensions.exists() {
+        return Ok(vec![]);
+    }
+    let database: Value = serde_json::from_str(&fs::read_to_string(&extensions)?)?;
+    let addons = match database["addons"].as_array() {
+        None => Err(format!("`{}` has no addons", extensions.display()))?,
+        Some(addons) => addons,
+    };
+
+    let mut listed: Vec<_> = addons
+        .iter()
+        .filter(|addon| {
+            addon["location"]
+                .as_str()
+                .is_none_or(|l| l == PROFILE_LOCATION)
+        })
+        .filter_map(|addon| {
+            let string = |value: &Value| value.as_str().map(|s| s.to_string());
+            Some(ExtensionInfo {
+                id: addon["id"].as_str()?.to_string(),
+                // name moved into the default locale at some point
+                name: string(&addon["defaultLocale"]["name"]).or_else(|| string(&addon["name"])),
+                version: string(&addon["version"]),
+                enabled: addon["active"]
+                    .as_bool()
+                    .or_else(|| addon["userDisabled"].as_bool().map(|disabled| !disabled)),
+            })
+        })
+        .collect();
+    listed.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(listed)
+}
+
+/// Ids and versions of the extensions installed into the profile, same as `list_extensions`.
+pub fn read_installed_extensions(profile: &Path) -> Result<Vec<InstalledExtension>, FfError> {
+    Ok(list_extensions(profile)?
+        .into_iter()
+        .map(|extension| InstalledExtension {
+            id: extension.id,
+            version: extension.version.unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Compares extensions installed into two profiles by their ids and versions.
+pub fn compare_extensions(profile_a: &Path, profile_b: &Path) -> Result<ExtensionDiff, FfError> {
+    let a = read_installed_extensions(profile_a)?;
+    let b: BTreeMap<_, _> = read_installed_extensions(profile_b)?
+        .into_iter()
+        .map(|extension| (extension.id.clone(), extension))
+        .collect();
+
+    let mut diff = ExtensionDiff::default();
+    for extension in &a {
+        match b.get(&extension.id) {
+            None => diff.only_in_a.push(extension.clone()),
+            Some(other) if other.version != extension.version => diff.different_versions.push((
+                extension.id.clone(),
+                extension.version.clone(),
+                other.version.clone(),
+            )),
+            Some(_) => {}
+        }
+    }
+    diff.only_in_b = b
+        .into_values()
+        .filter(|extension| !a.iter().any(|e| e.id == extension.id))
+        .collect();
+
+    Ok(diff)
+}
+
+/// Folder of the profile with installed extensions.
+pub const EXTENSIONS_DIR: &str = "extensions";
+// id given to extensions that don't set one in their manifest
+const GENERATED_ID_SUFFIX: &str = "@fftemplates.temporary";
+// prefs that let firefox enable extensions dropped into the profile,
+// signatures are only optional in developer and nightly builds
+const LOADED_EXTENSION_PREFS: &[(&str, i64)] = &[("extensions.autoDisableScopes", 0)];
+const LOADED_EXTENSION_BOOL_PREFS: &[(&str, bool)] = &[("xpinstall.signatures.required", false)];
+
+/// Extension put into the profile by `load_extension`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedExtension {
+    pub id: String,
+    pub version: String,
+    // where the extension is in the profile
+    pub path: PathBuf,
+}
+
+/// Puts an extension into `extensions` folder of the profile and registers
+/// it in `extensions.json`.
+///
+/// `source` is either a packed `.xpi` or a folder of an unpacked extension,
+/// folders are symlinked on unix, so changes show up after a reload.
+/// Extension without an id in its manifest gets a generated one.
+pub fn load_extension(profile: &Path, source: &Path) -> Result<LoadedExtension, FfError> {
+    let manifest = read_extension_manifest(source)?;
+    let gecko = manifest
+        .get("browser_specific_settings")
+        .or_else(|| manifest.get("applications"))
+        .and_then(|settings| settings.get("gecko"));
+    let id = match gecko.and_then(|gecko| gecko["id"].as_str()) {
+        Some(id) => id.to_string(),
+        None => format!(
+            "{}{}",
+            profiles::generate_profile_hash(),
+            GENERATED_ID_SUFFIX
+        ),
+    };
+    let version = manifest["version"].as_str().unwrap_or("0").to_string();
+
+    let extensions_dir = profile.join(EXTENSIONS_DIR);
+    fs::create_dir_all(&extensions_dir)?;
+    let path = if source.is_dir() {
+        let path = extensions_dir.join(&id);
+        link_or_copy_dir(source, &path)?;
+        path
+    } else {
+        let path = extensions_dir.join(format!("{}.xpi", id));
+        fs::copy(source, &path)?;
+        path
+    };
+
+    register_extension(&profile.join(EXTENSIONS_JSON), &id, &manifest, &path)?;
+    info!(
+        "Loaded extension `{}` {} from {}",
+        id,
+        version,
+        source.display()
+    );
+
+    Ok(LoadedExtension { id, version, path })
+}
+
+/// Sets prefs of the profile that keep loaded extensions enabled.
+///
+/// Returns names of the prefs that were set.
+pub fn allow_loaded_extensions(profile: &Path) -> Result<Vec<String>, FfError> {
+    let preferences = profile.join(session::PROFILE_FILE_NAME);
+    let mut content = match preferences.exists() {
+        true => fs::read_to_string(&preferences)?,
+        false => String::new(),
+    };
+    let mut adjusted = vec![];
+    for (name, value) in LOADED_EXTENSION_PREFS {
+        content = session::replace_pref_int(&content, name, *value);
+        adjusted.push(name.to_string());
+    }
+    for (name, value) in LOADED_EXTENSION_BOOL_PREFS {
+        content = session::replace_pref_bool(&content, name, *value);
+        adjusted.push(name.to_string());
+    }
+    paths::write_atomic(&preferences, content.as_bytes())?;
+
+    Ok(adjusted)
+}
+
+// manifest.json of an unpacked folder or of an .xpi archive
+fn read_extension_manifest(source: &Path) -> Result<Value, FfError> {
+    let content = if source.is_dir() {
+        fs::read_to_string(source.join(MANIFEST_NAME))?
+    } else {
+        let mut archive = ZipArchive::new(File::open(source)?)
+            .map_err(|e| format!("`{}` is not an extension archive : {}", source.display(), e))?;
+        let mut file = archive
+            .by_name(MANIFEST_NAME)
+            .map_err(|e| format!("`{}` has no {} : {}", source.display(), MANIFEST_NAME, e))?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        content
+    };
+
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(unix)]
+fn link_or_copy_dir(from: &Path, to: &Path) -> Result<(), FfError> {
+    Ok(std::os::unix::fs::symlink(fs::canonicalize(from)?, to)?)
+}
+
+#[cfg(not(unix))]
+fn link_or_copy_dir(from: &Path, to: &Path) -> Result<(), FfError> {
+    let mut options = fs_extra::dir::CopyOptions::new();
+    options.copy_inside = true;
+    fs_extra::dir::copy(from, to, &options).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// adds entry of the extension to extensions.json, replacing one with the same id
+fn register_extension(
+    extensions: &Path,
+    id: &str,
+    manifest: &Value,
+    path: &Path,
+) -> Result<(), FfError> {
+    let mut database = match extensions.exists() {
+        true => serde_json::from_str(&fs::read_to_string(extensions)?)?,
+        false => json!({ "schemaVersion": EXTENSIONS_SCHEMA_VERSION, "addons": [] }),
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let entry = json!({
+        "id": id,
+        "location": PROFILE_LOCATION,
+        "version": manifest["version"],
+        "type": "extension",
+        "manifestVersion": manifest["manifest_version"],
+        "defaultLocale": {
+            "name": manifest["name"],
+            "description": manifest["description"],
+        },
+        "visible": true,
+        "active": true,
+        "userDisabled": false,
+        "appDisabled": false,
+        "softDisabled": false,
+        "foreignInstall": false,
+        "seen": true,
+        "installDate": now,
+        "updateDate": now,
+        "path": path.display().to_string(),
+    });
+    match database["addons"].as_array_mut() {
+        None => Err(format!("`{}` has no addons", extensions.display()))?,
+        Some(addons) => {
+            addons.retain(|addon| addon["id"].as_str() != Some(id));
+            addons.push(entry);
+        }
+    }
+
+    paths::write_atomic(extensions, serde_json::to_string(&database)?.as_bytes())
+}
@@ -0,0 +1,125 @@
+use log::info;
+use rusqlite::params;
+
+use std::collections::HashMap;
+
+use crate::bookmarks::{self, Origin, Place};
+use crate::error::FfError;
+use crate::store::SqlitePlacesStore;
+use crate::sync;
+use crate::sync::SyncPlan;
+
+// visit types firefox doesn't count in visit_count of a place:
+// invalid, embed, download, framed link and reload
+const EXCLUDED_VISIT_TYPES: [i64; 5] = [0, 4, 7, 8, 9];
+
+pub type NewHistory = (
+    Vec<Visit>,
+    Option<HashMap<i64, Place>>,
+    Option<HashMap<i64, Origin>>,
+);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visit {
+    pub id: i64,
+    pub from_visit: Option<i64>,
+    pub place_id: i64,
+    pub visit_date: i64,
+    pub visit_type: i64,
+    pub session: Option<i64>,
+}
+
+/// Checks if firefox counts the visit in visit_count of its place.
+pub fn is_counted_visit(visit_type: i64) -> bool {
+    !EXCLUDED_VISIT_TYPES.contains(&visit_type)
+}
+
+/// Id of the latest visit of the profile, `None` if there is no history.
+pub fn get_latest_visit_id(profile_folder: &str) -> Result<Option<i64>, FfError> {
+    let conn = bookmarks::open_places(profile_folder, true)?;
+
+    bookmarks::with_busy_retry(bookmarks::BUSY_RETRY_ATTEMPTS, || {
+        conn.query_row("select max(id) from moz_historyvisits", params![], |row| {
+            row.get(0)
+        })
+    })
+}
+
+/// Visits after `latest_visit_id`, with their places and origins.
+pub fn get_new_history(
+    profile_folder: &str,
+    latest_visit_id: Option<i64>,
+) -> Result<NewHistory, FfError> {
+    let conn = bookmarks::open_places(profile_folder, true)?;
+
+    let mut statement = conn.prepare(
+        "
+            select
+                id, from_visit, place_id, visit_date, visit_type, session
+            from moz_historyvisits
+            where 1=1
+            and id > ?1
+            order by id
+        ",
+    )?;
+    let visits = bookmarks::with_busy_retry(bookmarks::BUSY_RETRY_ATTEMPTS, || {
+        statement
+            .query_map(params![latest_visit_id.unwrap_or(0)], |row| {
+                Ok(Visit {
+                    id: row.get(0)?,
+                    from_visit: row.get(1)?,
+                    place_id: row.get(2)?,
+                    visit_date: row.get(3)?,
+                    visit_type: row.get(4)?,
+                    session: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    if visits.is_empty() {
+        return Ok((visits, None, None));
+    }
+
+    let mut places_ids: Vec<i64> = visits.iter().map(|v| v.place_id).collect();
+    places_ids.sort_unstable();
+    places_ids.dedup();
+    let places = match bookmarks::get_places(profile_folder, &places_ids) {
+        Err(e) => return Err(FfError::context("Error during get visited places", e)),
+        Ok(places) => places,
+    };
+    let origins = match &places {
+        None => None,
+        Some(places) => match bookmarks::get_new_origins(profile_folder, places) {
+            Err(e) => return Err(FfError::context("Error during get new origins", e)),
+            Ok(origins) => origins,
+        },
+    };
+
+    Ok((visits, places, origins))
+}
+
+/// Syncs new visits into the profile.
+///
+/// Changes are planned first and logged before anything is written.
+/// Sync is a single transaction, made while holding the sync lock of the profile.
+pub fn insert_new_history(
+    profile_folder: &str,
+    new_visits: &[Visit],
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+) -> Result<SyncPlan, FfError> {
+    // released when dropped, after the transaction is over
+    let _lock = bookmarks::lock_for_sync(profile_folder)?;
+    let conn = bookmarks::open_places(profile_folder, false)?;
+
+    let store = SqlitePlacesStore::new(&conn);
+
+    // ids are planned from the rows in the target, nothing can be added in between
+    bookmarks::in_transaction(&conn, || {
+        let plan = sync::plan_history_sync(&store, new_visits, new_places, new_origins)?;
+        info!("{}", plan);
+        sync::apply_sync_plan(&store, &plan)?;
+
+        Ok(plan)
+    })
+}
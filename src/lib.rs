@@ -1,2 +1,24 @@
+pub mod archive;
+#[cfg(feature = "bookmarks")]
 pub mod bookmarks;
+pub mod browser;
+pub mod cache;
+pub mod cli;
+pub mod config_file;
+pub mod containers;
+pub mod error;
+pub mod extensions;
+#[cfg(feature = "bookmarks")]
+pub mod history;
+pub mod manifest;
+pub mod paths;
+pub mod profiles;
+pub mod progress;
+pub mod scrub;
 pub mod session;
+#[cfg(feature = "bookmarks")]
+pub mod store;
+#[cfg(feature = "bookmarks")]
+pub mod sync;
+pub mod temp_profile;
+pub mod timestamps;
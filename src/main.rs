@@ -1,39 +1,68 @@
-extern crate clap;
 extern crate dirs;
 extern crate fs_extra;
 extern crate tempfile;
 
-use clap::App;
-use clap::Arg;
 use fs_extra::dir;
 use fs_extra::dir::CopyOptions;
-use regex::Captures;
-use regex::Regex;
+use log::{info, warn};
+#[cfg(feature = "bookmarks")]
 use tempfile::TempDir;
 
 use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
-use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::io::BufWriter;
+use std::io::IsTerminal;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
 use std::time;
+use std::time::Duration;
 use std::time::SystemTime;
 
+use fftemplates::archive;
+use fftemplates::archive::ProfileSource;
+#[cfg(feature = "bookmarks")]
 use fftemplates::bookmarks;
+use fftemplates::browser;
+use fftemplates::cache;
+use fftemplates::cli;
+use fftemplates::cli::Config;
+#[cfg(feature = "bookmarks")]
+use fftemplates::cli::ConflictPolicy;
+use fftemplates::cli::SessionMode;
+use fftemplates::config_file;
+use fftemplates::containers;
+use fftemplates::error;
+use fftemplates::error::FfError;
+use fftemplates::extensions;
+use fftemplates::extensions::EXTENSIONS_JSON;
+#[cfg(feature = "bookmarks")]
+use fftemplates::history;
+use fftemplates::manifest;
+use fftemplates::manifest::Manifest;
+use fftemplates::paths;
+use fftemplates::profiles;
+use fftemplates::profiles::HASH_NAME_SPLIT_CHAR;
+use fftemplates::progress;
+use fftemplates::progress::CopyProgress;
+use fftemplates::scrub;
 use fftemplates::session;
+use fftemplates::temp_profile::TempProfile;
 
-const HASH_NAME_SPLIT_CHAR: char = '.';
-
-const IGNORE_FILES: [&str; 9] = [
+// lock files of profiles::LOCK_FILES are ignored as well
+const IGNORE_FILES: [&str; 8] = [
     "cache2",
     "cookies.sqlite-wal",
     "favicons.sqlite-wal",
-    "lock",
     "places.sqlite-wal",
     "safebrowsing",
     "sessionstore-backups",
@@ -41,358 +70,1870 @@ const IGNORE_FILES: [&str; 9] = [
     "webappsstore.sqllite-wal",
 ];
 
-const EXTENSIONS_JSON: &str = "extensions.json";
+// entries firefox only reads, symlinked in attach mode, everything else
+// is copied to keep the original profile untouched, as firefox writes to
+// databases with their WAL and shm files, json state and storage
+const ATTACH_LINK_ENTRIES: [&str; 3] = ["chrome", "extensions", "features"];
+// folders of downloaded media plugins, like gmp-widevinecdm
+const ATTACH_LINK_PREFIX: &str = "gmp-";
+
+// how often to check if the launched browser is still running when a timeout is set
+const LAUNCH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+// how long to wait after SIGTERM before killing the browser for good
+const LAUNCH_TERMINATE_GRACE: Duration = Duration::from_secs(10);
+// number of stderr lines of the browser reported when it fails
+const LAUNCH_STDERR_LINES: usize = 10;
+// how long to wait for the rest of stderr after the browser exits
+const LAUNCH_STDERR_DRAIN: Duration = Duration::from_secs(1);
+
+#[cfg(not(feature = "bookmarks"))]
+const BOOKMARKS_DISABLED: &str =
+    "Bookmarks are not supported, fftemplates was built without the `bookmarks` feature";
 
 const OPEN_SESSION_FILE_COMMAND: &str = "python3 /usr/bin/fftemplates_open_file.py";
 const SAVE_SESSION_FILE_COMMAND: &str = "python3 /usr/bin/fftemplates_save_file.py";
 
-pub struct Config {
-    pub profile_name: String,
-    pub profile_folder: PathBuf,
-    pub bookmarks_sync: bool,
-    pub session_file_to_load: Option<String>,
-    pub file_to_store_session_to: Option<String>,
-    pub same_load_and_save: Option<bool>,
-    pub session_prompt: bool,
-    pub session_prompt_load_skip: bool,
-    pub session_prompt_save_skip: bool,
+pub struct LaunchOutcome {
+    pub status: ExitStatus,
+    // true if the process had to be terminated after reaching the timeout
+    pub timed_out: bool,
+    // first lines the process wrote to stderr
+    pub stderr: Vec<String>,
 }
 
 fn main() {
-    let matches = App::new(env!("CARGO_PKG_NAME"))
-        .version(env!("CARGO_PKG_VERSION"))
-        .arg(
-            Arg::with_name("base_profile")
-                .help("profile to run")
-                .index(1)
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("bookmarks_sync")
-                .help("sync new bookmarks to original profile")
-                .short("b")
-                .long("--bookmarks"),
-        )
-        .arg(
-            Arg::with_name("load_session")
-                .help("load session file")
-                .takes_value(true)
-                .short("l"),
-        )
-        .arg(
-            Arg::with_name("save_session")
-                .help("save session file after exiting")
-                .takes_value(true)
-                .short("s"),
-        )
-        .arg(
-            Arg::with_name("save_load_session")
-                .conflicts_with("save_session")
-                .conflicts_with("load_session")
-                .help("load session from a file and save session to the same file after exiting")
-                .takes_value(true)
-                .short("L"),
-        )
-        .arg(
-            Arg::with_name("session_file_prompt")
-                .conflicts_with_all(&["load_session", "save_session", "save_load_session"])
-                .help("Show prompt to load/save session file before/after firefox")
-                .short("p"),
-        )
-        .arg(
-            Arg::with_name("session_file_prompt_skip_load")
-                .requires("session_file_prompt")
-                .help("Don't show prompt when starting")
-                .long("prompt-load-skip"),
-        )
-        .arg(
-            Arg::with_name("session_file_prompt_skip_save")
-                .requires("session_file_prompt")
-                .help("Don't show prompt at the end to save session")
-                .long("prompt-save-skip"),
-        )
-        .get_matches();
-
-    let profile_name = matches
-        .value_of("base_profile")
-        .or(Some("default"))
-        .unwrap();
-    let bookmarks_sync = matches.is_present("bookmarks_sync");
-    let mut session_file_to_load = matches.value_of("load_session").map(|v| v.to_string());
-    let mut file_to_store_session_to = matches.value_of("save_session").map(|v| v.to_string());
-    let same_load_and_save = if let Some(load_save) = matches.value_of("save_load_session") {
-        session_file_to_load = Some(load_save.to_string());
-        file_to_store_session_to = Some(load_save.to_string());
-        Some(true)
-    } else {
-        None
+    // sync plan and other progress is logged at info, RUST_LOG overrides it
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let mut conf = match cli::into_config(cli::parse()) {
+        Ok(conf) => conf,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
     };
-    let session_prompt = matches.is_present("session_file_prompt");
-    let session_prompt_load_skip = matches.is_present("session_file_prompt_skip_load");
-    let session_prompt_save_skip = matches.is_present("session_file_prompt_skip_save");
-
-    let profile_folder = Path::new(&dirs::home_dir().unwrap())
-        .join(Path::new(".mozilla"))
-        .join(Path::new("firefox"));
-
-    let conf = Config {
-        profile_name: profile_name.to_string(),
-        profile_folder,
-        bookmarks_sync,
-        session_file_to_load,
-        file_to_store_session_to,
-        same_load_and_save,
-        session_prompt,
-        session_prompt_load_skip,
-        session_prompt_save_skip,
+    // config being replaced may be the one that doesn't parse
+    if conf.init_config {
+        if let Err(e) = init_config(&conf) {
+            println!("Error from init config : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    // template of the config file can stand for a whole launch configuration
+    let file_config = match paths::config_dir()
+        .and_then(|dir| config_file::read_file_config(&dir.join(config_file::CONFIG_FILE_NAME)))
+    {
+        Ok(file_config) => file_config,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
     };
+    let template = config_file::resolve_template(&conf.profile_name, &file_config);
+    config_file::apply_template(&mut conf, template);
+    #[cfg(not(feature = "bookmarks"))]
+    {
+        if conf.tree || conf.count || conf.repair.is_some() || conf.merge_bookmarks.is_some() {
+            println!("{}", BOOKMARKS_DISABLED);
+            return;
+        }
+        // launching still makes sense, just without syncing bookmarks back
+        if conf.bookmarks_sync || conf.sync_history {
+            println!("{}", BOOKMARKS_DISABLED);
+            conf.bookmarks_sync = false;
+            conf.sync_history = false;
+        }
+    }
+    #[cfg(feature = "bookmarks")]
+    {
+        if conf.count {
+            if let Err(e) = print_counts(&conf) {
+                println!("Error from count : {}", error::display_chain(&*e));
+            }
+            return;
+        }
+        if conf.tree {
+            if let Err(e) = print_tree(&conf) {
+                println!("Error from tree : {}", error::display_chain(&*e));
+            }
+            return;
+        }
+        if let Some(name) = &conf.repair {
+            if let Err(e) = repair(&conf, name) {
+                println!("Error from repair : {}", error::display_chain(&*e));
+            }
+            return;
+        }
+        if let Some((source, dest)) = &conf.merge_bookmarks {
+            if let Err(e) = merge_bookmarks(&conf, source, dest) {
+                println!("Error from merge bookmarks : {}", error::display_chain(&*e));
+            }
+            return;
+        }
+    }
+    if let Some((from, to)) = &conf.export_session {
+        match session::export_session(from, to) {
+            Err(e) => println!("Error from export session : {}", error::display_chain(&e)),
+            Ok(()) => println!("Exported session to {}", to.display()),
+        }
+        return;
+    }
+    if let Some((from, to)) = &conf.import_session {
+        match session::import_session(from, to) {
+            Err(e) => println!("Error from import session : {}", error::display_chain(&e)),
+            Ok(()) => println!("Imported session to {}", to.display()),
+        }
+        return;
+    }
+    if let Some((a, b)) = &conf.diff_session {
+        if let Err(e) = print_session_diff(a, b) {
+            println!("Error from diff session : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    if let Some(path) = &conf.check_session {
+        if let Err(e) = print_session_check(path) {
+            println!("Error from check session : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    if let Some(other) = &conf.compare_extensions {
+        if let Err(e) = print_extension_diff(&conf, other) {
+            println!(
+                "Error from compare extensions : {}",
+                error::display_chain(&*e)
+            );
+        }
+        return;
+    }
+    if conf.list_extensions {
+        if let Err(e) = print_extensions(&conf) {
+            println!("Error from list extensions : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    if conf.list {
+        print_profiles();
+        return;
+    }
+    if conf.preview_session {
+        if let Err(e) = print_session_preview(&conf) {
+            println!("Error from preview session : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    if let Some(dir) = &conf.list_sessions {
+        if let Err(e) = print_sessions(dir) {
+            println!("Error from list sessions : {}", error::display_chain(&*e));
+        }
+        return;
+    }
+    if let Some(clone_name) = &conf.clone_name {
+        if let Err(e) = clone(&conf, clone_name) {
+            println!("Error from clone : {}", error::display_chain(&*e));
+        }
+        return;
+    }
     if let Err(e) = run(conf) {
-        println!("Error from run : {}", e);
+        println!("Error from run : {}", error::display_chain(&*e));
     }
 }
 
-fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let mut ignore_entries = HashSet::new();
-    for str_to_ignore in IGNORE_FILES.iter() {
-        ignore_entries.insert(*str_to_ignore);
+fn run(mut config: Config) -> Result<(), Box<dyn Error>> {
+    // scripts keep getting the default profile, only people get asked
+    if config.pick_profile && io::stdin().is_terminal() && io::stdout().is_terminal() {
+        let candidates: Vec<_> = if config.discover_profiles {
+            profiles::discover_all_profiles()
+                .into_iter()
+                .filter(|p| config.browser_name.as_ref().is_none_or(|b| p.browser == *b))
+                .collect()
+        } else {
+            let browser = config
+                .browser_name
+                .clone()
+                .unwrap_or_else(|| "firefox".to_string());
+            profiles::discover_profiles(&[(browser, config.profile_folder.clone())])
+        };
+        // nothing to choose from
+        if candidates.len() > 1 {
+            let picked = profiles::pick_profile(
+                &candidates,
+                &config.profile_name,
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+            )?;
+            if let Some(picked) = picked {
+                if config.browser_name.is_none() {
+                    config.browser_binary = picked.browser;
+                }
+                config.profile_name = picked.name;
+                config.profile_path = Some(picked.path);
+            }
+        }
+    }
+    // profile of a fork is launched with that fork, unless browser was given
+    if config.browser_name.is_none()
+        && config.discover_profiles
+        && config.profile_path.is_none()
+        && config.profile_index.is_none()
+    {
+        if let Some(discovered) = find_discovered_profile(&config)? {
+            config.browser_binary = discovered.browser;
+        }
+    }
+    let ignore_entries = ignored_entries(&config);
+    if config.list_files {
+        return list_files(&config, &ignore_entries);
     }
 
-    let tmp_dir = TempDir::new()?;
-
-    let found_profile_pair = find_profile_folder(&config.profile_folder, &config.profile_name)?;
-
-    let (found_profile_path, _) = match found_profile_pair {
-        None => Err(format!(
-            "No profile with name `{}` found",
-            config.profile_name
-        ))?,
-        Some((p, name)) => (p, name),
+    let found_profile_source = find_base_profile(&config)?;
+    let found_profile_path = found_profile_source.path().to_path_buf();
+    // changes go back to the base profile, unless another profile is the target
+    let mut sync_target_path = match &config.sync_target {
+        None => found_profile_path.clone(),
+        Some(target) => find_sync_target(&config.profile_roots, target)?,
     };
+    // there is nothing to sync back to, when profile comes from an archive
+    let is_archive =
+        matches!(found_profile_source, ProfileSource::Archive(_)) && config.sync_target.is_none();
+    if is_archive && (config.bookmarks_sync || config.sync_history || config.sync_containers) {
+        println!("Profile is an archive, bookmarks, history and containers won't be synced back");
+    }
+    // a profile that was never started has no places database to sync from
+    #[cfg(feature = "bookmarks")]
+    let has_places = is_archive || bookmarks::has_places(&found_profile_path);
+    #[cfg(feature = "bookmarks")]
+    if (config.bookmarks_sync || config.sync_history) && !has_places {
+        warn!("Source profile has no Places database; skipping bookmark sync");
+    }
+    #[cfg(feature = "bookmarks")]
+    let bookmarks_sync = config.bookmarks_sync && !is_archive && has_places;
+    #[cfg(feature = "bookmarks")]
+    let history_sync = config.sync_history && !is_archive && has_places;
+    let sync_containers = config.sync_containers && !is_archive;
+
+    // writing into a profile while a browser uses it, or into a damaged
+    // database, can lose the changes or break the profile further
+    let mut proceed = true;
+    // saved session goes into the target profile as well
+    let sync_session = config.sync_target.is_some()
+        && (config.session_mode.save_file().is_some() || config.session_prompt);
+    // changes synced into a kept temp profile never reach a real one
+    let syncs = config.bookmarks_sync || config.sync_history || sync_containers || sync_session;
+    if syncs && config.sync_target.is_none() {
+        if let Some(source) = manifest::temp_profile_source(&found_profile_path)? {
+            let warning = format!(
+                "`{}` looks like a temp profile made from `{}`",
+                found_profile_path.display(),
+                source.display()
+            );
+            if !config.dry_run && io::stdin().is_terminal() && io::stdout().is_terminal() {
+                println!("{}", warning);
+                if confirm(&format!("Sync into `{}` instead?", source.display()))? {
+                    sync_target_path = source;
+                }
+            } else {
+                proceed &= check_failed(
+                    &config,
+                    &format!(
+                        "{}, changes would be synced into the temp profile, use --sync-target to sync into another one",
+                        warning
+                    ),
+                )?;
+            }
+        }
+    }
+    if syncs && profiles::is_profile_locked(&sync_target_path) {
+        proceed &= check_failed(
+            &config,
+            &format!(
+                "Profile `{}` is used by a running browser",
+                sync_target_path.display()
+            ),
+        )?;
+    }
+    #[cfg(feature = "bookmarks")]
+    {
+        if bookmarks_sync || history_sync {
+            let conn = bookmarks::open_places(folder_str(&sync_target_path)?, true)?;
+            let problems = bookmarks::check_integrity(&conn)?;
+            if !problems.is_empty() {
+                proceed &= check_failed(
+                    &config,
+                    &format!(
+                        "Places database of `{}` is damaged : {}",
+                        sync_target_path.display(),
+                        problems.join(", ")
+                    ),
+                )?;
+            }
+        }
+    }
+    if config.dry_run {
+        if !proceed {
+            return Ok(());
+        }
+        println!(
+            "Would launch {} with a copy of {}",
+            config.browser_binary,
+            found_profile_path.display()
+        );
+        return Ok(());
+    }
 
     let options = CopyOptions::new();
     let start = SystemTime::now();
-    // some unique name for new temp profile
-    let new_tmp_dir_name = format!("{}", start.duration_since(time::UNIX_EPOCH)?.as_millis());
-    let new_tmp_path = tmp_dir.path().join(new_tmp_dir_name);
-    dir::create_all(&new_tmp_path, false)?;
-    let vec = fs::read_dir(&found_profile_path)?
-        .map(|x| x.expect("unable to read profile folder").path())
-        .filter_map(|e| {
-            let mut valid = false;
-            let name = e.as_path().file_name();
-            if let Some(name) = name {
-                let name = name.to_str();
-                if let Some(name) = name {
-                    valid = !ignore_entries.contains(name);
+    let new_tmp_dir_name = match &config.temp_name {
+        Some(name) => name.clone(),
+        // unique, but still recognizable in ps and /tmp
+        None => format!(
+            "fftemplates-{}-{}-{}",
+            config.profile_name.replace(['/', '\\'], "_"),
+            start.duration_since(time::UNIX_EPOCH)?.as_millis(),
+            std::process::id()
+        ),
+    };
+    let mut temp_profile = None;
+    // copy kept in the cache replaces the temp profile, fingerprint is
+    // stored once the copy is ready
+    let mut fingerprint_to_store = None;
+    if config.cache_copy {
+        match &found_profile_source {
+            ProfileSource::Archive(_) => println!("Copies of archived profiles are not cached"),
+            ProfileSource::Directory(found_profile_path) => {
+                let fingerprint = cache::profile_fingerprint(found_profile_path, &ignore_entries)?;
+                let copy_dir = cache::copy_dir(found_profile_path)?;
+                let copy_path = cache::copy_profile_dir(&copy_dir);
+                if cache::read_fingerprint(&copy_dir) == Some(fingerprint) {
+                    println!(
+                        "Base profile is unchanged, reusing copy at {}",
+                        copy_path.display()
+                    );
+                } else {
+                    if copy_path.exists() {
+                        fs::remove_dir_all(&copy_path)?;
+                    }
+                    fingerprint_to_store = Some((copy_dir, fingerprint));
                 }
+                temp_profile = Some(TempProfile::at(copy_path));
             }
-            if valid {
-                Some(e)
-            } else {
-                None
+        }
+    }
+    let mut source_fingerprint = None;
+    if let Some(stage) = &config.stage {
+        if let ProfileSource::Directory(found_profile_path) = &found_profile_source {
+            source_fingerprint = Some(cache::profile_fingerprint(
+                found_profile_path,
+                &ignore_entries,
+            )?);
+        }
+        refresh_stage(stage, &found_profile_path, source_fingerprint)?;
+        temp_profile = Some(TempProfile::at(stage.clone()));
+    }
+    // held until the run ends, so two launches of a name don't share the folder
+    let stable_lock = match config.stable_path {
+        false => None,
+        true => cache::lock_stable_profile_dir(&config.profile_name)?,
+    };
+    match &stable_lock {
+        None if config.stable_path => warn!(
+            "Stable path of `{}` is used by another launch, using a unique temp profile",
+            config.profile_name
+        ),
+        None => {}
+        Some((stable_path, _)) => {
+            // left by a launch that didn't clean up, nothing of it is reused
+            if stable_path.exists() {
+                fs::remove_dir_all(stable_path)?;
             }
-        })
-        .collect();
-    fs_extra::copy_items(&vec, &new_tmp_path, &options)?;
+            temp_profile = Some(TempProfile::at(stable_path.clone()));
+        }
+    }
+    let mut temp_profile = match temp_profile {
+        Some(temp_profile) => temp_profile,
+        None => TempProfile::new(&new_tmp_dir_name)?,
+    };
+    let new_tmp_path = temp_profile.path().to_path_buf();
+    let reuse_copy = new_tmp_path.exists();
+    // copy left by a previous run stays, even if this one fails
+    if reuse_copy {
+        temp_profile.keep();
+    }
+    dir::create_all(&new_tmp_path, false)?;
+    match &found_profile_source {
+        _ if reuse_copy => {}
+        ProfileSource::Archive(_) if config.attach => {
+            Err("Attach mode is not supported for archived profiles")?
+        }
+        ProfileSource::Directory(found_profile_path) if config.attach => {
+            let vec = profiles::plan_copy(found_profile_path, &ignore_entries)?;
+            attach_items(&vec, &new_tmp_path)?;
+            copy_wal_files(found_profile_path, &new_tmp_path, &ignore_entries)?;
+        }
+        source => prepare_profile(
+            source,
+            &new_tmp_path,
+            &ignore_entries,
+            &options,
+            &mut CopyProgress::new(show_progress_bar(&config)),
+        )?,
+    }
+    if let Some((copy_dir, fingerprint)) = fingerprint_to_store {
+        cache::write_fingerprint(&copy_dir, fingerprint)?;
+        temp_profile.keep();
+    }
+    // prefs as copied, before anything below adjusts them
+    let copied_prefs = match config.dump_prefs {
+        false => None,
+        true => Some(read_prefs_file(&new_tmp_path)?),
+    };
+    // loaded extensions are registered before the rewrite, so their paths
+    // go through it like the ones of installed extensions
+    let mut loaded_extension_prefs = vec![];
+    if !config.load_extensions.is_empty() {
+        if config.attach {
+            Err("Extensions can't be loaded in attach mode, extensions folder is shared")?;
+        }
+        for extension in &config.load_extensions {
+            extensions::load_extension(&new_tmp_path, extension).map_err(|e| {
+                FfError::context(
+                    &format!("Error during loading extension `{}`", extension.display()),
+                    e,
+                )
+            })?;
+        }
+        loaded_extension_prefs = extensions::allow_loaded_extensions(&new_tmp_path)?;
+    }
+    // extensions.json is always a copy, so paths in it are rewritten to point
+    // inside of the temp profile, in attach mode this goes through the symlinked
+    // extensions folder
+    //
+    // without rewriting the file is removed instead, firefox then scans the
+    // extensions folder and registers what is there again, which copes with
+    // any path, but extension settings kept in the file are lost and the
+    // first start is slower
     let extensions = new_tmp_path.join(Path::new(EXTENSIONS_JSON));
-    if extensions.exists() {
-        if let Err(e) = adjust_extensions_json(&extensions) {
+    let extension_paths_rewritten = extensions.exists() && !config.no_extension_rewrite;
+    if extension_paths_rewritten {
+        if let Err(e) = extensions::adjust_extensions_json(&extensions) {
             Err(format!("Error during adjusting extensions json : {}", e))?;
         }
+    } else if extensions.exists() {
+        fs::remove_file(&extensions)?;
+        info!(
+            "Removed `{}`, firefox rebuilds it on start",
+            EXTENSIONS_JSON
+        );
     }
 
     let profile_folder_path = format!("{}", new_tmp_path.display());
-    if config.session_file_to_load.is_some()
-        || config.file_to_store_session_to.is_some()
+    let mut adjusted_prefs = loaded_extension_prefs;
+    if config.scrub {
+        adjusted_prefs.extend(scrub::scrub_profile(&new_tmp_path)?);
+    }
+    let session_used = config.session_mode != SessionMode::None
         || config.session_prompt
-    {
-        session::adjust_profile_settings(
+        || config.session_dir.is_some();
+    if (session_used || config.dump_prefs) && !config.no_session_pref_changes {
+        adjusted_prefs.extend(session::adjust_profile_settings(
             &profile_folder_path,
-            config.file_to_store_session_to.is_some() || config.session_prompt,
-        )?;
+            config.session_mode.save_file().is_some() || config.session_prompt,
+            session_used,
+        )?);
+    }
+    let overlay = paths::config_dir()?.join(session::OVERLAY_FILE_NAME);
+    adjusted_prefs.extend(session::apply_overlay_file(
+        &profile_folder_path,
+        &overlay,
+        config.overlay_overrides,
+    )?);
+    if let Some(copied_prefs) = copied_prefs {
+        return print_prefs_diff(&copied_prefs, &read_prefs_file(&new_tmp_path)?);
     }
 
     let session_file_to_load = if config.session_prompt && !config.session_prompt_load_skip {
         if let Some(file) = get_open_file()? {
             Some(file)
         } else {
-            config.session_file_to_load.clone()
+            config.session_mode.load_file().map(|file| file.to_string())
         }
+    } else if let (None, Some(dir)) = (config.session_mode.load_file(), &config.session_dir) {
+        let latest = session::latest_session_in(dir)?;
+        if latest.is_none() {
+            println!("No session to load in `{}`", dir.display());
+        }
+        latest.map(|file| file.to_string_lossy().into_owned())
     } else {
-        config.session_file_to_load.clone()
+        config.session_mode.load_file().map(|file| file.to_string())
     };
-    if session_file_to_load.is_some() {
-        session::add_sessionstore_file(
-            &session_file_to_load.unwrap(),
+    if let Some(session_file_to_load) = &session_file_to_load {
+        let loaded_session = session::add_sessionstore_file(
+            session_file_to_load,
             &profile_folder_path,
-            if let Some(same_file) = config.same_load_and_save {
-                if same_file {
-                    false
-                } else {
-                    true
-                }
-            } else {
-                true
-            },
+            config.session_target.as_deref(),
+            config.session_mode.fail_if_load_missing(),
+            config.session_load_mode,
         )?;
+        // the file firefox reads is touched, source keeps its time
+        if let (Some(loaded_session), true) = (loaded_session, config.touch_session) {
+            session::touch_session(loaded_session)?;
+        }
     }
 
-    let command = format!("firefox --profile {}", new_tmp_path.display());
+    let mut command = match browser::find_in_path(&config.browser_binary) {
+        Some(_) => vec![config.browser_binary.clone()],
+        // snap, flatpak and tarball installs don't always put firefox in PATH
+        None if config.browser_binary == browser::DEFAULT_BROWSER => {
+            match browser::resolve_browser_binary() {
+                None => Err(format!(
+                    "Firefox not found, `{}` is not in PATH or any known install location, set browser binary with --browser",
+                    config.browser_binary
+                ))?,
+                Some(install) => {
+                    info!("Firefox is not in PATH, using `{}`", install.join(" "));
+                    install
+                }
+            }
+        }
+        None => Err(format!(
+            "Firefox not found, `{}` is not in PATH, set browser binary with --browser",
+            config.browser_binary
+        ))?,
+    };
+    command.extend(["--profile".to_string(), new_tmp_path.display().to_string()]);
+    command.extend(config.extra_args.iter().cloned());
+    // sandbox or other wrapper gets the whole browser command as its arguments
+    let command: Vec<String> = config.wrapper.iter().cloned().chain(command).collect();
 
-    let latest_bookmark = match config.bookmarks_sync {
+    #[cfg(feature = "bookmarks")]
+    let latest_bookmark = match bookmarks_sync {
         false => None,
         true => {
-            // TODO: fix unwrap
-            match bookmarks::get_latest_bookmark(found_profile_path.as_os_str().to_str().unwrap()) {
+            // temp profile is still the same as the base one, and can be read even for archives
+            match bookmarks::get_latest_bookmark(folder_str(&new_tmp_path)?) {
                 Err(e) => {
-                    return Err(format!("Error during get latest bookmark : {}", e))?;
+                    return Err(FfError::context("Error during get latest bookmark", e))?;
                 }
                 Ok(bookmark) => bookmark,
             }
         }
     };
 
-    execute_cmd(&command)?;
+    // bookmarks of the copy, the ones removed from the target when all are gone
+    #[cfg(feature = "bookmarks")]
+    let baseline_guids = match bookmarks_sync && config.sync_deletions {
+        false => vec![],
+        true => bookmarks::get_bookmark_guids(folder_str(&new_tmp_path)?)?,
+    };
+
+    #[cfg(feature = "bookmarks")]
+    let latest_visit_id = match history_sync {
+        false => None,
+        true => match history::get_latest_visit_id(folder_str(&new_tmp_path)?) {
+            Err(e) => {
+                return Err(FfError::context("Error during get latest visit", e))?;
+            }
+            Ok(id) => id,
+        },
+    };
+
+    // staged profile is kept as well, manifest tells when it can be reused
+    if config.keep_temp || config.stage.is_some() {
+        let mut entries = vec![];
+        for entry in fs::read_dir(&new_tmp_path)? {
+            entries.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        entries.sort();
+        let manifest = Manifest {
+            source_profile: found_profile_path.clone(),
+            created: start.duration_since(time::UNIX_EPOCH)?.as_secs(),
+            entries,
+            adjusted_prefs,
+            session_file: session_file_to_load,
+            extension_paths_rewritten,
+            #[cfg(feature = "bookmarks")]
+            baseline_bookmark_id: latest_bookmark.as_ref().map(|b| b.id),
+            #[cfg(not(feature = "bookmarks"))]
+            baseline_bookmark_id: None,
+            source_fingerprint,
+        };
+        manifest::write_manifest(&new_tmp_path, &manifest)?;
+        // staged profile is complete, it is reused by the next run
+        if config.stage.is_some() {
+            temp_profile.keep();
+        }
+    }
+
+    if config.print_command {
+        temp_profile.keep();
+        let variables = config
+            .env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, shell_quote(value)));
+        let args = command.iter().map(|arg| shell_quote(arg));
+        println!("{}", variables.chain(args).collect::<Vec<_>>().join(" "));
+        return Ok(());
+    }
+
+    #[cfg(feature = "bookmarks")]
+    let watch = match (config.watch, bookmarks_sync, &latest_bookmark) {
+        (Some(interval), true, Some(baseline)) => Some(watch_bookmarks(
+            interval,
+            new_tmp_path.clone(),
+            sync_target_path.clone(),
+            baseline.clone(),
+            bookmarks::EntryFilter {
+                folder: config.sync_folder.clone(),
+                since: config.since,
+            },
+            config.target_folder.clone(),
+            config.on_conflict,
+        )),
+        _ => None,
+    };
+
+    let outcome = execute_cmd(&command, &config.env, config.timeout, config.quiet_firefox)?;
+
+    // bookmarks synced while firefox was running are not synced again
+    #[cfg(feature = "bookmarks")]
+    let latest_bookmark = match watch {
+        None => latest_bookmark,
+        Some((stop, watcher)) => {
+            drop(stop);
+            match watcher.join() {
+                Err(_) => latest_bookmark,
+                Ok(synced) => Some(synced),
+            }
+        }
+    };
+    if outcome.timed_out {
+        println!("Firefox was closed after reaching the timeout");
+    }
+    // firefox killed after timeout is treated as crashed as well,
+    // since it might not had a chance to write the session
+    let crashed = !outcome.status.success();
+    if crashed {
+        println!(
+            "Firefox didn't exit cleanly, `{}` : {}",
+            config.browser_binary, outcome.status
+        );
+        for line in &outcome.stderr {
+            println!("  {}", line);
+        }
+    }
 
-    let file_to_store_session_to = if config.session_prompt && !config.session_prompt_save_skip {
+    let file_to_store_session_to = if crashed && config.no_crash_session {
+        if config.session_mode.save_file().is_some() || config.session_prompt {
+            println!("Not saving session after firefox crash");
+        }
+        None
+    } else if config.session_prompt && !config.session_prompt_save_skip {
         if let Some(file) = get_save_file()? {
             Some(file)
         } else {
-            config.file_to_store_session_to.clone()
+            config.session_mode.save_file().map(|file| file.to_string())
         }
     } else {
-        config.file_to_store_session_to.clone()
+        config.session_mode.save_file().map(|file| file.to_string())
     };
-    if file_to_store_session_to.is_some() {
-        session::save_sessionstore_file(&file_to_store_session_to.unwrap(), &profile_folder_path)?;
+    let session_saved = file_to_store_session_to.is_some();
+    if let Some(file_to_store_session_to) = file_to_store_session_to {
+        if crashed {
+            session::save_recovery_sessionstore_file(
+                &file_to_store_session_to,
+                &profile_folder_path,
+            )?;
+        } else if config.always_save {
+            session::save_sessionstore_file(&file_to_store_session_to, &profile_folder_path, None)?;
+        } else if !session::save_changed_sessionstore_file(
+            &file_to_store_session_to,
+            &profile_folder_path,
+            None,
+        )? {
+            println!(
+                "Session is unchanged, not saving `{}`",
+                file_to_store_session_to
+            );
+        }
+    }
+    // saved session of a clean exit becomes the session of the target profile
+    if sync_session
+        && session_saved
+        && !crashed
+        && new_tmp_path
+            .join(session::SESSIONSTORE_DEFAULT_NAME)
+            .exists()
+    {
+        session::save_sessionstore_file(
+            sync_target_path.join(session::SESSIONSTORE_DEFAULT_NAME),
+            &profile_folder_path,
+            None,
+        )?;
+    }
+
+    if sync_containers {
+        if let Err(e) = containers::sync_containers(&new_tmp_path, &sync_target_path) {
+            eprintln!("Error during sync containers : {}", e);
+        }
+    }
+
+    // history goes first, so places it inserts keep their visit counts
+    // and are only reused by the bookmarks
+    #[cfg(feature = "bookmarks")]
+    if history_sync {
+        let (new_visits, new_places, new_origins) =
+            match history::get_new_history(folder_str(&new_tmp_path)?, latest_visit_id) {
+                Err(e) => {
+                    return Err(FfError::context("Error during get new history", e))?;
+                }
+                Ok(history) => history,
+            };
+        if !new_visits.is_empty() {
+            if let Err(e) = history::insert_new_history(
+                folder_str(&sync_target_path)?,
+                &new_visits,
+                new_places.as_ref(),
+                new_origins.as_ref(),
+            ) {
+                eprintln!(
+                    "Error during insert new history : {}",
+                    error::display_chain(&e)
+                );
+            }
+        }
     }
 
-    if config.bookmarks_sync {
-        if let Some(latest_bookmark) = latest_bookmark {
-            // TODO: fix unwrap
-            let (mut new_bookmarks, mut new_places, mut new_origins) =
-                match bookmarks::get_new_entries(
-                    new_tmp_path.as_os_str().to_str().unwrap(),
+    // every run that asked for the sync tells what came of it
+    #[cfg(feature = "bookmarks")]
+    if config.bookmarks_sync && !bookmarks_sync {
+        println!(
+            "Bookmark sync skipped ({})",
+            match is_archive {
+                true => "profile is an archive",
+                false => "no Places database",
+            }
+        );
+    }
+    #[cfg(feature = "bookmarks")]
+    if bookmarks_sync {
+        match latest_bookmark {
+            None => {
+                println!("Bookmark sync skipped (temp profile had no bookmarks to compare with)")
+            }
+            Some(latest_bookmark) => {
+                let tmp_folder = folder_str(&new_tmp_path)?;
+                let sync_target_folder = folder_str(&sync_target_path)?;
+                let all_removed = bookmarks::all_bookmarks_removed(tmp_folder, &latest_bookmark)
+                    .map_err(|e| FfError::context("Error during check for removed bookmarks", e))?;
+                if all_removed {
+                    sync_removed_bookmarks(&config, &sync_target_path, &baseline_guids)?;
+                }
+                let (new_bookmarks, new_places, new_origins) = match bookmarks::get_new_entries(
+                    tmp_folder,
                     &latest_bookmark,
+                    &bookmarks::EntryFilter {
+                        folder: config.sync_folder.clone(),
+                        since: config.since,
+                    },
                 ) {
                     Err(e) => {
-                        return Err(format!("Error during get new entries : {}", e))?;
+                        return Err(FfError::context("Error during get new entries", e))?;
                     }
                     Ok(entries) => entries,
                 };
-            // TODO: fix unwrap
-            if let Err(e) = bookmarks::insert_new_entries(
-                found_profile_path.as_os_str().to_str().unwrap(),
-                new_bookmarks.as_mut(),
-                new_places.as_mut(),
-                new_origins.as_mut(),
+                if config.dry_run_sql {
+                    let statements = bookmarks::new_entries_sql(
+                        sync_target_folder,
+                        new_bookmarks.as_deref(),
+                        new_places.as_ref(),
+                        new_origins.as_ref(),
+                        config.target_folder.as_deref(),
+                        config.on_conflict,
+                    )
+                    .map_err(|e| FfError::context("Error during rendering of sync sql", e))?;
+                    print_sql(&statements);
+                } else {
+                    match bookmarks::insert_new_entries_into(
+                        sync_target_folder,
+                        new_bookmarks.as_deref(),
+                        new_places.as_ref(),
+                        new_origins.as_ref(),
+                        config.target_folder.as_deref(),
+                        config.on_conflict,
+                    ) {
+                        Err(e) => {
+                            eprintln!(
+                                "Error during insert new entries : {}",
+                                error::display_chain(&e)
+                            );
+                            println!(
+                            "Bookmark sync skipped (places database of `{}` couldn't be written)",
+                            sync_target_path.display()
+                        );
+                        }
+                        Ok(plan) => match plan.inserted_bookmarks() + plan.updated_bookmarks() {
+                            0 => println!("No new bookmarks to sync"),
+                            synced => println!("Synced {} bookmarks", synced),
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    if config.keep_temp {
+        temp_profile.keep();
+        println!("Temp profile kept at {}", new_tmp_path.display());
+    } else {
+        temp_profile.close()?;
+    }
+
+    Ok(())
+}
+
+// removing every bookmark is more likely a mistake than a cleanup, so it only
+// reaches the target when asked for and confirmed
+#[cfg(feature = "bookmarks")]
+fn sync_removed_bookmarks(
+    config: &Config,
+    target: &Path,
+    guids: &[String],
+) -> Result<(), Box<dyn Error>> {
+    if !config.sync_deletions {
+        warn!("All bookmarks were removed in the temp profile; not propagating deletion without --sync-deletions");
+        return Ok(());
+    }
+    let confirmed = config.force
+        || (io::stdin().is_terminal()
+            && io::stdout().is_terminal()
+            && confirm(&format!(
+                "All bookmarks were removed in the temp profile, remove {} bookmarks from `{}` too?",
+                guids.len(),
+                target.display()
+            ))?);
+    if !confirmed {
+        warn!("All bookmarks were removed in the temp profile; not propagating deletion without confirmation or --force");
+        return Ok(());
+    }
+    let removed = bookmarks::remove_bookmarks(folder_str(target)?, guids)?;
+    println!("Removed {} bookmarks from `{}`", removed, target.display());
+
+    Ok(())
+}
+
+// syncs new bookmarks of the running firefox every interval, until the sender
+// is dropped, the thread returns the latest bookmark synced by then
+#[cfg(feature = "bookmarks")]
+fn watch_bookmarks(
+    interval: Duration,
+    profile: PathBuf,
+    target: PathBuf,
+    baseline: bookmarks::Bookmark,
+    filter: bookmarks::EntryFilter,
+    target_folder: Option<String>,
+    on_conflict: ConflictPolicy,
+) -> (mpsc::Sender<()>, thread::JoinHandle<bookmarks::Bookmark>) {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let watcher = thread::spawn(move || {
+        let mut baseline = baseline;
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+            match sync_running_bookmarks(
+                &profile,
+                &target,
+                &baseline,
+                &filter,
+                target_folder.as_deref(),
+                on_conflict,
             ) {
-                eprintln!("Error during insert new entries : {}", e);
+                Err(e) => warn!(
+                    "Unable to sync bookmarks of the running firefox : {}",
+                    error::display_chain(&*e)
+                ),
+                Ok(None) => {}
+                Ok(Some(latest)) => baseline = latest,
             }
         }
+        baseline
+    });
+
+    (stop, watcher)
+}
+
+// syncs bookmarks added after the baseline from a snapshot of the database,
+// returns the latest bookmark of the snapshot if there are new ones
+#[cfg(feature = "bookmarks")]
+fn sync_running_bookmarks(
+    profile: &Path,
+    target: &Path,
+    baseline: &bookmarks::Bookmark,
+    filter: &bookmarks::EntryFilter,
+    target_folder: Option<&str>,
+    on_conflict: ConflictPolicy,
+) -> Result<Option<bookmarks::Bookmark>, Box<dyn Error>> {
+    let snapshot = TempDir::new()?;
+    bookmarks::snapshot_places(profile, snapshot.path())?;
+    let snapshot_folder = folder_str(snapshot.path())?;
+    let latest = match bookmarks::get_latest_bookmark(snapshot_folder)? {
+        Some(latest) if latest.id > baseline.id => latest,
+        _ => return Ok(None),
+    };
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(snapshot_folder, baseline, filter)?;
+    if new_bookmarks.is_some() {
+        let plan = bookmarks::insert_new_entries_into(
+            folder_str(target)?,
+            new_bookmarks.as_deref(),
+            new_places.as_ref(),
+            new_origins.as_ref(),
+            target_folder,
+            on_conflict,
+        )?;
+        info!(
+            "Synced {} bookmarks of the running firefox",
+            plan.inserted_bookmarks()
+        );
     }
 
-    tmp_dir.close()?;
+    Ok(Some(latest))
+}
+
+// copies items into the folder, symlinking only the read-only ones
+#[cfg(unix)]
+fn attach_items(items: &[PathBuf], to: &Path) -> Result<(), Box<dyn Error>> {
+    let mut to_copy = vec![];
+    for item in items {
+        let name = match item.file_name() {
+            None => continue,
+            Some(name) => name,
+        };
+        let read_only = ATTACH_LINK_ENTRIES.iter().any(|entry| *entry == name)
+            || name.to_string_lossy().starts_with(ATTACH_LINK_PREFIX);
+        if read_only {
+            std::os::unix::fs::symlink(item, to.join(name))?;
+        } else {
+            to_copy.push(item.clone());
+        }
+    }
+    fs_extra::copy_items(&to_copy, to, &CopyOptions::new())?;
 
     Ok(())
 }
 
-fn adjust_extensions_json(extensions: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let mut content = String::new();
-    {
-        let file = File::open(extensions)?;
-        let mut buf_reader = BufReader::new(file);
-        buf_reader.read_to_string(&mut content)?;
-    }
-
-    let mut temp_path = Path::new(extensions).to_path_buf();
-    temp_path.pop();
-    let re = Regex::new(
-        r#"(?x)
-    ("path":)                       # starting with "path":
-    (")(                              # "
-    ([\w\W--"]+)                    # any number of words or characters except for " to avoid going over the value in json
-    (extensions[\w\W--"]+\.xpi)     # trying to match ending of extenstions/some.extension@name.xpi
-    )(")                              # "
-    "#,
+#[cfg(not(unix))]
+fn attach_items(_items: &[PathBuf], _to: &Path) -> Result<(), Box<dyn Error>> {
+    Err("Attach mode is only supported on unix systems")?
+}
+
+// creates a new permanent profile next to the other ones, registered in profiles.ini
+fn clone(config: &Config, new_name: &str) -> Result<(), Box<dyn Error>> {
+    let found_profile_source = find_base_profile(config)?;
+    if profiles::find_profile_in(&config.profile_folder, new_name)?.is_some() {
+        Err(format!("Profile with name `{}` already exists", new_name))?;
+    }
+
+    let new_folder_name = format!(
+        "{}{}{}",
+        profiles::generate_profile_hash(),
+        HASH_NAME_SPLIT_CHAR,
+        new_name
+    );
+    let new_profile_path = config.profile_folder.join(&new_folder_name);
+    dir::create_all(&new_profile_path, false)?;
+
+    prepare_profile(
+        &found_profile_source,
+        &new_profile_path,
+        &ignored_entries(config),
+        &CopyOptions::new(),
+        &mut CopyProgress::new(show_progress_bar(config)),
     )?;
+    let extensions = new_profile_path.join(Path::new(EXTENSIONS_JSON));
+    if extensions.exists() {
+        if let Err(e) = extensions::adjust_extensions_json(&extensions) {
+            Err(format!("Error during adjusting extensions json : {}", e))?;
+        }
+    }
 
-    let results = re.replace_all(content.as_str(), |caps: &Captures| {
-        format!(
-            "{}{}{}{}",
-            &caps[1],
-            &caps[2],
-            temp_path.join(&caps[5]).display(),
-            &caps[6]
-        )
-    });
+    profiles::add_profile_to_ini(&config.profile_folder, new_name, &new_folder_name)?;
+    println!(
+        "Created profile `{}` at {}",
+        new_name,
+        new_profile_path.display()
+    );
 
-    {
-        let file = File::create(extensions)?;
-        let mut buf_writer = BufWriter::new(file);
-        buf_writer.write_all(results.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(feature = "bookmarks")]
+fn print_tree(config: &Config) -> Result<(), Box<dyn Error>> {
+    let found_profile_source = find_base_profile(config)?;
+    // archived profile has to be extracted to read anything from it
+    let extracted_dir = TempDir::new()?;
+    let found_profile_path = match found_profile_source {
+        ProfileSource::Directory(path) => path,
+        ProfileSource::Archive(path) => {
+            archive::extract_archive(path.as_path(), extracted_dir.path())?;
+            extracted_dir.path().to_path_buf()
+        }
+    };
+
+    let conn = bookmarks::open_places(folder_str(&found_profile_path)?, true)?;
+    let tree = bookmarks::build_bookmark_tree(&conn)?;
+    // places root itself has no title, start from its children
+    if let bookmarks::BookmarkNode::Folder { children, .. } = &tree {
+        for child in children {
+            print_tree_node(child, 0, config.tree_depth);
+        }
     }
 
     Ok(())
 }
 
-fn find_profile_folder<P: AsRef<Path>>(
-    profile_folder: P,
-    profile_name: &str,
-) -> Result<Option<(PathBuf, String)>, Box<dyn Error>> {
-    let mut found = None;
+#[cfg(feature = "bookmarks")]
+fn print_counts(config: &Config) -> Result<(), Box<dyn Error>> {
+    let found_profile_path = match find_base_profile(config)? {
+        ProfileSource::Archive(_) => Err("Counting is not supported for archives")?,
+        ProfileSource::Directory(p) => p,
+    };
 
-    for entry in fs::read_dir(profile_folder)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let entry_name = entry
-            .file_name()
-            .into_string()
-            .expect("Error during path to string");
-        if !entry_name.contains(HASH_NAME_SPLIT_CHAR) {
+    let conn = bookmarks::open_places(folder_str(&found_profile_path)?, true)?;
+    let counts = bookmarks::profile_counts(&conn)?;
+    println!(
+        "bookmarks : {} ({} folders, {} links, {} separators)",
+        counts.folders + counts.links + counts.separators,
+        counts.folders,
+        counts.links,
+        counts.separators
+    );
+    println!("places : {}", counts.places);
+    println!("origins : {}", counts.origins);
+
+    Ok(())
+}
+
+#[cfg(feature = "bookmarks")]
+fn repair(config: &Config, name: &str) -> Result<(), Box<dyn Error>> {
+    let profile_path = find_profile_directory(config, name, "Repairing")?;
+
+    let profile_folder = folder_str(&profile_path)?;
+    let backup_file = bookmarks::backup_places(profile_folder)?;
+    println!("Backed up places to {}", backup_file.display());
+
+    let conn = bookmarks::open_places(profile_folder, false)?;
+    let report = bookmarks::repair_places(&conn)?;
+    println!("orphaned places : {}", report.orphaned_places);
+    println!("foreign counts fixed : {}", report.foreign_counts_fixed);
+    println!("removed origins : {}", report.removed_origins);
+
+    Ok(())
+}
+
+// everything is merged, so running it again only skips what is already there
+#[cfg(feature = "bookmarks")]
+fn merge_bookmarks(config: &Config, source: &str, dest: &str) -> Result<(), Box<dyn Error>> {
+    let source_path = find_profile_directory(config, source, "Merging")?;
+    let dest_path = find_profile_directory(config, dest, "Merging")?;
+
+    let (all_bookmarks, all_places, all_origins) = bookmarks::get_all_entries(
+        folder_str(&source_path)?,
+        &bookmarks::EntryFilter {
+            since: config.since,
+            ..bookmarks::EntryFilter::default()
+        },
+    )?;
+    let dest_folder = folder_str(&dest_path)?;
+    if config.dry_run_sql {
+        print_sql(&bookmarks::new_entries_sql(
+            dest_folder,
+            all_bookmarks.as_deref(),
+            all_places.as_ref(),
+            all_origins.as_ref(),
+            config.target_folder.as_deref(),
+            config.on_conflict,
+        )?);
+        return Ok(());
+    }
+    let backup_file = bookmarks::backup_places(dest_folder)?;
+    println!("Backed up places to {}", backup_file.display());
+    let plan = bookmarks::insert_new_entries_into(
+        dest_folder,
+        all_bookmarks.as_deref(),
+        all_places.as_ref(),
+        all_origins.as_ref(),
+        config.target_folder.as_deref(),
+        config.on_conflict,
+    )?;
+    println!(
+        "Merged {} bookmarks, {} were already in `{}`",
+        plan.inserted_bookmarks(),
+        plan.reused_bookmarks(),
+        dest
+    );
+
+    Ok(())
+}
+
+// statements are wrapped in a transaction, so the output can be piped into sqlite3
+#[cfg(feature = "bookmarks")]
+fn print_sql(statements: &[String]) {
+    if statements.is_empty() {
+        println!("-- nothing to sync");
+        return;
+    }
+    println!("begin transaction;");
+    for statement in statements {
+        println!("{}", statement);
+    }
+    println!("commit;");
+}
+
+// places database can only be changed in a profile folder, not an archive
+#[cfg(feature = "bookmarks")]
+fn find_profile_directory(
+    config: &Config,
+    name: &str,
+    action: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    match profiles::find_profile(&config.profile_roots, name)? {
+        None => Err(profile_not_found(&config.profile_roots, name))?,
+        Some(found) => match found.source {
+            ProfileSource::Archive(_) => Err(format!("{} is not supported for archives", action))?,
+            ProfileSource::Directory(p) => Ok(p),
+        },
+    }
+}
+
+#[cfg(feature = "bookmarks")]
+fn print_tree_node(node: &bookmarks::BookmarkNode, depth: usize, max_depth: Option<usize>) {
+    if let Some(max_depth) = max_depth {
+        if depth >= max_depth {
+            return;
+        }
+    }
+
+    let indent = "  ".repeat(depth);
+    match node {
+        bookmarks::BookmarkNode::Folder { title, children } => {
+            println!("{}[+] {}", indent, title.as_deref().unwrap_or(""));
+            for child in children {
+                print_tree_node(child, depth + 1, max_depth);
+            }
+        }
+        bookmarks::BookmarkNode::Link { title, url } => println!(
+            "{}{} ({})",
+            indent,
+            title.as_deref().unwrap_or(""),
+            url.as_deref().unwrap_or("")
+        ),
+        bookmarks::BookmarkNode::Separator => println!("{}----", indent),
+    }
+}
+
+fn print_sessions(dir: &Path) -> Result<(), Box<dyn Error>> {
+    for info in session::list_sessions(dir)? {
+        let age = match info.modified.map(|m| m.elapsed()) {
+            Some(Ok(age)) => format_age(age),
+            _ => "unknown".to_string(),
+        };
+        match info.error {
+            Some(e) => println!("{}\t{}\terror : {}", info.path.display(), age, e),
+            None => println!(
+                "{}\t{}\t{} windows, {} tabs",
+                info.path.display(),
+                age,
+                info.windows,
+                info.tabs
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_session_diff(a: &Path, b: &Path) -> Result<(), Box<dyn Error>> {
+    let diff = session::diff_sessions(a, b)?;
+    if diff.is_empty() {
+        println!("Sessions have the same tabs");
+        return Ok(());
+    }
+
+    for (i, window) in diff.windows.iter().enumerate() {
+        if window.is_empty() {
             continue;
         }
-        let name_split: Vec<_> = entry_name.splitn(2, HASH_NAME_SPLIT_CHAR).collect();
-        if name_split.len() != 2 {
-            panic!(format!(
-                "Not split character `{}` in file name",
-                HASH_NAME_SPLIT_CHAR
-            ));
+        println!("window {} :", i + 1);
+        for url in &window.added {
+            println!("  + {}", url);
         }
-        let entry_profile_name = name_split[1];
-        if entry_profile_name == profile_name {
-            found = Some((entry_path, entry_name));
-            break;
+        for url in &window.removed {
+            println!("  - {}", url);
+        }
+        if window.reordered {
+            println!("  ~ tabs reordered");
         }
     }
 
-    Ok(found)
+    Ok(())
 }
 
-pub fn execute_cmd(cmd: &String) -> Result<(), Box<dyn Error>> {
-    let cmd_split: Vec<_> = cmd.split(' ').collect();
-    if cmd_split.len() < 1 || cmd_split[0] == "" {
-        return Err("No command specified")?;
+fn print_session_check(path: &Path) -> Result<(), Box<dyn Error>> {
+    let report = session::validate_session(path)?;
+    println!("{} windows, {} tabs", report.windows, report.tabs);
+    if report.warnings.is_empty() {
+        println!("Session can be fully restored");
+    }
+    for warning in &report.warnings {
+        println!("  ! {}", warning);
+    }
+
+    Ok(())
+}
+
+fn read_prefs_file(profile: &Path) -> Result<String, Box<dyn Error>> {
+    let prefs = profile.join(session::PROFILE_FILE_NAME);
+    if !prefs.exists() {
+        return Ok(String::new());
+    }
+    Ok(fs::read_to_string(prefs)?)
+}
+
+fn print_prefs_diff(original: &str, adjusted: &str) -> Result<(), Box<dyn Error>> {
+    let changes = session::diff_prefs(original, adjusted)?;
+    if changes.is_empty() {
+        println!("No prefs were adjusted");
     }
+    for change in changes {
+        if let Some(old) = &change.old {
+            println!("- user_pref(\"{}\", {});", change.name, old);
+        }
+        if let Some(new) = &change.new {
+            println!("+ user_pref(\"{}\", {});", change.name, new);
+        }
+    }
+    Ok(())
+}
 
-    let proc;
-    if cmd_split.len() < 2 {
-        proc = Command::new(cmd_split[0]).spawn()?;
+// extensions of the base profile next to the ones of the other profile,
+// given by its folder or looked up by name
+fn print_extension_diff(config: &Config, other: &str) -> Result<(), Box<dyn Error>> {
+    let profile = match find_base_profile(config)? {
+        ProfileSource::Archive(path) => Err(format!(
+            "`{}` is an archive, extensions of archived profiles can't be compared",
+            path.display()
+        ))?,
+        ProfileSource::Directory(path) => path,
+    };
+    let other = if Path::new(other).is_dir() {
+        PathBuf::from(other)
     } else {
-        proc = Command::new(cmd_split[0])
-            .args(&cmd_split[1..cmd_split.len()])
-            .spawn()?;
+        match profiles::find_profile(&config.profile_roots, other)? {
+            None => Err(profile_not_found(&config.profile_roots, other))?,
+            Some(found) => match found.source {
+                ProfileSource::Archive(path) => Err(format!(
+                    "`{}` is an archive, extensions of archived profiles can't be compared",
+                    path.display()
+                ))?,
+                ProfileSource::Directory(path) => path,
+            },
+        }
+    };
+    for path in [&profile, &other] {
+        if !path.join(EXTENSIONS_JSON).exists() {
+            println!(
+                "`{}` has no {}, it is compared as a profile without extensions",
+                path.display(),
+                EXTENSIONS_JSON
+            );
+        }
     }
 
-    let _ = proc.wait_with_output()?;
+    let diff = extensions::compare_extensions(&profile, &other)?;
+    if diff.is_empty() {
+        println!("Profiles have the same extensions");
+        return Ok(());
+    }
+    if !diff.only_in_a.is_empty() {
+        println!("only in {} :", profile.display());
+        for extension in &diff.only_in_a {
+            println!("  {} {}", extension.id, extension.version);
+        }
+    }
+    if !diff.only_in_b.is_empty() {
+        println!("only in {} :", other.display());
+        for extension in &diff.only_in_b {
+            println!("  {} {}", extension.id, extension.version);
+        }
+    }
+    if !diff.different_versions.is_empty() {
+        println!("different versions :");
+        for (id, version, other_version) in &diff.different_versions {
+            println!("  {} {} -> {}", id, version, other_version);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_extensions(config: &Config) -> Result<(), Box<dyn Error>> {
+    let profile = match find_base_profile(config)? {
+        ProfileSource::Archive(path) => Err(format!(
+            "`{}` is an archive, extensions of archived profiles can't be listed",
+            path.display()
+        ))?,
+        ProfileSource::Directory(path) => path,
+    };
+    let listed = extensions::list_extensions(&profile)?;
+    if listed.is_empty() {
+        println!("No extensions installed in {}", profile.display());
+        return Ok(());
+    }
+
+    for extension in &listed {
+        let mut line = format!(
+            "{} {}",
+            extension.id,
+            extension.version.as_deref().unwrap_or("?")
+        );
+        if let Some(name) = &extension.name {
+            line.push_str(&format!(" ({})", name));
+        }
+        match extension.enabled {
+            Some(true) => line.push_str(" enabled"),
+            Some(false) => line.push_str(" disabled"),
+            None => {}
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn print_session_preview(config: &Config) -> Result<(), Box<dyn Error>> {
+    let file = match config.session_mode.load_file() {
+        None => Err("No session file to preview, set one with -l or -L")?,
+        Some(file) => file,
+    };
+    let windows = session::read_session_urls(file)?;
+    let tabs: usize = windows.iter().map(|urls| urls.len()).sum();
+    println!("{} windows, {} tabs", windows.len(), tabs);
+
+    for (i, urls) in windows.iter().enumerate() {
+        println!("window {} :", i + 1);
+        for url in urls {
+            println!("  {}", url);
+        }
+    }
+
+    Ok(())
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    if seconds < 60 {
+        format!("{}s ago", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{}m ago", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h ago", seconds / (60 * 60))
+    } else {
+        format!("{}d ago", seconds / (60 * 60 * 24))
+    }
+}
+
+// prints entries a run would copy with their sizes, nothing is copied
+fn list_files(config: &Config, ignore_entries: &HashSet<String>) -> Result<(), Box<dyn Error>> {
+    let profile_path = match find_base_profile(config)? {
+        ProfileSource::Archive(path) => Err(format!(
+            "`{}` is an archive, it is extracted as a whole",
+            path.display()
+        ))?,
+        ProfileSource::Directory(path) => path,
+    };
+
+    let mut total = 0;
+    for entry in profiles::plan_copy(&profile_path, ignore_entries)? {
+        let size = dir::get_size(&entry)?;
+        total += size;
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        if entry.is_dir() {
+            println!("{:>10}  {}/", progress::format_bytes(size), name);
+        } else {
+            println!("{:>10}  {}", progress::format_bytes(size), name);
+        }
+    }
+    println!(
+        "{:>10}  total from {}",
+        progress::format_bytes(total),
+        profile_path.display()
+    );
+
+    Ok(())
+}
+
+// copies profile folder, or extracts archived profile, into `to`
+fn prepare_profile(
+    source: &ProfileSource,
+    to: &Path,
+    ignore_entries: &HashSet<String>,
+    options: &CopyOptions,
+    progress: &mut CopyProgress,
+) -> Result<(), Box<dyn Error>> {
+    match source {
+        ProfileSource::Directory(path) => {
+            let vec = profiles::plan_copy(path, ignore_entries)?;
+            fs_extra::copy_items_with_progress(&vec, to, options, |process| {
+                progress.update(process.copied_bytes, process.total_bytes);
+                dir::TransitProcessResult::ContinueOrAbort
+            })?;
+            progress.finish();
+            copy_wal_files(path, to, ignore_entries)?;
+        }
+        ProfileSource::Archive(path) => archive::extract_archive(path.as_path(), to)?,
+    }
+
+    Ok(())
+}
+
+// WAL files are not copied with the profile, but a non-empty one holds
+// the latest transactions of its database, without them the copy is stale
+fn copy_wal_files(
+    from: &Path,
+    to: &Path,
+    ignore_entries: &HashSet<String>,
+) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let database = match name.strip_suffix("-wal") {
+            Some(database) if ignore_entries.contains(name.as_str()) => database,
+            _ => continue,
+        };
+        let size = entry.metadata()?.len();
+        // WAL is only of use next to a copy of its database
+        let copied = fs::symlink_metadata(to.join(database))
+            .map(|m| m.file_type().is_file())
+            .unwrap_or(false);
+        if size == 0 || !copied {
+            continue;
+        }
+        if let Err(e) = fs::copy(entry.path(), to.join(&name)) {
+            warn!(
+                "Unable to copy `{}`, {} of recent changes of {} are missing in the copy : {}",
+                name,
+                progress::format_bytes(size),
+                database,
+                e
+            );
+            continue;
+        }
+        info!(
+            "Copied `{}` with {} of changes not yet written to {}",
+            name,
+            progress::format_bytes(size),
+            database
+        );
+        // checkpoint leaves a single consistent database, that can be read
+        // even without write access to the folder
+        #[cfg(feature = "bookmarks")]
+        bookmarks::checkpoint_wal(&to.join(database))?;
+    }
 
     Ok(())
 }
 
+// removes a staged profile that is out of date, so it is copied again,
+// a folder that wasn't staged before is never removed
+fn refresh_stage(
+    stage: &Path,
+    source: &Path,
+    fingerprint: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    if !stage.exists() {
+        return Ok(());
+    }
+    if fs::read_dir(stage)?.next().is_none() {
+        fs::remove_dir(stage)?;
+        return Ok(());
+    }
+    let manifest = match manifest::read_manifest(stage)? {
+        None => Err(format!(
+            "`{}` is not empty and isn't a staged profile",
+            stage.display()
+        ))?,
+        Some(manifest) => manifest,
+    };
+    // archives have no fingerprint, their copies are always refreshed
+    if manifest.source_profile == source
+        && fingerprint.is_some()
+        && manifest.source_fingerprint == fingerprint
+    {
+        println!(
+            "Base profile is unchanged, reusing staged profile at {}",
+            stage.display()
+        );
+        return Ok(());
+    }
+    println!("Refreshing staged profile at {}", stage.display());
+    fs::remove_dir_all(stage)?;
+
+    Ok(())
+}
+
+// asks a yes or no question, anything but yes is a no
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// failed safety check stops the run, unless it's forced,
+// returns whether a dry run would proceed
+fn check_failed(config: &Config, warning: &str) -> Result<bool, Box<dyn Error>> {
+    match (config.dry_run, config.force) {
+        (true, true) => println!("Would proceed despite warning : {}", warning),
+        (true, false) => {
+            println!("Would stop : {}", warning);
+            return Ok(false);
+        }
+        (false, true) => warn!("{}, proceeding because of --force", warning),
+        (false, false) => Err(format!("{}, use --force to proceed anyway", warning))?,
+    }
+
+    Ok(true)
+}
+
+// places of a profile are opened by the folder as a string
+#[cfg(feature = "bookmarks")]
+fn folder_str(path: &Path) -> Result<&str, Box<dyn Error>> {
+    Ok(path
+        .to_str()
+        .ok_or_else(|| format!("`{}` is not valid unicode", path.display()))?)
+}
+
+// bar is drawn for people watching the terminal, otherwise progress is logged
+fn show_progress_bar(config: &Config) -> bool {
+    config.progress || io::stdout().is_terminal()
+}
+
+fn ignored_entries(config: &Config) -> HashSet<String> {
+    let mut ignore_entries = HashSet::new();
+    // manifest of a kept temp profile describes that copy, not a new one
+    ignore_entries.insert(manifest::MANIFEST_FILE_NAME.to_string());
+    #[cfg(feature = "bookmarks")]
+    ignore_entries.insert(bookmarks::SYNC_LOCK_FILE_NAME.to_string());
+    for str_to_ignore in IGNORE_FILES.iter().chain(profiles::LOCK_FILES) {
+        ignore_entries.insert(str_to_ignore.to_string());
+    }
+    for str_to_ignore in &config.extra_ignore {
+        ignore_entries.insert(str_to_ignore.clone());
+    }
+
+    ignore_entries
+}
+
+// profile given by path is used as is, otherwise it's looked up by name
+fn find_base_profile(config: &Config) -> Result<ProfileSource, Box<dyn Error>> {
+    if let Some(path) = &config.profile_path {
+        if fs::symlink_metadata(path).is_err() {
+            Err(format!("Profile path `{}` doesn't exist", path.display()))?;
+        }
+        let path = profiles::resolve_profile_path(path)?;
+        return Ok(if path.is_file() {
+            ProfileSource::Archive(path)
+        } else {
+            ProfileSource::Directory(path)
+        });
+    }
+
+    // section of profiles.ini is unambiguous, whatever the names are
+    if let Some(index) = config.profile_index {
+        let entries = match profiles::read_profiles_ini(&config.profile_folder)? {
+            None => Err(format!(
+                "There is no profiles.ini in `{}`",
+                config.profile_folder.display()
+            ))?,
+            Some(entries) => entries,
+        };
+        return match profiles::find_profile_by_index(&entries, index) {
+            None => Err(format!(
+                "There is no [Profile{}] section in profiles.ini of `{}`",
+                index,
+                config.profile_folder.display()
+            ))?,
+            Some(entry) => Ok(ProfileSource::Directory(profiles::resolve_profile_path(
+                &entry.path,
+            )?)),
+        };
+    }
+
+    if config.discover_profiles {
+        if let Some(discovered) = find_discovered_profile(config)? {
+            return Ok(ProfileSource::Directory(profiles::resolve_profile_path(
+                &discovered.path,
+            )?));
+        }
+    }
+
+    match profiles::find_profile(&config.profile_roots, &config.profile_name)? {
+        None => Err(profile_not_found(
+            &config.profile_roots,
+            &config.profile_name,
+        ))?,
+        Some(found) => Ok(found.source),
+    }
+}
+
+// profile with the name among profiles of every known browser,
+// the same name in several browsers has to be narrowed down with --browser
+fn find_discovered_profile(
+    config: &Config,
+) -> Result<Option<profiles::DiscoveredProfile>, Box<dyn Error>> {
+    let found: Vec<_> = profiles::discover_all_profiles()
+        .into_iter()
+        .filter(|p| p.name == config.profile_name)
+        .filter(|p| match &config.browser_name {
+            None => true,
+            Some(browser) => p.browser == *browser,
+        })
+        .collect();
+    let mut browsers: Vec<_> = found.iter().map(|p| p.browser.as_str()).collect();
+    browsers.dedup();
+    if browsers.len() > 1 {
+        Err(format!(
+            "Profile `{}` exists for {}, choose one with --browser",
+            config.profile_name,
+            browsers.join(", ")
+        ))?;
+    }
+
+    Ok(found.into_iter().next())
+}
+
+fn init_config(config: &Config) -> Result<(), Box<dyn Error>> {
+    let path = paths::config_dir()?.join(config_file::CONFIG_FILE_NAME);
+    let profile = profiles::detect_default_profile(&config.profile_roots)
+        .unwrap_or_else(|| config.profile_name.clone());
+    let browser = match browser::find_in_path(&config.browser_binary) {
+        Some(binary) => Some(vec![binary.display().to_string()]),
+        None if config.browser_binary == browser::DEFAULT_BROWSER => {
+            browser::resolve_browser_binary()
+        }
+        None => None,
+    };
+    let content = config_file::default_config(&profile, browser.as_deref());
+    config_file::write_config(&path, &content, config.force)?;
+    println!("Wrote config to {}", path.display());
+
+    Ok(())
+}
+
+fn print_profiles() {
+    let discovered = profiles::discover_all_profiles();
+    if discovered.is_empty() {
+        println!("No profiles found");
+        return;
+    }
+
+    let mut browser = None;
+    for profile in &discovered {
+        if browser != Some(&profile.browser) {
+            println!("{} :", profile.browser);
+            browser = Some(&profile.browser);
+        }
+        println!("  {} ({})", profile.name, profile.path.display());
+    }
+}
+
+fn find_sync_target(roots: &[PathBuf], name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match profiles::find_profile(roots, name)? {
+        None => Err(profile_not_found(roots, name))?,
+        Some(found) => match found.source {
+            ProfileSource::Archive(_) => {
+                Err(format!("Sync target profile `{}` is an archive", name))?
+            }
+            ProfileSource::Directory(path) => Ok(path),
+        },
+    }
+}
+
+// names of profiles that can be launched, as the user has to type them
+fn available_profile_names(roots: &[PathBuf]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names = vec![];
+    for root in roots.iter().filter(|root| root.is_dir()) {
+        if let Some(entries) = profiles::read_profiles_ini(root)? {
+            names.extend(entries.into_iter().map(|e| e.name));
+        }
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            let entry_name = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_file() {
+                if let Some(name) = archive::archive_profile_name(&entry_name) {
+                    names.push(name.to_string());
+                }
+            } else if let Some((_, name)) = entry_name.split_once(HASH_NAME_SPLIT_CHAR) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    Ok(names)
+}
+
+fn profile_not_found(roots: &[PathBuf], name: &str) -> String {
+    match available_profile_names(roots) {
+        Ok(names) if !names.is_empty() => format!(
+            "No profile named `{}` found. Available : {}",
+            name,
+            names.join(", ")
+        ),
+        _ => format!("No profile named `{}` found", name),
+    }
+}
+
+// quotes argument for a posix shell, arguments that don't need it are left as is
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Runs the command given as program followed by its arguments.
+///
+/// Stderr of the process is passed through and its first lines are kept
+/// in the outcome, so failures can be explained. With `quiet` stdout is
+/// dropped and stderr is only kept, not passed through.
+pub fn execute_cmd(
+    argv: &[String],
+    env: &[(String, String)],
+    timeout: Option<Duration>,
+    quiet: bool,
+) -> Result<LaunchOutcome, Box<dyn Error>> {
+    let (program, args) = match argv.split_first() {
+        Some((program, args)) if !program.is_empty() => (program, args),
+        _ => return Err("No command specified".into()),
+    };
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stderr(Stdio::piped());
+    if quiet {
+        command.stdout(Stdio::null());
+    }
+    let mut proc = match command.spawn() {
+        Err(e) => Err(format!("Unable to start `{}` : {}", program, e))?,
+        Ok(proc) => proc,
+    };
+    let stderr = Arc::new(Mutex::new(vec![]));
+    let (drained_sender, drained) = mpsc::channel();
+    if let Some(pipe) = proc.stderr.take() {
+        let stderr = Arc::clone(&stderr);
+        // not joined, a child of the browser can keep the pipe open after it exits
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).lines() {
+                let line = match line {
+                    Err(_) => break,
+                    Ok(line) => line,
+                };
+                if !quiet {
+                    eprintln!("{}", line);
+                }
+                if let Ok(mut lines) = stderr.lock() {
+                    if lines.len() < LAUNCH_STDERR_LINES {
+                        lines.push(line);
+                    }
+                }
+            }
+            let _ = drained_sender.send(());
+        });
+    }
+    let stderr_lines = || {
+        let _ = drained.recv_timeout(LAUNCH_STDERR_DRAIN);
+        stderr.lock().map(|l| l.clone()).unwrap_or_default()
+    };
+
+    let timeout = match timeout {
+        None => {
+            return Ok(LaunchOutcome {
+                status: proc.wait()?,
+                timed_out: false,
+                stderr: stderr_lines(),
+            })
+        }
+        Some(timeout) => timeout,
+    };
+
+    if let Some(status) = wait_with_deadline(&mut proc, timeout)? {
+        return Ok(LaunchOutcome {
+            status,
+            timed_out: false,
+            stderr: stderr_lines(),
+        });
+    }
+
+    // give the browser a chance to shut down properly and write its session
+    terminate(&mut proc);
+    let status = match wait_with_deadline(&mut proc, LAUNCH_TERMINATE_GRACE)? {
+        Some(status) => status,
+        None => {
+            proc.kill()?;
+            proc.wait()?
+        }
+    };
+
+    Ok(LaunchOutcome {
+        status,
+        timed_out: true,
+        stderr: stderr_lines(),
+    })
+}
+
+fn wait_with_deadline(
+    proc: &mut std::process::Child,
+    timeout: Duration,
+) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+    let deadline = time::Instant::now() + timeout;
+    loop {
+        if let Some(status) = proc.try_wait()? {
+            return Ok(Some(status));
+        }
+        if time::Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(LAUNCH_POLL_INTERVAL);
+    }
+}
+
+#[cfg(unix)]
+fn terminate(proc: &mut std::process::Child) {
+    unsafe {
+        libc::kill(proc.id() as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(proc: &mut std::process::Child) {
+    // no graceful termination available
+    let _ = proc.kill();
+}
+
 pub fn execute_cmd_output(cmd: &str) -> Result<String, Box<dyn Error>> {
     let cmd_split: Vec<_> = cmd.split(' ').collect();
-    if cmd_split.len() < 1 || cmd_split[0] == "" {
-        return Err("No command specified")?;
+    if cmd_split.is_empty() || cmd_split[0].is_empty() {
+        return Err("No command specified".into());
     }
 
     let output = if cmd_split.len() < 2 {
@@ -412,7 +1953,7 @@ pub fn execute_cmd_output(cmd: &str) -> Result<String, Box<dyn Error>> {
 pub fn get_open_file() -> Result<Option<String>, Box<dyn Error>> {
     let file_name = execute_cmd_output(OPEN_SESSION_FILE_COMMAND)?;
 
-    if file_name != "" {
+    if !file_name.is_empty() {
         Ok(Some(file_name))
     } else {
         Ok(None)
@@ -422,7 +1963,7 @@ pub fn get_open_file() -> Result<Option<String>, Box<dyn Error>> {
 pub fn get_save_file() -> Result<Option<String>, Box<dyn Error>> {
     let file_name = execute_cmd_output(SAVE_SESSION_FILE_COMMAND)?;
 
-    if file_name != "" {
+    if !file_name.is_empty() {
         Ok(Some(file_name))
     } else {
         Ok(None)
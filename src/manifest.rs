@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::FfError;
+use crate::paths;
+
+pub const MANIFEST_FILE_NAME: &str = "fftemplates-manifest.json";
+
+/// Describes how a temp profile was created from its template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub source_profile: PathBuf,
+    // seconds since unix epoch
+    pub created: u64,
+    // top level files and folders of the temp profile
+    pub entries: Vec<String>,
+    pub adjusted_prefs: Vec<String>,
+    pub session_file: Option<String>,
+    pub extension_paths_rewritten: bool,
+    // id of the latest bookmark of the source profile, new bookmarks come after it
+    pub baseline_bookmark_id: Option<i64>,
+    // fingerprint of the source profile the copy was made from, staged
+    // profiles are reused while it stays the same
+    #[serde(default)]
+    pub source_fingerprint: Option<u64>,
+}
+
+/// Writes manifest into `fftemplates-manifest.json` of the profile folder.
+pub fn write_manifest(path: &Path, manifest: &Manifest) -> Result<(), FfError> {
+    paths::write_atomic(
+        path.join(MANIFEST_FILE_NAME),
+        serde_json::to_string_pretty(manifest)?.as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// Reads manifest of the profile folder, `None` if profile has no manifest.
+pub fn read_manifest(path: &Path) -> Result<Option<Manifest>, FfError> {
+    let file = path.join(MANIFEST_FILE_NAME);
+    if !file.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&fs::read_to_string(file)?)?))
+}
+
+/// Profile a kept temp profile was made from, `None` if the folder
+/// isn't a temp profile with a manifest.
+pub fn temp_profile_source(path: &Path) -> Result<Option<PathBuf>, FfError> {
+    if !path.is_dir() {
+        return Ok(None);
+    }
+
+    Ok(read_manifest(path)?.map(|manifest| manifest.source_profile))
+}
@@ -0,0 +1,83 @@
+use std::env;
+use std::fs;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tempfile::Builder;
+
+use crate::error::FfError;
+
+const APP_DIR_NAME: &str = "fftemplates";
+
+/// Folder for configuration files, `~/.config/fftemplates` by default.
+pub fn config_dir() -> Result<PathBuf, FfError> {
+    app_dir("XDG_CONFIG_HOME", dirs::config_dir())
+}
+
+/// Folder for stored data like sessions, `~/.local/share/fftemplates` by default.
+pub fn data_dir() -> Result<PathBuf, FfError> {
+    app_dir("XDG_DATA_HOME", dirs::data_dir())
+}
+
+/// Folder for temporary state, `~/.cache/fftemplates` by default.
+pub fn cache_dir() -> Result<PathBuf, FfError> {
+    app_dir("XDG_CACHE_HOME", dirs::cache_dir())
+}
+
+// XDG variable takes precedence over platform default, so the layout
+// can be overridden on every platform and not only on linux
+fn app_dir(xdg_variable: &str, default: Option<PathBuf>) -> Result<PathBuf, FfError> {
+    let base = match env::var_os(xdg_variable) {
+        Some(value) if !value.is_empty() => PathBuf::from(value),
+        _ => match default {
+            None => Err(format!(
+                "Unable to find home folder, set `{}` instead",
+                xdg_variable
+            ))?,
+            Some(path) => path,
+        },
+    };
+    let dir = base.join(APP_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+/// Replaces content of the file at `path` with `content`.
+///
+/// Content is written into a temp file next to it first, so interrupted
+/// write never leaves a truncated file behind.
+pub fn write_atomic<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<(), FfError> {
+    write_atomic_with(path, |writer| Ok(writer.write_all(content)?))
+}
+
+/// Same as `write_atomic`, content is produced by `write`.
+///
+/// If `write` fails, the file at `path` is left as it was.
+pub fn write_atomic_with<P, F>(path: P, write: F) -> Result<(), FfError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> Result<(), FfError>,
+{
+    let path = path.as_ref();
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // same folder, so rename doesn't cross file systems
+    let mut temp = Builder::new()
+        .prefix(&format!(".{}.", name))
+        .suffix(".tmp")
+        .tempfile_in(parent)?;
+    write(temp.as_file_mut())?;
+    temp.as_file_mut().sync_all()?;
+    // temp files are only readable by the owner, keep what the file had
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+    }
+    temp.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
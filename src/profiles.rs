@@ -0,0 +1,552 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::fs;
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::archive;
+use crate::archive::ProfileSource;
+use crate::error::FfError;
+
+const PROFILES_INI_NAME: &str = "profiles.ini";
+
+/// Separates random hash from the name in profile folder names, `a1b2c3d4.default`.
+pub const HASH_NAME_SPLIT_CHAR: char = '.';
+
+const PROFILE_HASH_LENGTH: usize = 8;
+const PROFILE_HASH_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+#[derive(Debug, PartialEq)]
+pub struct ProfileEntry {
+    // section name in profiles.ini, like `Profile0`
+    pub section: String,
+    pub name: String,
+    // absolute path to the profile folder
+    pub path: PathBuf,
+    pub is_relative: bool,
+    pub is_default: bool,
+}
+
+/// Profile found in one of the known browser locations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredProfile {
+    // firefox or one of its forks, like `librewolf`
+    pub browser: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Folders with profiles of firefox and its forks on the current platform,
+/// paired with the browser they belong to.
+pub fn known_profile_roots() -> Vec<(String, PathBuf)> {
+    let mut roots = vec![];
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(home) = dirs::home_dir() {
+            roots.push(("firefox", home.join(".mozilla").join("firefox")));
+            roots.push(("librewolf", home.join(".librewolf")));
+            roots.push(("waterfox", home.join(".waterfox")));
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(data) = dirs::data_dir() {
+            roots.push(("firefox", data.join("Firefox").join("Profiles")));
+            roots.push(("librewolf", data.join("librewolf").join("Profiles")));
+            roots.push(("waterfox", data.join("Waterfox").join("Profiles")));
+        }
+    }
+    #[cfg(windows)]
+    {
+        if let Some(data) = dirs::data_dir() {
+            roots.push(("firefox", data.join("Mozilla").join("Firefox")));
+            roots.push(("librewolf", data.join("librewolf")));
+            roots.push(("waterfox", data.join("Waterfox")));
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|(browser, path)| (browser.to_string(), path))
+        .collect()
+}
+
+/// Every profile found in the known locations of all browsers.
+pub fn discover_all_profiles() -> Vec<DiscoveredProfile> {
+    discover_profiles(&known_profile_roots())
+}
+
+/// Every profile found in the given roots, tagged with browser of the root.
+///
+/// Profiles are taken from `profiles.ini` of the root, or from folder names
+/// when there is none. Roots that don't exist or can't be read are skipped.
+pub fn discover_profiles(roots: &[(String, PathBuf)]) -> Vec<DiscoveredProfile> {
+    let mut discovered = vec![];
+    for (browser, root) in roots {
+        let profiles: Vec<(String, PathBuf)> = match read_profiles_ini(root) {
+            Ok(Some(entries)) => entries.into_iter().map(|e| (e.name, e.path)).collect(),
+            Ok(None) => profile_folders(root),
+            Err(_) => continue,
+        };
+        discovered.extend(profiles.into_iter().map(|(name, path)| DiscoveredProfile {
+            browser: browser.clone(),
+            name,
+            path,
+        }));
+    }
+
+    discovered
+}
+
+/// Shows numbered list of the profiles and reads choice of the user.
+///
+/// Empty answer keeps the default profile and returns `None`.
+pub fn pick_profile<R: BufRead, W: Write>(
+    profiles: &[DiscoveredProfile],
+    default_name: &str,
+    input: &mut R,
+    output: &mut W,
+) -> Result<Option<DiscoveredProfile>, FfError> {
+    for (i, profile) in profiles.iter().enumerate() {
+        writeln!(
+            output,
+            "{:>3}) {} {} ({})",
+            i + 1,
+            profile.browser,
+            profile.name,
+            profile.path.display()
+        )?;
+    }
+    loop {
+        write!(
+            output,
+            "Choose profile 1-{}, empty for `{}` : ",
+            profiles.len(),
+            default_name
+        )?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            // closed input is the same as no answer
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        match line.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= profiles.len() => return Ok(Some(profiles[n - 1].clone())),
+            _ => writeln!(output, "`{}` is not a number from the list", line)?,
+        }
+    }
+}
+
+// folders named `<hash>.<name>`, for roots without profiles.ini
+fn profile_folders(root: &Path) -> Vec<(String, PathBuf)> {
+    let mut profiles = vec![];
+    for entry in fs::read_dir(root).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if let Some((_, name)) = entry
+            .file_name()
+            .to_string_lossy()
+            .split_once(HASH_NAME_SPLIT_CHAR)
+        {
+            profiles.push((name.to_string(), path));
+        }
+    }
+    profiles.sort();
+
+    profiles
+}
+
+// symlink to `<ip>:+<pid>` of the browser, created on unix only
+const SYMLINK_LOCK_FILE: &str = "lock";
+
+/// Files a browser uses to lock its profile, they are never copied.
+///
+/// `.parentlock` is the fcntl locked file on unix, `parent.lock` is the
+/// one opened exclusively on windows.
+pub const LOCK_FILES: &[&str] = &[SYMLINK_LOCK_FILE, ".parentlock", "parent.lock"];
+
+/// Checks if a running browser holds the lock of the profile.
+///
+/// Lock files that are left by a browser that exited or crashed don't count
+/// on unix, where it can be checked, elsewhere an existing file is a lock.
+pub fn is_profile_locked<P: AsRef<Path>>(profile: P) -> bool {
+    let profile = profile.as_ref();
+    LOCK_FILES.iter().any(|name| {
+        let path = profile.join(name);
+        if *name == SYMLINK_LOCK_FILE {
+            is_symlink_lock_held(&path)
+        } else {
+            is_file_lock_held(&path)
+        }
+    })
+}
+
+fn is_symlink_lock_held(path: &Path) -> bool {
+    let target = match fs::read_link(path) {
+        Err(_) => return false,
+        Ok(target) => target,
+    };
+    let pid = target
+        .to_string_lossy()
+        .rsplit_once('+')
+        .and_then(|(_, pid)| pid.parse::<u32>().ok());
+    match pid {
+        // can't tell who holds it, better to assume somebody does
+        None => true,
+        Some(pid) => is_process_running(pid),
+    }
+}
+
+// firefox keeps a write lock on the whole file while it runs,
+// the file itself stays after it exits
+#[cfg(unix)]
+fn is_file_lock_held(path: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    let file = match fs::File::open(path) {
+        Err(_) => return false,
+        Ok(file) => file,
+    };
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    // only reports a conflicting lock, nothing is locked here
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) } == -1 {
+        return false;
+    }
+
+    lock.l_type != libc::F_UNLCK as libc::c_short
+}
+
+#[cfg(not(unix))]
+fn is_file_lock_held(path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    // signal 0 only checks if the process exists, EPERM means it belongs to another user
+    unsafe {
+        libc::kill(pid as libc::pid_t, 0) == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
+
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    true
+}
+
+/// Reads `profiles.ini` from the firefox root folder.
+///
+/// Returns `None` if there is no `profiles.ini` in the folder.
+pub fn read_profiles_ini<P: AsRef<Path>>(root: P) -> Result<Option<Vec<ProfileEntry>>, FfError> {
+    let ini = root.as_ref().join(PROFILES_INI_NAME);
+    if !ini.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&ini)?;
+
+    Ok(Some(parse_profiles_ini(root, &content)))
+}
+
+/// Parses content of `profiles.ini`, resolving profile paths against `root`.
+///
+/// `IsRelative=1` means that `Path=` is relative to `root`, otherwise it's absolute.
+/// Default profile is the one pointed by `Default=` of an `[Install...]` section,
+/// or the one with `Default=1` for older files without install sections.
+pub fn parse_profiles_ini<P: AsRef<Path>>(root: P, content: &str) -> Vec<ProfileEntry> {
+    let root = root.as_ref();
+    let sections = parse_ini(content);
+
+    let install_default = sections
+        .iter()
+        .filter(|(section, _)| section.starts_with("Install"))
+        .filter_map(|(_, values)| ini_value(values, "Default"))
+        .map(|path| root.join(path))
+        .next();
+
+    let mut entries = vec![];
+    for (section, values) in &sections {
+        if !section.starts_with("Profile") {
+            continue;
+        }
+        let (name, path) = match (ini_value(values, "Name"), ini_value(values, "Path")) {
+            (Some(name), Some(path)) => (name, path),
+            _ => continue,
+        };
+        let is_relative = ini_value(values, "IsRelative") != Some("0");
+        let path = if is_relative {
+            root.join(path)
+        } else {
+            PathBuf::from(path)
+        };
+        let is_default = match &install_default {
+            Some(default) => *default == path,
+            None => ini_value(values, "Default") == Some("1"),
+        };
+
+        entries.push(ProfileEntry {
+            section: section.to_string(),
+            name: name.to_string(),
+            path,
+            is_relative,
+            is_default,
+        });
+    }
+
+    entries
+}
+
+/// Entry of the `[ProfileN]` section with `index` as N.
+pub fn find_profile_by_index(entries: &[ProfileEntry], index: usize) -> Option<&ProfileEntry> {
+    let section = format!("Profile{}", index);
+    entries.iter().find(|entry| entry.section == section)
+}
+
+/// Profile found by name, with the root it was found in, where the
+/// `profiles.ini` registering it lives.
+#[derive(Debug, PartialEq)]
+pub struct FoundProfile {
+    pub source: ProfileSource,
+    // name of the folder or archive in the root
+    pub entry_name: String,
+    pub root: PathBuf,
+}
+
+/// Looks up the profile in each of the roots in order, returning the first match.
+///
+/// Roots that don't exist are skipped.
+pub fn find_profile<P: AsRef<Path>>(
+    roots: &[P],
+    profile_name: &str,
+) -> Result<Option<FoundProfile>, FfError> {
+    for root in roots {
+        if !root.as_ref().is_dir() {
+            continue;
+        }
+        if let Some(found) = find_profile_in(root, profile_name)? {
+            return Ok(Some(found));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Looks up the profile in a single root, by `profiles.ini` first and then
+/// by folder names, `<hash>.<name>`, or archives named after the profile.
+pub fn find_profile_in<P: AsRef<Path>>(
+    root: P,
+    profile_name: &str,
+) -> Result<Option<FoundProfile>, FfError> {
+    let root = root.as_ref();
+    let found_in = |source, entry_name| FoundProfile {
+        source,
+        entry_name,
+        root: root.to_path_buf(),
+    };
+
+    // profiles registered in profiles.ini take precedence over folder names
+    if let Some(entries) = read_profiles_ini(root)? {
+        if let Some(entry) = entries.into_iter().find(|e| e.name == profile_name) {
+            let entry_name = entry
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let path = resolve_profile_path(&entry.path)?;
+            return Ok(Some(found_in(ProfileSource::Directory(path), entry_name)));
+        }
+    }
+
+    let mut found = None;
+    let mut found_archive = None;
+
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let entry_name = entry.file_name().to_string_lossy().into_owned();
+        if entry_path.is_file() {
+            if archive::archive_profile_name(&entry_name) == Some(profile_name) {
+                let path = resolve_profile_path(&entry_path)?;
+                found_archive = Some(found_in(ProfileSource::Archive(path), entry_name));
+            }
+            continue;
+        }
+        let entry_profile_name = match entry_name.split_once(HASH_NAME_SPLIT_CHAR) {
+            None => continue,
+            Some((_, name)) => name,
+        };
+        if entry_profile_name == profile_name {
+            let path = resolve_profile_path(&entry_path)?;
+            found = Some(found_in(ProfileSource::Directory(path), entry_name));
+            break;
+        }
+    }
+
+    // live profile folder wins over an archive with the same name
+    Ok(found.or(found_archive))
+}
+
+/// Name of the default profile of the first root that has one in its
+/// `profiles.ini`, or of the first profile found when none is marked default.
+pub fn detect_default_profile<P: AsRef<Path>>(roots: &[P]) -> Option<String> {
+    for root in roots {
+        if let Ok(Some(entries)) = read_profiles_ini(root) {
+            if let Some(entry) = entries.into_iter().find(|e| e.is_default) {
+                return Some(entry.name);
+            }
+        }
+    }
+
+    let roots: Vec<_> = roots
+        .iter()
+        .map(|root| (String::new(), root.as_ref().to_path_buf()))
+        .collect();
+    discover_profiles(&roots).into_iter().next().map(|p| p.name)
+}
+
+/// Resolves symlinks, so profile is copied from and synced back to the real folder.
+pub fn resolve_profile_path(path: &Path) -> Result<PathBuf, FfError> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() && !path.exists() {
+        Err(format!(
+            "Profile `{}` is a broken symlink to `{}`",
+            path.display(),
+            fs::read_link(path)?.display()
+        ))?;
+    }
+
+    Ok(fs::canonicalize(path)?)
+}
+
+/// Registers a profile folder relative to `root` in its `profiles.ini` as a new
+/// `[ProfileN]` section, creating the file if needed. A profile that is
+/// already registered under the same name is an error.
+///
+/// Returns name of the added section.
+pub fn add_profile_to_ini<P: AsRef<Path>>(
+    root: P,
+    name: &str,
+    relative_path: &str,
+) -> Result<String, FfError> {
+    let ini = root.as_ref().join(PROFILES_INI_NAME);
+    let content = if ini.exists() {
+        fs::read_to_string(&ini)?
+    } else {
+        String::new()
+    };
+
+    let sections = parse_ini(&content);
+    let registered = sections
+        .iter()
+        .filter(|(section, _)| section.starts_with("Profile"))
+        .any(|(_, values)| ini_value(values, "Name") == Some(name));
+    if registered {
+        Err(format!(
+            "Profile with name `{}` is already in `{}`",
+            name,
+            ini.display()
+        ))?;
+    }
+
+    let next_index = sections
+        .iter()
+        .filter_map(|(section, _)| section.strip_prefix("Profile"))
+        .filter_map(|index| index.parse::<usize>().ok())
+        .map(|index| index + 1)
+        .max()
+        .unwrap_or(0);
+    let section = format!("Profile{}", next_index);
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&ini)?;
+    if content.is_empty() {
+        writeln!(file, "[General]\nStartWithLastProfile=1\nVersion=2")?;
+    } else if !content.ends_with('\n') {
+        writeln!(file)?;
+    }
+    writeln!(
+        file,
+        "\n[{}]\nName={}\nIsRelative=1\nPath={}",
+        section, name, relative_path
+    )?;
+
+    Ok(section)
+}
+
+/// Random prefix for a new profile folder, in the same format firefox uses.
+pub fn generate_profile_hash() -> String {
+    let mut hash = String::with_capacity(PROFILE_HASH_LENGTH);
+    // std has no random generator, but RandomState is seeded randomly
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    let mut value = hasher.finish();
+    for _ in 0..PROFILE_HASH_LENGTH {
+        let index = (value % PROFILE_HASH_CHARS.len() as u64) as usize;
+        hash.push(PROFILE_HASH_CHARS[index] as char);
+        value /= PROFILE_HASH_CHARS.len() as u64;
+    }
+
+    hash
+}
+
+type IniSection<'a> = (&'a str, Vec<(&'a str, &'a str)>);
+
+fn parse_ini(content: &str) -> Vec<IniSection<'_>> {
+    let mut sections: Vec<IniSection> = vec![];
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push((&line[1..line.len() - 1], vec![]));
+            continue;
+        }
+        let mut split = line.splitn(2, '=');
+        if let (Some(key), Some(value), Some(section)) =
+            (split.next(), split.next(), sections.last_mut())
+        {
+            section.1.push((key.trim(), value.trim()));
+        }
+    }
+
+    sections
+}
+
+fn ini_value<'a>(values: &[(&'a str, &'a str)], key: &str) -> Option<&'a str> {
+    values.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+/// Top level entries of the profile folder that are copied into a temp
+/// profile, every entry except for the ignored ones, sorted by name.
+pub fn plan_copy(profile_dir: &Path, ignore: &HashSet<String>) -> Result<Vec<PathBuf>, FfError> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(profile_dir)? {
+        let entry = entry?;
+        // names that aren't valid unicode can't be on the ignore list
+        let ignored = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| ignore.contains(name));
+        if !ignored {
+            entries.push(entry.path());
+        }
+    }
+    entries.sort();
+
+    Ok(entries)
+}
@@ -0,0 +1,82 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+
+const BAR_TEMPLATE: &str = "[{bar:30}] {percent:>3}% {bytes} / {total_bytes}";
+// percent between progress lines, when there is no terminal to draw a bar in
+const LOG_STEP_PERCENT: u64 = 10;
+
+/// Reports progress of copying the profile.
+///
+/// Interactive progress is a bar drawn on stderr, otherwise, or when
+/// stderr is not a terminal, a line is logged every few percent.
+pub struct CopyProgress {
+    bar: Option<ProgressBar>,
+    last_percent: Option<u64>,
+}
+
+impl CopyProgress {
+    pub fn new(interactive: bool) -> Self {
+        let bar = ProgressBar::new(0);
+        // bar is hidden when stderr is not a terminal, progress is logged then
+        let bar = match interactive && !bar.is_hidden() {
+            false => None,
+            true => {
+                bar.set_style(
+                    ProgressStyle::with_template(BAR_TEMPLATE)
+                        .unwrap_or_else(|_| ProgressStyle::default_bar())
+                        .progress_chars("# "),
+                );
+                Some(bar)
+            }
+        };
+        CopyProgress {
+            bar,
+            last_percent: None,
+        }
+    }
+
+    pub fn update(&mut self, copied_bytes: u64, total_bytes: u64) {
+        let percent = match total_bytes {
+            0 => 100,
+            _ => copied_bytes * 100 / total_bytes,
+        };
+        if let Some(bar) = &self.bar {
+            bar.set_length(total_bytes);
+            bar.set_position(copied_bytes);
+        } else if self
+            .last_percent
+            .is_none_or(|last| percent / LOG_STEP_PERCENT > last / LOG_STEP_PERCENT)
+        {
+            info!(
+                "Copied {}% of the profile ({} / {})",
+                percent,
+                format_bytes(copied_bytes),
+                format_bytes(total_bytes)
+            );
+        }
+        self.last_percent = Some(percent);
+    }
+
+    /// Leaves the bar as it is, so following output starts on a new line.
+    pub fn finish(&self) {
+        if let (Some(bar), Some(_)) = (&self.bar, self.last_percent) {
+            bar.finish();
+        }
+    }
+}
+
+/// Formats size with a binary unit, `12.3 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
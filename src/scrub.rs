@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use log::info;
+
+use crate::paths;
+use crate::session;
+
+/// Telemetry and crash report state removed from the temp profile by `--scrub`,
+/// relative to the profile folder:
+///
+/// - `datareporting/`, telemetry client id and health report state
+/// - `crashes/`, pending and submitted crash reports
+/// - `saved-telemetry-pings/`, pings waiting to be sent
+/// - `Telemetry.FailedProfileLocks.txt`, count of failed profile locks
+///
+/// They are removed after copying, so it doesn't matter what is ignored.
+pub const SCRUBBED_PATHS: &[&str] = &[
+    "datareporting",
+    "crashes",
+    "saved-telemetry-pings",
+    "Telemetry.FailedProfileLocks.txt",
+];
+
+/// Prefs of prefs.js set by `--scrub`, they turn off telemetry, health
+/// report and automatic submission of crash reports.
+pub const SCRUB_PREFS: &[(&str, &str)] = &[
+    ("toolkit.telemetry.enabled", "false"),
+    ("toolkit.telemetry.unified", "false"),
+    ("toolkit.telemetry.archive.enabled", "false"),
+    ("datareporting.healthreport.uploadEnabled", "false"),
+    ("datareporting.policy.dataSubmissionEnabled", "false"),
+    ("browser.crashReports.unsubmittedCheck.autoSubmit2", "false"),
+];
+
+/// Prefs of prefs.js removed by `--scrub`, identifiers firefox keeps next to
+/// the ones in `datareporting/`.
+pub const SCRUBBED_PREFS: &[&str] = &[
+    "toolkit.telemetry.cachedClientID",
+    "toolkit.telemetry.cachedProfileGroupID",
+];
+
+/// Removes `SCRUBBED_PATHS` from the profile and sets `SCRUB_PREFS`
+/// in its prefs.js, removing `SCRUBBED_PREFS`.
+///
+/// Returns names of the prefs that were set.
+pub fn scrub_profile(profile: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    for name in SCRUBBED_PATHS {
+        let path = profile.join(name);
+        let metadata = match fs::symlink_metadata(&path) {
+            Err(_) => continue,
+            Ok(metadata) => metadata,
+        };
+        // symlinks of attach mode are removed, not what they point to
+        if metadata.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        info!("Scrubbed `{}`", name);
+    }
+
+    let preferences = profile.join(session::PROFILE_FILE_NAME);
+    let mut content = if preferences.exists() {
+        fs::read_to_string(&preferences)?
+    } else {
+        String::new()
+    };
+    for name in SCRUBBED_PREFS {
+        content = session::remove_pref(&content, name)?;
+    }
+    let mut set = vec![];
+    for (name, value) in SCRUB_PREFS {
+        content = session::ensure_pref(&content, name, value)?;
+        set.push(name.to_string());
+    }
+    paths::write_atomic(&preferences, content.as_bytes())?;
+
+    Ok(set)
+}
@@ -1,21 +1,63 @@
-use regex::Captures;
+use filetime::FileTime;
+use regex::NoExpand;
 use regex::Regex;
+use ruzstd::decoding::StreamingDecoder;
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::io::BufWriter;
 use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
 
-const PROFILE_FILE_NAME: &'static str = "prefs.js";
-const SESSIONSTORE_DEFAULT_NAME: &'static str = "sessionstore.jsonlz4";
+use serde_json::Value;
 
+use crate::error::FfError;
+use crate::paths;
+
+pub const PROFILE_FILE_NAME: &str = "prefs.js";
+pub const SESSIONSTORE_DEFAULT_NAME: &str = "sessionstore.jsonlz4";
+// session written periodically while firefox is running,
+// it is preferred over the default one when firefox wasn't closed cleanly
+pub const SESSIONSTORE_RECOVERY_NAME: &str = "sessionstore-backups/recovery.jsonlz4";
+// header of firefox lz4 files, followed by decompressed size as u32 little endian
+const MOZLZ4_MAGIC: &[u8] = b"mozLz40\0";
+const MOZLZ4_HEADER_SIZE: usize = 12;
+// first bytes of a zstd frame
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// companion file next to a saved session, describing where it came from
+const SESSION_METADATA_SUFFIX: &str = ".meta.json";
+
+// prefs required to keep history of the session, so it can be restored
+const SESSION_PREFS: &[(&str, bool)] = &[("places.history.enabled", true)];
+// prefs required for firefox to keep session on exit, so it can be saved
+const SESSION_SAVE_PREFS: &[(&str, bool)] = &[("privacy.sanitize.sanitizeOnShutdown", false)];
+// prefs to restore previous session on start, the value of the user
+// is kept only when no session is loaded or saved
+const SESSION_RESTORE_PREFS: &[(&str, i64)] = &[("browser.startup.page", 3)];
+
+// prefs applied to every temp profile, kept in the config dir
+pub const OVERLAY_FILE_NAME: &str = "user-overrides.js";
+// `user_pref(...)` line with the pref name and its value as written
+const USER_PREF_PATTERN: &str = r#"user_pref\("((?:[^"\\]|\\.)*)",\s*("(?:[^"\\]|\\.)*"|[^)]*)\);"#;
+
+/// Adjusts prefs.js of the profile, so session can be restored and saved.
+///
+/// With `restore_session` the startup page is set to restore the session
+/// whatever it was, otherwise only when the user didn't configure it.
+///
+/// Returns names of the prefs that were set.
 pub fn adjust_profile_settings(
     folder_location: &str,
     disable_clean_history_on_close: bool,
-) -> Result<(), Box<dyn Error>> {
+    restore_session: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
     let preferences = Path::new(folder_location).join(Path::new(PROFILE_FILE_NAME));
     let mut content = String::new();
     {
@@ -24,47 +66,231 @@ pub fn adjust_profile_settings(
         buf_reader.read_to_string(&mut content)?;
     }
 
-    // enable saving history
-    let re = Regex::new(r#"(user_pref)(\("places.history.enabled", )(false|true)(\);)"#)?;
-    content = re
-        .replace_all(content.as_str(), |caps: &Captures| {
-            format!("{}{}{}{}", &caps[1], &caps[2], "true", &caps[4])
-        })
-        .into_owned();
+    let mut adjusted = vec![];
+    for (name, value) in SESSION_PREFS {
+        content = replace_pref_bool(&content, name, *value);
+        adjusted.push(name.to_string());
+    }
+    if disable_clean_history_on_close {
+        for (name, value) in SESSION_SAVE_PREFS {
+            content = replace_pref_bool(&content, name, *value);
+            adjusted.push(name.to_string());
+        }
+    }
+    for (name, value) in SESSION_RESTORE_PREFS {
+        if restore_session || !is_pref_set(&content, name)? {
+            content = replace_pref_int(&content, name, *value);
+            adjusted.push(name.to_string());
+        }
+    }
+
+    paths::write_atomic(&preferences, content.as_bytes())?;
 
-    // enable saving session
-    let re = Regex::new(r#"user_pref\("browser.startup.page", (\d)\);"#)?;
-    // expected behaviour
-    if !re.is_match(&content) {
-        content.push_str(r#"user_pref("browser.startup.page", 3);"#);
+    Ok(adjusted)
+}
+
+/// Applies prefs of the overlay file to prefs.js of the profile.
+///
+/// Returns names of the prefs that were set.
+pub fn apply_overlay_file(
+    folder_location: &str,
+    overlay_path: &Path,
+    override_existing: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if !overlay_path.exists() {
+        return Ok(vec![]);
     }
+    let preferences = Path::new(folder_location).join(Path::new(PROFILE_FILE_NAME));
+    let mut content = if preferences.exists() {
+        fs::read_to_string(&preferences)?
+    } else {
+        String::new()
+    };
 
-    // disable history sanitization on closing (needed to store session)
-    if disable_clean_history_on_close {
-        let re = Regex::new(
-            r#"(user_pref)(\("privacy.sanitize.sanitizeOnShutdown", )(false|true)(\);)"#,
-        )?;
-        content = re
-            .replace_all(content.as_str(), |caps: &Captures| {
-                format!("{}{}{}{}", &caps[1], &caps[2], "false", &caps[4])
-            })
-            .into_owned();
+    let applied = apply_overlay_prefs(&mut content, overlay_path, override_existing)?;
+    if !applied.is_empty() {
+        paths::write_atomic(&preferences, content.as_bytes())?;
     }
 
-    {
-        let file = File::create(&preferences)?;
-        let mut buf_writer = BufWriter::new(file);
-        buf_writer.write_all(content.as_bytes())?;
+    Ok(applied)
+}
+
+/// Adds `user_pref(...)` lines of the overlay file to prefs.js content.
+///
+/// Prefs already set in content are kept, unless `override_existing` is set.
+/// Missing overlay file changes nothing. Returns names of the prefs that were set.
+pub fn apply_overlay_prefs(
+    content: &mut String,
+    overlay_path: &Path,
+    override_existing: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if !overlay_path.exists() {
+        return Ok(vec![]);
     }
+    let overlay = fs::read_to_string(overlay_path)?;
 
-    Ok(())
+    let re = Regex::new(USER_PREF_PATTERN)?;
+    let mut applied = vec![];
+    for captures in re.captures_iter(&overlay) {
+        let name = &captures[1];
+        let value = captures[2].trim();
+        if !override_existing && is_pref_set(content, name)? {
+            continue;
+        }
+        *content = ensure_pref(content, name, value)?;
+        applied.push(name.to_string());
+    }
+
+    Ok(applied)
+}
+
+/// Sets `name` pref to `value` in prefs.js content, replacing every existing
+/// definition of the pref, or appending a new one if pref is not there.
+///
+/// `value` is written as is, so strings have to be quoted.
+/// Applying the same pref twice leaves content unchanged the second time.
+pub fn ensure_pref(content: &str, name: &str, value: &str) -> Result<String, Box<dyn Error>> {
+    let pref = format!("user_pref(\"{}\", {});", name, value);
+    let re = pref_regex(name)?;
+    if re.is_match(content) {
+        return Ok(re.replace_all(content, NoExpand(&pref)).into_owned());
+    }
+
+    let mut content = content.to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&pref);
+    content.push('\n');
+
+    Ok(content)
+}
+
+/// Removes every definition of `name` pref from prefs.js content.
+pub fn remove_pref(content: &str, name: &str) -> Result<String, Box<dyn Error>> {
+    let line = Regex::new(&format!(r"(?m)^\s*{}\s*\n?", pref_regex(name)?.as_str()))?;
+
+    Ok(line.replace_all(content, "").into_owned())
+}
+
+/// Sets bool pref in prefs.js content, same as `ensure_pref`.
+pub fn replace_pref_bool(content: &str, pref: &str, value: bool) -> String {
+    replace_pref(content, pref, &value.to_string())
+}
+
+/// Sets integer pref in prefs.js content, same as `ensure_pref`.
+pub fn replace_pref_int(content: &str, pref: &str, value: i64) -> String {
+    replace_pref(content, pref, &value.to_string())
+}
+
+/// Sets string pref in prefs.js content, same as `ensure_pref`.
+///
+/// Value is quoted and escaped, so it is written as given.
+pub fn replace_pref_string(content: &str, pref: &str, value: &str) -> String {
+    let quoted = format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+    replace_pref(content, pref, &quoted)
+}
+
+fn replace_pref(content: &str, pref: &str, value: &str) -> String {
+    // pref name is escaped, so the regex is always valid
+    ensure_pref(content, pref, value).expect("Invalid pref regex")
+}
+
+/// Checks if there is any value set for `name` pref in prefs.js content.
+pub fn is_pref_set(content: &str, name: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(pref_regex(name)?.is_match(content))
+}
+
+/// Pref with different values in two prefs.js contents, `None` if it isn't set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Prefs set in prefs.js content with their values as written, in order.
+///
+/// Pref set more than once keeps its last value, like in firefox.
+pub fn read_prefs(content: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let re = Regex::new(USER_PREF_PATTERN)?;
+    let mut prefs: Vec<(String, String)> = vec![];
+    for captures in re.captures_iter(content) {
+        let value = captures[2].trim().to_string();
+        match prefs.iter_mut().find(|(name, _)| *name == captures[1]) {
+            Some(pref) => pref.1 = value,
+            None => prefs.push((captures[1].to_string(), value)),
+        }
+    }
+    Ok(prefs)
 }
 
+/// Prefs added, changed or removed between two prefs.js contents.
+///
+/// Prefs of `adjusted` come first in its order, then the removed ones.
+pub fn diff_prefs(original: &str, adjusted: &str) -> Result<Vec<PrefChange>, Box<dyn Error>> {
+    let original: HashMap<_, _> = read_prefs(original)?.into_iter().collect();
+    let adjusted = read_prefs(adjusted)?;
+
+    let mut changes = vec![];
+    for (name, value) in &adjusted {
+        let old = original.get(name);
+        if old != Some(value) {
+            changes.push(PrefChange {
+                name: name.clone(),
+                old: old.cloned(),
+                new: Some(value.clone()),
+            });
+        }
+    }
+    let mut removed: Vec<_> = original
+        .into_iter()
+        .filter(|(name, _)| !adjusted.iter().any(|(adjusted, _)| adjusted == name))
+        .collect();
+    removed.sort();
+    changes.extend(removed.into_iter().map(|(name, old)| PrefChange {
+        name,
+        old: Some(old),
+        new: None,
+    }));
+
+    Ok(changes)
+}
+
+fn pref_regex(name: &str) -> Result<Regex, Box<dyn Error>> {
+    // value is either a quoted string, that can contain `)`, or a bool/number
+    Ok(Regex::new(&format!(
+        r#"user_pref\("{}",\s*(?:"(?:[^"\\]|\\.)*"|[^)]*)\);"#,
+        regex::escape(name)
+    ))?)
+}
+
+/// How a loaded session is combined with the session the profile already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SessionLoadMode {
+    // loaded session is copied over the profile one
+    #[default]
+    Replace,
+    // windows of the loaded session are added, tabs already open are skipped
+    Merge,
+    // windows of the loaded session are added as they are
+    Append,
+}
+
+/// Copies session file into the profile as `target_name`,
+/// relative to the profile folder, `sessionstore.jsonlz4` by default.
+///
+/// Plain json sessions are validated and compressed, since firefox only reads mozlz4.
+/// With `Merge` or `Append` mode windows are added to the session the profile
+/// already has, without one it is the same as `Replace`.
+/// Returns path of the copy, or `None` when an optional file doesn't exist.
 pub fn add_sessionstore_file(
     file_location: &str,
     folder_location: &str,
+    target_name: Option<&str>,
     fail_if_does_not_exist: bool,
-) -> Result<(), Box<dyn Error>> {
+    mode: SessionLoadMode,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
     let sessionstore = Path::new(file_location);
     if !sessionstore.exists() && fail_if_does_not_exist {
         Err(format!(
@@ -72,26 +298,522 @@ pub fn add_sessionstore_file(
             file_location
         ))?;
     } else if !sessionstore.exists() && !fail_if_does_not_exist {
-        return Ok(());
+        return Ok(None);
     }
 
-    fs::copy(
-        sessionstore,
-        Path::new(folder_location).join(Path::new(SESSIONSTORE_DEFAULT_NAME)),
-    )?;
+    let target = Path::new(folder_location).join(target_name.unwrap_or(SESSIONSTORE_DEFAULT_NAME));
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let bytes = fs::read(sessionstore)?;
+    if mode != SessionLoadMode::Replace && target.exists() {
+        let json = decompress_session(&bytes)?;
+        validate_session_json(&json)
+            .map_err(|e| format!("Invalid session `{}` : {}", file_location, e))?;
+        let mut session = read_session(&target)
+            .map_err(|e| format!("Invalid session `{}` : {}", target.display(), e))?;
+        if !session["windows"].is_array() {
+            Err(format!(
+                "Invalid session `{}` : `windows` list is missing",
+                target.display()
+            ))?;
+        }
+        combine_sessions(
+            &mut session,
+            serde_json::from_slice(&json)?,
+            mode == SessionLoadMode::Merge,
+        );
+        paths::write_atomic(&target, &compress_session(&serde_json::to_vec(&session)?))?;
+    } else if is_plain_json(&bytes) {
+        validate_session_json(&bytes)
+            .map_err(|e| format!("Invalid session `{}` : {}", file_location, e))?;
+        paths::write_atomic(&target, &compress_session(&bytes))?;
+    } else {
+        paths::write_atomic(&target, &bytes)?;
+    }
+
+    Ok(Some(target))
+}
+
+// adds windows of the loaded session after the ones of the session,
+// with dedupe tabs whose url is already open anywhere are dropped
+fn combine_sessions(session: &mut Value, loaded: Value, dedupe: bool) {
+    let mut open_urls: HashSet<String> = session_urls(session).into_iter().flatten().collect();
+    let loaded_windows = match loaded {
+        Value::Object(mut loaded) => match loaded.remove("windows") {
+            Some(Value::Array(windows)) => windows,
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    for mut window in loaded_windows {
+        if dedupe {
+            if let Some(tabs) = window["tabs"].as_array_mut() {
+                let count = tabs.len();
+                tabs.retain(|tab| tab_url(tab).is_none_or(|url| open_urls.insert(url.to_string())));
+                if tabs.is_empty() {
+                    continue;
+                }
+                // selected tab is 1 based and may be gone
+                if tabs.len() != count {
+                    window["selected"] = Value::from(1);
+                }
+            }
+        }
+        if let Some(windows) = session["windows"].as_array_mut() {
+            windows.push(window);
+        }
+    }
+}
+
+/// Most recently modified `*.jsonlz4` session of the folder, `None` if it has none.
+pub fn latest_session_in(dir: &Path) -> Result<Option<PathBuf>, FfError> {
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "jsonlz4") {
+            continue;
+        }
+        let modified = fs::metadata(&path)?.modified()?;
+        if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            latest = Some((modified, path));
+        }
+    }
+
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// Sets modification time of the session file to now.
+///
+/// Firefox may ignore a session that looks older than the rest of the profile.
+pub fn touch_session<P: AsRef<Path>>(path: P) -> Result<(), FfError> {
+    filetime::set_file_mtime(path, FileTime::now())?;
 
     Ok(())
 }
 
-pub fn save_sessionstore_file(
-    file_name: &str,
+/// Copies `target_name` session of the profile, `sessionstore.jsonlz4` by default,
+/// to `file_name`.
+pub fn save_sessionstore_file<P: AsRef<Path>>(
+    file_name: P,
     folder_location: &str,
+    target_name: Option<&str>,
 ) -> Result<(), Box<dyn Error>> {
-    let sessionstore = Path::new(file_name);
+    let sessionstore = file_name.as_ref();
     let source_session_store =
-        Path::new(folder_location).join(Path::new(SESSIONSTORE_DEFAULT_NAME));
+        Path::new(folder_location).join(target_name.unwrap_or(SESSIONSTORE_DEFAULT_NAME));
 
     fs::copy(source_session_store, sessionstore)?;
 
+    // session is a regular one now, metadata of a previous crash save is stale
+    let metadata = session_metadata_path(sessionstore);
+    if metadata.exists() {
+        fs::remove_file(metadata)?;
+    }
+
+    Ok(())
+}
+
+/// Same as `save_sessionstore_file`, but leaves `file_name` untouched if it
+/// already holds a session with the same tabs in the same windows.
+///
+/// Returns whether the session was saved.
+pub fn save_changed_sessionstore_file(
+    file_name: &str,
+    folder_location: &str,
+    target_name: Option<&str>,
+) -> Result<bool, Box<dyn Error>> {
+    let source_session_store =
+        Path::new(folder_location).join(target_name.unwrap_or(SESSIONSTORE_DEFAULT_NAME));
+
+    // a file that can't be read as a session is overwritten as before
+    let unchanged = Path::new(file_name).exists()
+        && diff_sessions(Path::new(file_name), &source_session_store)
+            .map(|diff| diff.is_empty())
+            .unwrap_or(false);
+    if unchanged {
+        return Ok(false);
+    }
+
+    save_sessionstore_file(file_name, folder_location, target_name)?;
+
+    Ok(true)
+}
+
+/// Saves session after firefox crashed.
+///
+/// Top level sessionstore is not written by firefox when it crashes,
+/// so the periodically updated recovery file is used instead, if it exists.
+/// A companion `<file_name>.meta.json` records that the session was recovered.
+pub fn save_recovery_sessionstore_file(
+    file_name: &str,
+    folder_location: &str,
+) -> Result<(), Box<dyn Error>> {
+    let recovery = Path::new(folder_location).join(Path::new(SESSIONSTORE_RECOVERY_NAME));
+    if !recovery.exists() {
+        return save_sessionstore_file(file_name, folder_location, None);
+    }
+
+    fs::copy(recovery, Path::new(file_name))?;
+
+    let metadata = serde_json::json!({
+        "recovered_from_crash": true,
+        "source": SESSIONSTORE_RECOVERY_NAME,
+    });
+    paths::write_atomic(
+        session_metadata_path(Path::new(file_name)),
+        serde_json::to_string_pretty(&metadata)?.as_bytes(),
+    )?;
+
     Ok(())
 }
+
+fn session_metadata_path(file_name: &Path) -> PathBuf {
+    let mut metadata = file_name.as_os_str().to_owned();
+    metadata.push(SESSION_METADATA_SUFFIX);
+    PathBuf::from(metadata)
+}
+
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub windows: usize,
+    pub tabs: usize,
+    // set when file couldn't be read as a session, counts are 0 then
+    pub error: Option<String>,
+}
+
+/// Changes of tabs in a single window between two sessions.
+#[derive(Debug, Default, PartialEq)]
+pub struct WindowDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // true if tabs present in both sessions are in a different order
+    pub reordered: bool,
+}
+
+impl WindowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && !self.reordered
+    }
+}
+
+/// Changes between two sessions, windows are matched by their position.
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionDiff {
+    pub windows: Vec<WindowDiff>,
+}
+
+impl SessionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.windows.iter().all(|w| w.is_empty())
+    }
+}
+
+/// Compares urls of tabs of two session files.
+///
+/// Windows are aligned by position, windows that exist only in `b`
+/// are reported as wholly added and the ones only in `a` as wholly removed.
+pub fn diff_sessions(a: &Path, b: &Path) -> Result<SessionDiff, FfError> {
+    let a_windows = read_session_urls(a)?;
+    let b_windows = read_session_urls(b)?;
+
+    let count = a_windows.len().max(b_windows.len());
+    let windows = (0..count)
+        .map(|i| {
+            diff_window(
+                a_windows.get(i).map(|w| w.as_slice()).unwrap_or_default(),
+                b_windows.get(i).map(|w| w.as_slice()).unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    Ok(SessionDiff { windows })
+}
+
+// same url can be open in several tabs, so urls are compared as multisets
+fn diff_window(a: &[String], b: &[String]) -> WindowDiff {
+    let (removed, a_common) = split_by_presence(a, b);
+    let (added, b_common) = split_by_presence(b, a);
+
+    WindowDiff {
+        added,
+        removed,
+        reordered: a_common != b_common,
+    }
+}
+
+// splits urls into the ones missing from `other` and the ones present in it
+fn split_by_presence<'a>(urls: &'a [String], other: &[String]) -> (Vec<String>, Vec<&'a str>) {
+    let mut available: HashMap<&str, usize> = HashMap::new();
+    for url in other {
+        *available.entry(url).or_default() += 1;
+    }
+
+    let mut missing = vec![];
+    let mut present = vec![];
+    for url in urls {
+        match available.get_mut(url.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                present.push(url.as_str());
+            }
+            _ => missing.push(url.clone()),
+        }
+    }
+
+    (missing, present)
+}
+
+/// Decompresses content of a session file into raw json bytes.
+///
+/// Format is detected by the header: mozlz4, zstd frame, or uncompressed json.
+pub fn decompress_session(bytes: &[u8]) -> Result<Vec<u8>, FfError> {
+    if bytes.starts_with(MOZLZ4_MAGIC) {
+        return decompress_mozlz4(bytes);
+    }
+    if bytes.starts_with(ZSTD_MAGIC) {
+        let mut json = vec![];
+        match StreamingDecoder::new(bytes) {
+            Err(e) => Err(format!("Unable to decompress session : {}", e))?,
+            Ok(mut decoder) => decoder.read_to_end(&mut json)?,
+        };
+        return Ok(json);
+    }
+    if is_plain_json(bytes) {
+        return Ok(bytes.to_vec());
+    }
+
+    Err("Unknown session file format".into())
+}
+
+fn is_plain_json(bytes: &[u8]) -> bool {
+    bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+}
+
+// session needs a list of windows to be restored
+fn validate_session_json(bytes: &[u8]) -> Result<(), FfError> {
+    let session: Value = serde_json::from_slice(bytes)?;
+    if !session["windows"].is_array() {
+        return Err("`windows` list is missing".into());
+    }
+
+    Ok(())
+}
+
+/// Compresses json of a session into mozlz4, the format firefox reads.
+pub fn compress_session(json: &[u8]) -> Vec<u8> {
+    let mut bytes = MOZLZ4_MAGIC.to_vec();
+    bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    bytes.extend(lz4_flex::block::compress(json));
+
+    bytes
+}
+
+fn decompress_mozlz4(bytes: &[u8]) -> Result<Vec<u8>, FfError> {
+    if bytes.len() < MOZLZ4_HEADER_SIZE {
+        return Err("Truncated mozlz4 file".into());
+    }
+    let mut size = [0; 4];
+    size.copy_from_slice(&bytes[MOZLZ4_MAGIC.len()..MOZLZ4_HEADER_SIZE]);
+    let size = u32::from_le_bytes(size) as usize;
+
+    match lz4_flex::block::decompress(&bytes[MOZLZ4_HEADER_SIZE..], size) {
+        Err(e) => Err(format!("Unable to decompress session : {}", e))?,
+        Ok(decompressed) => Ok(decompressed),
+    }
+}
+
+/// Writes session file, compressed or not, as pretty printed json.
+pub fn export_session(from: &Path, to: &Path) -> Result<(), FfError> {
+    let session = read_session_json(from)?;
+    let mut pretty = serde_json::to_vec_pretty(&session)?;
+    pretty.push(b'\n');
+    paths::write_atomic(to, &pretty)?;
+
+    Ok(())
+}
+
+/// Compresses json session into mozlz4 session file firefox reads.
+///
+/// Compressed sessions are accepted as well, they are written as mozlz4.
+pub fn import_session(from: &Path, to: &Path) -> Result<(), FfError> {
+    // formatting of an edited file is dropped, firefox writes it compact as well
+    let session = read_session_json(from)?;
+    paths::write_atomic(to, &compress_session(&serde_json::to_vec(&session)?))?;
+
+    Ok(())
+}
+
+// session file in any format, with a clear error for anything else
+fn read_session_json(path: &Path) -> Result<Value, FfError> {
+    let not_session =
+        |e| FfError::context(&format!("`{}` is not a session file", path.display()), e);
+    let bytes = fs::read(path)
+        .map_err(|e| FfError::context(&format!("Unable to read `{}`", path.display()), e))?;
+    let json = decompress_session(&bytes).map_err(not_session)?;
+    validate_session_json(&json).map_err(not_session)?;
+
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Structure of a session file, with everything that would keep firefox
+/// from restoring it fully.
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionReport {
+    pub windows: usize,
+    pub tabs: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Checks that every window of the session has tabs, and every tab has
+/// entries with its index pointing to one of them.
+///
+/// Session that can't be decompressed or has no list of windows is an error,
+/// anything else firefox may partially restore is reported as a warning.
+pub fn validate_session(path: &Path) -> Result<SessionReport, FfError> {
+    let session = read_session_json(path)?;
+    let mut report = SessionReport::default();
+    let windows = session["windows"].as_array().into_iter().flatten();
+
+    for (i, window) in windows.enumerate() {
+        report.windows += 1;
+        let tabs = match window["tabs"].as_array() {
+            None => {
+                report
+                    .warnings
+                    .push(format!("window {} has no `tabs` list", i + 1));
+                continue;
+            }
+            Some(tabs) => tabs,
+        };
+        if tabs.is_empty() {
+            report.warnings.push(format!("window {} is empty", i + 1));
+        }
+        for (j, tab) in tabs.iter().enumerate() {
+            report.tabs += 1;
+            if let Some(warning) = check_tab(tab) {
+                report
+                    .warnings
+                    .push(format!("tab {} of window {} {}", j + 1, i + 1, warning));
+            }
+        }
+    }
+
+    // index of the selected window starts from 1 as well
+    match session["selectedWindow"].as_u64() {
+        None => report
+            .warnings
+            .push("`selectedWindow` is missing".to_string()),
+        Some(index) if index < 1 || index as usize > report.windows => {
+            report.warnings.push(format!(
+                "`selectedWindow` {} is out of range of {} windows",
+                index, report.windows
+            ))
+        }
+        Some(_) => {}
+    }
+
+    Ok(report)
+}
+
+// what is wrong with the tab, `None` if it can be restored
+fn check_tab(tab: &Value) -> Option<String> {
+    let entries = match tab["entries"].as_array() {
+        None => return Some("has no `entries` list".to_string()),
+        Some(entries) => entries,
+    };
+    if entries.is_empty() {
+        return Some("has no entries".to_string());
+    }
+    match tab["index"].as_u64() {
+        None => Some("has no `index`".to_string()),
+        Some(index) if index < 1 || index as usize > entries.len() => Some(format!(
+            "has `index` {} out of range of {} entries",
+            index,
+            entries.len()
+        )),
+        Some(_) => None,
+    }
+}
+
+/// Reads session file as json.
+pub fn read_session<P: AsRef<Path>>(path: P) -> Result<Value, FfError> {
+    let bytes = fs::read(path)?;
+    let json = decompress_session(&bytes)?;
+
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Reads urls of currently open page of every tab, grouped by window.
+pub fn read_session_urls<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<String>>, FfError> {
+    let session = read_session(path)?;
+
+    Ok(session_urls(&session))
+}
+
+fn session_urls(session: &Value) -> Vec<Vec<String>> {
+    let mut windows = vec![];
+    for window in session["windows"].as_array().into_iter().flatten() {
+        let mut urls = vec![];
+        for tab in window["tabs"].as_array().into_iter().flatten() {
+            if let Some(url) = tab_url(tab) {
+                urls.push(url.to_string());
+            }
+        }
+        windows.push(urls);
+    }
+
+    windows
+}
+
+// index of a tab points to the current entry and starts from 1
+fn tab_url(tab: &Value) -> Option<&str> {
+    let entries = tab["entries"].as_array()?;
+    let entry = match tab["index"].as_u64() {
+        Some(index) if index >= 1 && (index as usize) <= entries.len() => {
+            &entries[index as usize - 1]
+        }
+        _ => entries.last()?,
+    };
+
+    entry["url"].as_str()
+}
+
+/// Lists every session file in the folder, newest first.
+///
+/// Files that can't be read as a session are listed with an error
+/// instead of failing the whole listing.
+pub fn list_sessions<P: AsRef<Path>>(dir: P) -> Result<Vec<SessionInfo>, FfError> {
+    let mut sessions = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() || path.to_string_lossy().ends_with(SESSION_METADATA_SUFFIX) {
+            continue;
+        }
+        let modified = entry.metadata().and_then(|m| m.modified()).ok();
+
+        let info = match read_session_urls(&path) {
+            Err(e) => SessionInfo {
+                path,
+                modified,
+                windows: 0,
+                tabs: 0,
+                error: Some(e.to_string()),
+            },
+            Ok(windows) => SessionInfo {
+                path,
+                modified,
+                windows: windows.len(),
+                tabs: windows.iter().map(|w| w.len()).sum(),
+                error: None,
+            },
+        };
+        sessions.push(info);
+    }
+    sessions.sort_by_key(|s| Reverse(s.modified));
+
+    Ok(sessions)
+}
@@ -0,0 +1,536 @@
+use rusqlite::types::{ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::bookmarks::{self, Bookmark, Keyword, Origin, Place};
+use crate::error::FfError;
+use crate::history::Visit;
+
+/// Tables of the places database that rows are synced into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    Origins,
+    Places,
+    Bookmarks,
+    Visits,
+}
+
+impl Table {
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::Origins => "moz_origins",
+            Table::Places => "moz_places",
+            Table::Bookmarks => "moz_bookmarks",
+            Table::Visits => "moz_historyvisits",
+        }
+    }
+}
+
+/// Places of a single profile, as the sync reads and writes them.
+///
+/// Sync logic only goes through this trait, so it can be run against
+/// something other than a sqlite database, like an in memory store in tests.
+pub trait PlacesStore {
+    /// Bookmark with the highest id, `None` if there are no bookmarks.
+    fn latest_bookmark(&self) -> Result<Option<Bookmark>, FfError>;
+
+    /// Bookmarks with `after_id < id <= until_id`, ordered by id.
+    fn bookmarks_after(&self, after_id: i64, until_id: i64) -> Result<Vec<Bookmark>, FfError>;
+
+    /// Every bookmark ordered by id.
+    fn all_bookmarks(&self) -> Result<Vec<Bookmark>, FfError>;
+
+    /// Places with the given ids by their id, with their keywords,
+    /// ids without a place are skipped.
+    fn places_for(&self, ids: &[i64]) -> Result<HashMap<i64, Place>, FfError>;
+
+    /// Origins with the given ids by their id, ids without an origin are skipped.
+    fn origins_for(&self, ids: &[i64]) -> Result<HashMap<i64, Origin>, FfError>;
+
+    /// Ids of the built-in root folders by their guid.
+    fn root_folder_ids(&self) -> Result<HashMap<String, i64>, FfError>;
+
+    /// First id after the current max id of the table.
+    fn next_id(&self, table: Table) -> Result<i64, FfError>;
+
+    /// Id of the row of the table with the guid.
+    fn id_by_guid(&self, table: Table, guid: &str) -> Result<Option<i64>, FfError>;
+
+    /// Id of the place with the url, `url_hash` narrows down the lookup.
+    fn place_id_by_url(&self, url_hash: i64, url: &str) -> Result<Option<i64>, FfError>;
+
+    /// Id of the origin with the prefix and host.
+    fn origin_id(&self, prefix: &str, host: &str) -> Result<Option<i64>, FfError>;
+
+    /// Id of the first folder with the title.
+    fn find_folder_by_title(&self, title: &str) -> Result<Option<i64>, FfError>;
+
+    /// Position after the last child of the folder.
+    fn next_child_position(&self, folder_id: i64) -> Result<i64, FfError>;
+
+    /// Whether keywords can be stored, older profiles have no keywords table.
+    fn has_keywords(&self) -> Result<bool, FfError>;
+
+    /// Id of the place the keyword opens, `None` if there is no such keyword.
+    fn keyword_place_id(&self, keyword: &str) -> Result<Option<i64>, FfError>;
+
+    fn insert_origin(&self, origin: &Origin) -> Result<(), FfError>;
+
+    fn insert_place(&self, place: &Place) -> Result<(), FfError>;
+
+    fn insert_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError>;
+
+    fn insert_keyword(&self, place_id: i64, keyword: &Keyword) -> Result<(), FfError>;
+
+    fn insert_visit(&self, visit: &Visit) -> Result<(), FfError>;
+
+    /// Sets title, place and modification time of an existing bookmark.
+    fn update_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError>;
+
+    /// Sets foreign count of the place to the number of bookmarks
+    /// and keywords pointing to it.
+    fn update_foreign_count(&self, place_id: i64) -> Result<(), FfError>;
+
+    /// Sets frecency of the origin from the frecencies of its places.
+    fn update_origin_frecency(&self, origin_id: i64) -> Result<(), FfError>;
+
+    /// Adds visits to the counters of an existing place.
+    fn add_place_visits(
+        &self,
+        place_id: i64,
+        visit_count: i64,
+        last_visit_date: Option<i64>,
+    ) -> Result<(), FfError>;
+}
+
+/// `PlacesStore` of a `places.sqlite` database.
+///
+/// Statements are cached on the connection, so each one is prepared once
+/// however many rows are read or written. Reads and writes are retried
+/// while firefox keeps the database busy.
+pub struct SqlitePlacesStore<'conn> {
+    conn: &'conn Connection,
+    // writes rendered as sql instead of being run, when recording
+    recorded: Option<RefCell<Vec<String>>>,
+}
+
+impl<'conn> SqlitePlacesStore<'conn> {
+    pub fn new(conn: &'conn Connection) -> Self {
+        SqlitePlacesStore {
+            conn,
+            recorded: None,
+        }
+    }
+
+    /// Store that reads from the database, but only records the statements
+    /// it would write with, see `recorded_statements`.
+    pub fn recording(conn: &'conn Connection) -> Self {
+        SqlitePlacesStore {
+            conn,
+            recorded: Some(RefCell::new(vec![])),
+        }
+    }
+
+    /// Statements of the writes so far, in order, with their parameters
+    /// filled in, ready to be run by `sqlite3`.
+    pub fn recorded_statements(&self) -> Vec<String> {
+        match &self.recorded {
+            None => vec![],
+            Some(recorded) => recorded.borrow().clone(),
+        }
+    }
+
+    fn write(&self, sql: &str, params: &[&dyn ToSql]) -> Result<(), FfError> {
+        if let Some(recorded) = &self.recorded {
+            recorded.borrow_mut().push(render_sql(sql, params)?);
+            return Ok(());
+        }
+        let mut statement = self.conn.prepare_cached(sql)?;
+        retry(|| statement.execute(params))?;
+
+        Ok(())
+    }
+}
+
+// reads and writes of the store, both fail while firefox holds the lock
+fn retry<T, F>(f: F) -> Result<T, FfError>
+where
+    F: FnMut() -> Result<T, rusqlite::Error>,
+{
+    bookmarks::with_busy_retry(bookmarks::BUSY_RETRY_ATTEMPTS, f)
+}
+
+// statement on a single line with `?N` replaced by the literal of the parameter
+fn render_sql(sql: &str, params: &[&dyn ToSql]) -> Result<String, FfError> {
+    let mut literals = vec![];
+    for param in params {
+        let literal = match param.to_sql()? {
+            ToSqlOutput::Borrowed(value) => sql_literal(value),
+            ToSqlOutput::Owned(value) => sql_literal(ValueRef::from(&value)),
+        };
+        literals.push(literal);
+    }
+    let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut rendered = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            rendered.push(c);
+            continue;
+        }
+        let mut index = String::new();
+        while let Some(digit) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            index.push(*digit);
+            chars.next();
+        }
+        match index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| literals.get(i.wrapping_sub(1)))
+        {
+            None => Err(format!("No parameter for `?{}` in `{}`", index, sql))?,
+            Some(literal) => rendered.push_str(literal),
+        }
+    }
+
+    Ok(format!("{};", rendered))
+}
+
+fn sql_literal(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(text) => format!("'{}'", String::from_utf8_lossy(text).replace('\'', "''")),
+        ValueRef::Blob(blob) => {
+            let hex: String = blob.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+impl PlacesStore for SqlitePlacesStore<'_> {
+    fn latest_bookmark(&self) -> Result<Option<Bookmark>, FfError> {
+        let mut statement = self.conn.prepare_cached(
+            "
+                select
+                    id, type, fk, parent, position, title, keyword_id,
+                    folder_type, dateAdded, lastModified, guid, syncStatus, syncChangeCounter,
+                    (select p.guid from moz_bookmarks p where p.id = moz_bookmarks.parent)
+                from moz_bookmarks
+                order by id desc
+                limit 1",
+        )?;
+
+        retry(|| {
+            let mut bookmark_iter = statement.query_map(params![], bookmarks::bookmark_from_row)?;
+            bookmark_iter.next().transpose()
+        })
+    }
+
+    fn bookmarks_after(&self, after_id: i64, until_id: i64) -> Result<Vec<Bookmark>, FfError> {
+        bookmarks::iter_bookmarks_between(self.conn, after_id, until_id)?.collect()
+    }
+
+    fn all_bookmarks(&self) -> Result<Vec<Bookmark>, FfError> {
+        bookmarks::iter_bookmarks(self.conn)?.collect()
+    }
+
+    fn places_for(&self, ids: &[i64]) -> Result<HashMap<i64, Place>, FfError> {
+        let mut statement = self.conn.prepare_cached(
+            "
+                select
+                    id, url, title, rev_host, visit_count, hidden,
+                    typed, favicon_id, frecency, last_visit_date,
+                    guid, foreign_count, url_hash, description, preview_image_url, origin_id
+                from moz_places
+                where 1=1
+                and id = :places_id
+            ",
+        )?;
+
+        let mut places = HashMap::new();
+        for places_id in ids {
+            let place = retry(|| {
+                let mut places_iter = statement
+                    .query_map_named(&[(":places_id", places_id)], bookmarks::place_from_row)?;
+                places_iter.next().transpose()
+            })?;
+            if let Some(place) = place {
+                places.insert(*places_id, place);
+            }
+        }
+        // older profiles may have no keywords table
+        if bookmarks::has_keywords(self.conn)? {
+            let mut statement = self.conn.prepare_cached(
+                "select keyword, post_data from moz_keywords where place_id = ?1 order by id",
+            )?;
+            for (place_id, place) in places.iter_mut() {
+                place.keywords = retry(|| {
+                    statement
+                        .query_map(params![place_id], |row| {
+                            Ok(Keyword {
+                                keyword: row.get(0)?,
+                                post_data: row.get(1)?,
+                            })
+                        })?
+                        .collect()
+                })?;
+            }
+        }
+
+        Ok(places)
+    }
+
+    fn origins_for(&self, ids: &[i64]) -> Result<HashMap<i64, Origin>, FfError> {
+        let mut statement = self.conn.prepare_cached(
+            "
+                select
+                    id, prefix, host, frecency
+                from moz_origins
+                where 1=1
+                and id = :origin_id
+            ",
+        )?;
+
+        let mut origins = HashMap::new();
+        for origin_id in ids {
+            let origin = retry(|| {
+                let mut origins_iter =
+                    statement.query_map_named(&[(":origin_id", origin_id)], |row| {
+                        Ok(Origin {
+                            id: row.get(0)?,
+                            prefix: row.get(1)?,
+                            host: row.get(2)?,
+                            frecency: row.get(3)?,
+                        })
+                    })?;
+                origins_iter.next().transpose()
+            })?;
+            if let Some(origin) = origin {
+                origins.insert(*origin_id, origin);
+            }
+        }
+
+        Ok(origins)
+    }
+
+    fn root_folder_ids(&self) -> Result<HashMap<String, i64>, FfError> {
+        bookmarks::root_folder_ids(self.conn)
+    }
+
+    fn next_id(&self, table: Table) -> Result<i64, FfError> {
+        retry(|| {
+            self.conn.query_row(
+                &format!("select coalesce(max(id), 0) + 1 from {}", table.name()),
+                params![],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    fn id_by_guid(&self, table: Table, guid: &str) -> Result<Option<i64>, FfError> {
+        let mut statement = self
+            .conn
+            .prepare_cached(&format!("select id from {} where guid = ?1", table.name()))?;
+
+        retry(|| {
+            let mut ids = statement.query_map(params![guid], |row| row.get(0))?;
+            ids.next().transpose()
+        })
+    }
+
+    fn place_id_by_url(&self, url_hash: i64, url: &str) -> Result<Option<i64>, FfError> {
+        // url_hash is indexed, url is compared too as hashes can collide
+        let mut statement = self
+            .conn
+            .prepare_cached("select id from moz_places where url_hash = ?1 and url = ?2")?;
+
+        retry(|| {
+            let mut ids = statement.query_map(params![url_hash, url], |row| row.get(0))?;
+            ids.next().transpose()
+        })
+    }
+
+    fn origin_id(&self, prefix: &str, host: &str) -> Result<Option<i64>, FfError> {
+        let mut statement = self
+            .conn
+            .prepare_cached("select id from moz_origins where prefix = ?1 and host = ?2")?;
+
+        retry(|| {
+            let mut ids = statement.query_map(params![prefix, host], |row| row.get(0))?;
+            ids.next().transpose()
+        })
+    }
+
+    fn find_folder_by_title(&self, title: &str) -> Result<Option<i64>, FfError> {
+        bookmarks::find_folder_by_title(self.conn, title)
+    }
+
+    fn next_child_position(&self, folder_id: i64) -> Result<i64, FfError> {
+        bookmarks::next_child_position(self.conn, folder_id)
+    }
+
+    fn has_keywords(&self) -> Result<bool, FfError> {
+        bookmarks::has_keywords(self.conn)
+    }
+
+    fn keyword_place_id(&self, keyword: &str) -> Result<Option<i64>, FfError> {
+        if !bookmarks::has_keywords(self.conn)? {
+            return Ok(None);
+        }
+        let mut statement = self
+            .conn
+            .prepare_cached("select place_id from moz_keywords where keyword = ?1")?;
+
+        retry(|| {
+            let mut ids = statement.query_map(params![keyword], |row| row.get(0))?;
+            ids.next().transpose()
+        })
+    }
+
+    fn insert_origin(&self, origin: &Origin) -> Result<(), FfError> {
+        self.write(
+            "insert into moz_origins (id, prefix, host, frecency)
+            values(?1, ?2, ?3, ?4)",
+            params![origin.id, origin.prefix, origin.host, origin.frecency],
+        )
+    }
+
+    fn insert_place(&self, place: &Place) -> Result<(), FfError> {
+        self.write(
+            "insert into moz_places (id, url, title, rev_host,
+                visit_count, hidden, typed, favicon_id,
+                frecency, last_visit_date, guid, foreign_count,
+                url_hash, description, preview_image_url, origin_id)
+            values(?1, ?2, ?3, ?4,
+                ?5, ?6, ?7, ?8,
+                ?9, ?10, ?11, ?12,
+                ?13, ?14, ?15, ?16)",
+            params![
+                place.id,
+                place.url,
+                place.title,
+                place.rev_host,
+                place.visit_count,
+                place.hidden,
+                place.typed,
+                place.favicon_id,
+                place.frecency,
+                place.last_visit_date,
+                place.guid,
+                place.foreign_count,
+                place.url_hash,
+                place.description,
+                place.preview_image_url,
+                place.origin_id
+            ],
+        )
+    }
+
+    fn insert_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError> {
+        self.write(
+            "
+                insert  into moz_bookmarks (
+                    id, type, fk, parent, position,
+                    title, keyword_id, folder_type, dateAdded, lastModified,
+                    guid, syncStatus, syncChangeCounter)
+                values(
+                    ?1, ?2, ?3, ?4, ?5,
+                    ?6, ?7, ?8, ?9, ?10,
+                    ?11, ?12, ?13)
+            ",
+            params![
+                bookmark.id,
+                bookmark.r#type,
+                bookmark.fk,
+                bookmark.parent,
+                bookmark.position,
+                bookmark.title,
+                bookmark.keyword_id,
+                bookmark.folder_type,
+                bookmark.date_added,
+                bookmark.last_modified,
+                bookmark.guid,
+                bookmark.sync_status,
+                bookmark.sync_change_counter
+            ],
+        )
+    }
+
+    // id is left to the table, nothing else refers to it
+    fn insert_keyword(&self, place_id: i64, keyword: &Keyword) -> Result<(), FfError> {
+        self.write(
+            "insert into moz_keywords (keyword, place_id, post_data) values(?1, ?2, ?3)",
+            params![keyword.keyword, place_id, keyword.post_data],
+        )
+    }
+
+    fn update_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError> {
+        // change counter tells firefox sync that the bookmark has to be uploaded
+        self.write(
+            "
+                update moz_bookmarks
+                set title = ?2, fk = ?3, lastModified = ?4,
+                    syncChangeCounter = syncChangeCounter + 1
+                where id = ?1
+            ",
+            params![
+                bookmark.id,
+                bookmark.title,
+                bookmark.fk,
+                bookmark.last_modified
+            ],
+        )
+    }
+
+    // history statements are only prepared once there is a visit,
+    // profiles without history tables can still sync bookmarks
+    fn insert_visit(&self, visit: &Visit) -> Result<(), FfError> {
+        self.write(
+            "insert into moz_historyvisits (id, from_visit, place_id,
+                visit_date, visit_type, session)
+            values(?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                visit.id,
+                visit.from_visit,
+                visit.place_id,
+                visit.visit_date,
+                visit.visit_type,
+                visit.session
+            ],
+        )
+    }
+
+    fn update_foreign_count(&self, place_id: i64) -> Result<(), FfError> {
+        let references = bookmarks::foreign_references(self.conn)?;
+        self.write(
+            &format!(
+                "update moz_places set foreign_count = {} where id = ?1",
+                references
+            ),
+            params![place_id],
+        )
+    }
+
+    fn update_origin_frecency(&self, origin_id: i64) -> Result<(), FfError> {
+        self.write(bookmarks::ORIGIN_FRECENCY_SQL, params![origin_id])
+    }
+
+    fn add_place_visits(
+        &self,
+        place_id: i64,
+        visit_count: i64,
+        last_visit_date: Option<i64>,
+    ) -> Result<(), FfError> {
+        self.write(
+            "update moz_places
+            set visit_count = coalesce(visit_count, 0) + ?2,
+                last_visit_date = max(coalesce(last_visit_date, 0), coalesce(?3, 0))
+            where id = ?1",
+            params![place_id, visit_count, last_visit_date],
+        )
+    }
+}
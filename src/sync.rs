@@ -0,0 +1,792 @@
+use log::warn;
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::bookmarks::{self, Bookmark, Keyword, NewEntries, Origin, Place};
+use crate::cli::ConflictPolicy;
+use crate::error::FfError;
+use crate::history::{self, Visit};
+use crate::store::{PlacesStore, Table};
+
+/// Single change to places of the target profile, ids are already final.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncStep {
+    InsertOrigin(Origin),
+    // origin with the same prefix and host already exists in the target
+    ReuseOrigin {
+        source_id: i64,
+        target_id: i64,
+    },
+    InsertPlace(Place),
+    // place with the same guid, or the same url, already exists in the target
+    ReusePlace {
+        source_id: i64,
+        target_id: i64,
+    },
+    InsertBookmark(Bookmark),
+    // keyword of a place, inserted or reused, that the target doesn't have
+    InsertKeyword {
+        place_id: i64,
+        keyword: Keyword,
+    },
+    // bookmark with the same guid already exists in the target
+    ReuseBookmark {
+        source_id: i64,
+        target_id: i64,
+    },
+    // bookmark with the same guid was changed in the target, the synced one replaces it
+    UpdateBookmark {
+        bookmark: Bookmark,
+        replaced_fk: Option<i64>,
+    },
+    InsertVisit(Visit),
+    // new visits of a place that already exists in the target
+    AddPlaceVisits {
+        target_id: i64,
+        visit_count: i64,
+        last_visit_date: Option<i64>,
+    },
+}
+
+/// Bookmark with the same guid in both profiles, but with another title or url,
+/// it was changed in one of them after it was synced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkConflict {
+    pub guid: String,
+    pub target_id: i64,
+    pub title: Option<String>,
+    pub target_title: Option<String>,
+    pub url: Option<String>,
+    pub target_url: Option<String>,
+}
+
+/// Ordered list of changes that syncs new entries into the target profile.
+///
+/// Plan is computed up front from the target database, so it can be shown
+/// or checked before anything is written.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncPlan {
+    pub steps: Vec<SyncStep>,
+    pub remaps: IdRemaps,
+    pub conflicts: Vec<BookmarkConflict>,
+}
+
+/// Ids in the target profile by ids in the source profile, for every
+/// planned row, inserted or reused.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IdRemaps {
+    pub origins: HashMap<i64, i64>,
+    pub places: HashMap<i64, i64>,
+    pub bookmarks: HashMap<i64, i64>,
+    pub visits: HashMap<i64, i64>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn inserted_origins(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::InsertOrigin(_)))
+    }
+
+    pub fn reused_origins(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::ReuseOrigin { .. }))
+    }
+
+    pub fn inserted_places(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::InsertPlace(_)))
+    }
+
+    pub fn inserted_bookmarks(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::InsertBookmark(_)))
+    }
+
+    pub fn reused_places(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::ReusePlace { .. }))
+    }
+
+    pub fn reused_bookmarks(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::ReuseBookmark { .. }))
+    }
+
+    pub fn updated_bookmarks(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::UpdateBookmark { .. }))
+    }
+
+    pub fn inserted_keywords(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::InsertKeyword { .. }))
+    }
+
+    pub fn inserted_visits(&self) -> usize {
+        self.count(|s| matches!(s, SyncStep::InsertVisit(_)))
+    }
+
+    fn count<F: Fn(&SyncStep) -> bool>(&self, f: F) -> usize {
+        self.steps.iter().filter(|s| f(s)).count()
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Will insert {} origins, {} places, {} bookmarks",
+            self.inserted_origins(),
+            self.inserted_places(),
+            self.inserted_bookmarks()
+        )?;
+        if self.inserted_visits() > 0 {
+            write!(f, ", {} visits", self.inserted_visits())?;
+        }
+        if self.inserted_keywords() > 0 {
+            write!(f, ", {} keywords", self.inserted_keywords())?;
+        }
+        write!(f, "; reuse {} existing origins", self.reused_origins())?;
+        if self.reused_places() > 0 || self.reused_bookmarks() > 0 {
+            write!(
+                f,
+                ", {} places, {} bookmarks",
+                self.reused_places(),
+                self.reused_bookmarks()
+            )?;
+        }
+        if self.updated_bookmarks() > 0 {
+            write!(f, "; update {} bookmarks", self.updated_bookmarks())?;
+        }
+        if !self.conflicts.is_empty() {
+            write!(f, "; {} conflicts", self.conflicts.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decides how new entries of another profile are added to `store`.
+///
+/// New rows get ids after the current max id of their table, references
+/// between origins, places and bookmarks are remapped to the final ids.
+/// Places and bookmarks with a guid already present in the target are
+/// reused, so syncing the same entries again changes nothing.
+/// Inserted bookmarks are put after the children their folder already has.
+/// Nothing is written to the database.
+pub fn plan_sync<S: PlacesStore>(
+    store: &S,
+    new_bookmarks: &[Bookmark],
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+) -> Result<SyncPlan, FfError> {
+    plan_sync_into(
+        store,
+        new_bookmarks,
+        new_places,
+        new_origins,
+        None,
+        ConflictPolicy::Skip,
+    )
+}
+
+/// Same as `plan_sync`, with `target_folder` every bookmark that isn't
+/// inside of another synced folder is put into the folder with this title,
+/// which is created in the bookmarks menu if the target has none.
+///
+/// Bookmark with a guid already in the target, but with another title or
+/// url, is a conflict, it is resolved with `on_conflict`.
+pub fn plan_sync_into<S: PlacesStore>(
+    store: &S,
+    new_bookmarks: &[Bookmark],
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+    target_folder: Option<&str>,
+    on_conflict: ConflictPolicy,
+) -> Result<SyncPlan, FfError> {
+    let mut steps = vec![];
+    let origin_ids = plan_origins(store, new_origins, &mut steps)?;
+    let place_ids = plan_places(store, new_places, new_origins, &origin_ids, &mut steps)?;
+
+    // target ids are assigned to every bookmark first, so children
+    // can be remapped to their parents regardless of the order
+    let roots = store.root_folder_ids()?;
+    let mut bookmark_ids = HashMap::new();
+    let mut source_roots = HashSet::new();
+    let mut inserted = vec![];
+    let mut conflicts = vec![];
+    // source ids of conflicting bookmarks added next to the ones of the target
+    let mut kept_both = HashSet::new();
+    let mut next_id = store.next_id(Table::Bookmarks)?;
+    // id of the folder and the next free position in it
+    let mut target_folder = match target_folder {
+        None => None,
+        Some(title) => Some(plan_target_folder(
+            store,
+            title,
+            &roots,
+            &mut next_id,
+            &mut steps,
+        )?),
+    };
+    for bookmark in new_bookmarks {
+        let guid = bookmark.guid.as_deref();
+        match guid {
+            // roots exist in every profile and are never inserted
+            Some(guid) if bookmarks::is_root_guid(guid) => {
+                source_roots.insert(bookmark.id);
+                if let Some(target_id) = roots.get(guid) {
+                    bookmark_ids.insert(bookmark.id, *target_id);
+                }
+            }
+            _ => match id_by_guid(store, Table::Bookmarks, guid)? {
+                Some(target_id) => {
+                    let conflict = find_conflict(store, bookmark, target_id, new_places)?;
+                    match (conflict, on_conflict) {
+                        (None, _) => {
+                            bookmark_ids.insert(bookmark.id, target_id);
+                            steps.push(SyncStep::ReuseBookmark {
+                                source_id: bookmark.id,
+                                target_id,
+                            });
+                        }
+                        (Some((conflict, _)), ConflictPolicy::Skip) => {
+                            bookmark_ids.insert(bookmark.id, target_id);
+                            steps.push(SyncStep::ReuseBookmark {
+                                source_id: bookmark.id,
+                                target_id,
+                            });
+                            conflicts.push(conflict);
+                        }
+                        (Some((conflict, target)), ConflictPolicy::Overwrite) => {
+                            bookmark_ids.insert(bookmark.id, target_id);
+                            let fk = match (new_places, bookmark.fk) {
+                                (Some(_), Some(fk)) => match place_ids.get(&fk) {
+                                    None => Err("unable to find fk place from bookmark")?,
+                                    Some(id) => Some(*id),
+                                },
+                                (_, fk) => fk,
+                            };
+                            steps.push(SyncStep::UpdateBookmark {
+                                bookmark: Bookmark {
+                                    title: bookmark.title.clone(),
+                                    fk,
+                                    last_modified: bookmark.last_modified,
+                                    ..target.clone()
+                                },
+                                replaced_fk: target.fk,
+                            });
+                            conflicts.push(conflict);
+                        }
+                        (Some((conflict, _)), ConflictPolicy::KeepBoth) => {
+                            bookmark_ids.insert(bookmark.id, next_id);
+                            inserted.push(bookmark);
+                            kept_both.insert(bookmark.id);
+                            next_id += 1;
+                            conflicts.push(conflict);
+                        }
+                    }
+                }
+                None => {
+                    bookmark_ids.insert(bookmark.id, next_id);
+                    inserted.push(bookmark);
+                    next_id += 1;
+                }
+            },
+        }
+    }
+
+    let mut planned = vec![];
+    // ids of bookmarks already placed at the end of the target folder
+    let mut positioned = HashSet::new();
+    for bookmark in inserted {
+        let mut bookmark = bookmark.clone();
+        // same guid can't be in the target twice
+        if kept_both.contains(&bookmark.id) {
+            bookmark.guid = Some(bookmarks::generate_guid());
+        }
+        bookmark.id = bookmark_ids[&bookmark.id];
+        // keyword ids of the source mean nothing in the target,
+        // keywords are synced through their place instead
+        bookmark.keyword_id = None;
+
+        // tags are folders in the tags root, they can't be moved anywhere else
+        let keeps_parent = bookmark.parent_guid.as_deref() == Some(bookmarks::TAGS_GUID)
+            || bookmark.parent.is_some_and(|parent| {
+                bookmark_ids.contains_key(&parent) && !source_roots.contains(&parent)
+            });
+        match bookmark.parent.and_then(|parent| bookmark_ids.get(&parent)) {
+            // top level bookmarks are appended to the target folder instead
+            _ if target_folder.is_some() && !keeps_parent => {
+                if let Some((folder_id, position)) = &mut target_folder {
+                    bookmark.parent = Some(*folder_id);
+                    bookmark.position = Some(*position);
+                    *position += 1;
+                    positioned.insert(bookmark.id);
+                }
+            }
+            // parent is synced as well, or already exists in the target
+            Some(parent) => bookmark.parent = Some(*parent),
+            // bookmarks directly in a root are attached to the root of the target
+            None => match bookmark.parent_guid.as_deref() {
+                Some(parent_guid) if bookmarks::is_root_guid(parent_guid) => {
+                    bookmark.parent = match roots.get(parent_guid) {
+                        None => Err(format!(
+                            "Target profile has no `{}` root folder",
+                            parent_guid
+                        ))?,
+                        Some(id) => Some(*id),
+                    };
+                }
+                // folder existed before, ids of the same folder differ between profiles
+                Some(parent_guid) => {
+                    bookmark.parent = match store.id_by_guid(Table::Bookmarks, parent_guid)? {
+                        Some(id) => Some(id),
+                        None => {
+                            warn!(
+                                "Folder `{}` of bookmark `{}` isn't in the target profile, adding it to other bookmarks",
+                                parent_guid,
+                                bookmark.title.as_deref().unwrap_or_default()
+                            );
+                            match roots.get(bookmarks::UNFILED_GUID) {
+                                None => Err(format!(
+                                    "Target profile has no `{}` root folder",
+                                    bookmarks::UNFILED_GUID
+                                ))?,
+                                Some(id) => Some(*id),
+                            }
+                        }
+                    };
+                }
+                None => {}
+            },
+        }
+        if new_places.is_some() {
+            if let Some(fk) = bookmark.fk {
+                bookmark.fk = match place_ids.get(&fk) {
+                    None => Err("unable to find fk place from bookmark")?,
+                    Some(id) => Some(*id),
+                };
+            }
+        }
+        planned.push(bookmark);
+    }
+    assign_positions(store, &mut planned, &positioned)?;
+    steps.extend(planned.into_iter().map(SyncStep::InsertBookmark));
+
+    Ok(SyncPlan {
+        steps,
+        remaps: IdRemaps {
+            origins: origin_ids,
+            places: place_ids,
+            bookmarks: bookmark_ids,
+            visits: HashMap::new(),
+        },
+        conflicts,
+    })
+}
+
+// children of every parent are numbered in their source order, missing
+// positions last, the ones of existing folders after what the target has,
+// so positions in a folder never overlap or stay NULL
+fn assign_positions<S: PlacesStore>(
+    store: &S,
+    planned: &mut [Bookmark],
+    positioned: &HashSet<i64>,
+) -> Result<(), FfError> {
+    let new_ids: HashSet<i64> = planned.iter().map(|b| b.id).collect();
+    let mut order: Vec<usize> = (0..planned.len())
+        .filter(|i| !positioned.contains(&planned[*i].id))
+        .collect();
+    order.sort_by_key(|i| {
+        let bookmark = &planned[*i];
+        (bookmark.position.is_none(), bookmark.position, bookmark.id)
+    });
+
+    let mut next_positions = HashMap::new();
+    for i in order {
+        let parent = match planned[i].parent {
+            None => continue,
+            Some(parent) => parent,
+        };
+        let next = match next_positions.entry(parent) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) if new_ids.contains(&parent) => entry.insert(0),
+            Entry::Vacant(entry) => entry.insert(store.next_child_position(parent)?),
+        };
+        planned[i].position = Some(*next);
+        *next += 1;
+    }
+
+    Ok(())
+}
+
+// conflict when the bookmark of the target with the same guid has another
+// title or url, returned together with that bookmark
+fn find_conflict<S: PlacesStore>(
+    store: &S,
+    bookmark: &Bookmark,
+    target_id: i64,
+    new_places: Option<&HashMap<i64, Place>>,
+) -> Result<Option<(BookmarkConflict, Bookmark)>, FfError> {
+    let target = match store.bookmarks_after(target_id - 1, target_id)?.pop() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let url = match (new_places, bookmark.fk) {
+        (Some(new_places), Some(fk)) => new_places.get(&fk).and_then(|p| p.url.clone()),
+        _ => None,
+    };
+    let target_url = match target.fk {
+        None => None,
+        Some(fk) => store.places_for(&[fk])?.remove(&fk).and_then(|p| p.url),
+    };
+    // url is unknown when places aren't synced, only titles are compared then
+    let same_url = new_places.is_none() || url == target_url;
+    if bookmark.title == target.title && same_url {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        BookmarkConflict {
+            guid: target.guid.clone().unwrap_or_default(),
+            target_id,
+            title: bookmark.title.clone(),
+            target_title: target.title.clone(),
+            url,
+            target_url,
+        },
+        target,
+    )))
+}
+
+// existing folder with the title, or a new one at the end of the bookmarks menu,
+// returns its id and position after its last child
+fn plan_target_folder<S: PlacesStore>(
+    store: &S,
+    title: &str,
+    roots: &HashMap<String, i64>,
+    next_id: &mut i64,
+    steps: &mut Vec<SyncStep>,
+) -> Result<(i64, i64), FfError> {
+    if let Some(folder_id) = store.find_folder_by_title(title)? {
+        return Ok((folder_id, store.next_child_position(folder_id)?));
+    }
+
+    let menu_id = match roots.get(bookmarks::MENU_GUID) {
+        None => Err(format!(
+            "Target profile has no `{}` root folder",
+            bookmarks::MENU_GUID
+        ))?,
+        Some(id) => *id,
+    };
+    let folder_id = *next_id;
+    *next_id += 1;
+    steps.push(SyncStep::InsertBookmark(bookmarks::new_folder(
+        folder_id,
+        menu_id,
+        store.next_child_position(menu_id)?,
+        title,
+    )));
+
+    Ok((folder_id, 0))
+}
+
+/// Ids the new entries get in `store`, without writing anything.
+///
+/// Same remapping `plan_sync` uses, every max id is queried once per table.
+pub fn plan_id_remaps<S: PlacesStore>(
+    store: &S,
+    entries: &NewEntries,
+) -> Result<IdRemaps, FfError> {
+    let (new_bookmarks, new_places, new_origins) = entries;
+    let plan = plan_sync(
+        store,
+        new_bookmarks.as_deref().unwrap_or_default(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )?;
+
+    Ok(plan.remaps)
+}
+
+/// Decides how visits of another profile are added to `store`.
+///
+/// Places and origins of the visits are planned the same way as for
+/// bookmarks. Visits of places that already exist in the target update
+/// visit count and last visit date of the place, new places keep theirs.
+/// Nothing is written to the database.
+pub fn plan_history_sync<S: PlacesStore>(
+    store: &S,
+    new_visits: &[Visit],
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+) -> Result<SyncPlan, FfError> {
+    let mut steps = vec![];
+    let origin_ids = plan_origins(store, new_origins, &mut steps)?;
+    let place_ids = plan_places(store, new_places, new_origins, &origin_ids, &mut steps)?;
+    let reused_places: HashSet<i64> = steps
+        .iter()
+        .filter_map(|step| match step {
+            SyncStep::ReusePlace { target_id, .. } => Some(*target_id),
+            _ => None,
+        })
+        .collect();
+
+    let mut visit_ids = HashMap::new();
+    // visit count and last visit date added to the reused places
+    let mut reused_visits: HashMap<i64, (i64, Option<i64>)> = HashMap::new();
+    let first_id = store.next_id(Table::Visits)?;
+    for (id, visit) in (first_id..).zip(new_visits) {
+        let mut visit = visit.clone();
+        visit_ids.insert(visit.id, id);
+        visit.id = id;
+
+        visit.place_id = match place_ids.get(&visit.place_id) {
+            None => Err("unable to find place from visit")?,
+            Some(id) => *id,
+        };
+        // visits from before the baseline may have other ids in the target,
+        // 0 is what firefox uses for a visit without a source
+        visit.from_visit = visit
+            .from_visit
+            .map(|from| visit_ids.get(&from).cloned().unwrap_or(0));
+
+        if reused_places.contains(&visit.place_id) && history::is_counted_visit(visit.visit_type) {
+            let (count, last_date) = reused_visits.entry(visit.place_id).or_default();
+            *count += 1;
+            *last_date = (*last_date).max(Some(visit.visit_date));
+        }
+        steps.push(SyncStep::InsertVisit(visit));
+    }
+    for target_id in sorted_keys(&reused_visits) {
+        let (visit_count, last_visit_date) = reused_visits[&target_id];
+        steps.push(SyncStep::AddPlaceVisits {
+            target_id,
+            visit_count,
+            last_visit_date,
+        });
+    }
+
+    Ok(SyncPlan {
+        steps,
+        remaps: IdRemaps {
+            origins: origin_ids,
+            places: place_ids,
+            bookmarks: HashMap::new(),
+            visits: visit_ids,
+        },
+        conflicts: vec![],
+    })
+}
+
+// origins with the same prefix and host are reused, returns target ids by source ids
+fn plan_origins<S: PlacesStore>(
+    store: &S,
+    new_origins: Option<&HashMap<i64, Origin>>,
+    steps: &mut Vec<SyncStep>,
+) -> Result<HashMap<i64, i64>, FfError> {
+    let mut origin_ids = HashMap::new();
+    let new_origins = match new_origins {
+        None => return Ok(origin_ids),
+        Some(new_origins) => new_origins,
+    };
+    let mut next_id = store.next_id(Table::Origins)?;
+    for source_id in sorted_keys(new_origins) {
+        let origin = &new_origins[&source_id];
+        match store.origin_id(&origin.prefix, &origin.host)? {
+            Some(target_id) => {
+                origin_ids.insert(source_id, target_id);
+                steps.push(SyncStep::ReuseOrigin {
+                    source_id,
+                    target_id,
+                });
+            }
+            None => {
+                origin_ids.insert(source_id, next_id);
+                steps.push(SyncStep::InsertOrigin(Origin {
+                    id: next_id,
+                    ..origin.clone()
+                }));
+                next_id += 1;
+            }
+        }
+    }
+
+    Ok(origin_ids)
+}
+
+// places with the same guid are reused, returns target ids by source ids
+fn plan_places<S: PlacesStore>(
+    store: &S,
+    new_places: Option<&HashMap<i64, Place>>,
+    new_origins: Option<&HashMap<i64, Origin>>,
+    origin_ids: &HashMap<i64, i64>,
+    steps: &mut Vec<SyncStep>,
+) -> Result<HashMap<i64, i64>, FfError> {
+    let mut place_ids = HashMap::new();
+    let new_places = match new_places {
+        None => return Ok(place_ids),
+        Some(new_places) => new_places,
+    };
+    let mut next_id = store.next_id(Table::Places)?;
+    for source_id in sorted_keys(new_places) {
+        let place = &new_places[&source_id];
+        // same guid means the place was synced before, same url that the page
+        // was visited or bookmarked in the target on its own, a second row
+        // for it would split its visit count and frecency
+        let existing_id = match id_by_guid(store, Table::Places, place.guid.as_deref())? {
+            Some(id) => Some(id),
+            None => match &place.url {
+                None => None,
+                Some(url) => store.place_id_by_url(place.url_hash, url)?,
+            },
+        };
+        if let Some(target_id) = existing_id {
+            place_ids.insert(source_id, target_id);
+            steps.push(SyncStep::ReusePlace {
+                source_id,
+                target_id,
+            });
+            plan_keywords(store, target_id, &place.keywords, steps)?;
+            continue;
+        }
+        let mut place = place.clone();
+        place.id = next_id;
+        next_id += 1;
+        // keywords are rows of their own, inserted after the place
+        let keywords = std::mem::take(&mut place.keywords);
+        // origin could have gotten a different id, or be an existing one
+        if new_origins.is_some() {
+            if let Some(origin_id) = place.origin_id {
+                place.origin_id = match origin_ids.get(&origin_id) {
+                    None => Err("unable to find origin from place")?,
+                    Some(id) => Some(*id),
+                };
+            }
+        }
+        place_ids.insert(source_id, place.id);
+        let place_id = place.id;
+        steps.push(SyncStep::InsertPlace(place));
+        plan_keywords(store, place_id, &keywords, steps)?;
+    }
+
+    Ok(place_ids)
+}
+
+// keywords the target doesn't have yet, a keyword can only open one place,
+// so the one the target already uses for another place is kept
+fn plan_keywords<S: PlacesStore>(
+    store: &S,
+    place_id: i64,
+    keywords: &[Keyword],
+    steps: &mut Vec<SyncStep>,
+) -> Result<(), FfError> {
+    if !keywords.is_empty() && !store.has_keywords()? {
+        for keyword in keywords {
+            warn!(
+                "Target profile has no keywords table, keyword `{}` isn't synced",
+                keyword.keyword
+            );
+        }
+        return Ok(());
+    }
+    for keyword in keywords {
+        match store.keyword_place_id(&keyword.keyword)? {
+            Some(id) if id == place_id => {}
+            Some(_) => warn!(
+                "Keyword `{}` opens another page in the target profile, it isn't synced",
+                keyword.keyword
+            ),
+            None => steps.push(SyncStep::InsertKeyword {
+                place_id,
+                keyword: keyword.clone(),
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes every step of the plan to `store`, in order.
+pub fn apply_sync_plan<S: PlacesStore>(store: &S, plan: &SyncPlan) -> Result<(), FfError> {
+    for step in &plan.steps {
+        match step {
+            SyncStep::ReuseOrigin { .. }
+            | SyncStep::ReusePlace { .. }
+            | SyncStep::ReuseBookmark { .. } => {}
+            SyncStep::InsertOrigin(origin) => store.insert_origin(origin)?,
+            SyncStep::InsertPlace(place) => store.insert_place(place)?,
+            SyncStep::InsertVisit(visit) => store.insert_visit(visit)?,
+            SyncStep::AddPlaceVisits {
+                target_id,
+                visit_count,
+                last_visit_date,
+            } => store.add_place_visits(*target_id, *visit_count, *last_visit_date)?,
+            SyncStep::InsertBookmark(bookmark) => store.insert_bookmark(bookmark)?,
+            SyncStep::InsertKeyword { place_id, keyword } => {
+                store.insert_keyword(*place_id, keyword)?
+            }
+            SyncStep::UpdateBookmark { bookmark, .. } => store.update_bookmark(bookmark)?,
+        }
+    }
+
+    // counts copied from the source are about its bookmarks, and reused
+    // places get new bookmarks, so both are counted again in the target,
+    // as well as places a replaced bookmark pointed to
+    let mut counted_places: Vec<i64> = plan
+        .steps
+        .iter()
+        .flat_map(|step| match step {
+            SyncStep::InsertPlace(place) => vec![Some(place.id)],
+            SyncStep::InsertBookmark(bookmark) => vec![bookmark.fk],
+            SyncStep::InsertKeyword { place_id, .. } => vec![Some(*place_id)],
+            SyncStep::UpdateBookmark {
+                bookmark,
+                replaced_fk,
+            } => vec![bookmark.fk, *replaced_fk],
+            _ => vec![],
+        })
+        .flatten()
+        .collect();
+    counted_places.sort_unstable();
+    counted_places.dedup();
+    for place_id in counted_places {
+        store.update_foreign_count(place_id)?;
+    }
+    // frecency copied with an origin is about places of the source, it is
+    // derived again once places of the target are in
+    let mut ranked_origins: Vec<i64> = plan
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            SyncStep::InsertOrigin(origin) => Some(origin.id),
+            SyncStep::InsertPlace(place) => place.origin_id,
+            _ => None,
+        })
+        .collect();
+    ranked_origins.sort_unstable();
+    ranked_origins.dedup();
+    for origin_id in ranked_origins {
+        store.update_origin_frecency(origin_id)?;
+    }
+
+    Ok(())
+}
+
+// id of the row with the guid, rows without guid are never matched
+fn id_by_guid<S: PlacesStore>(
+    store: &S,
+    table: Table,
+    guid: Option<&str>,
+) -> Result<Option<i64>, FfError> {
+    match guid {
+        None => Ok(None),
+        Some(guid) => store.id_by_guid(table, guid),
+    }
+}
+
+// maps are keyed by ids of the source profile, sorting keeps the plan stable
+fn sorted_keys<T>(map: &HashMap<i64, T>) -> Vec<i64> {
+    let mut keys: Vec<i64> = map.keys().cloned().collect();
+    keys.sort_unstable();
+    keys
+}
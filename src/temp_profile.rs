@@ -0,0 +1,76 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Folder the profile is copied into and launched from.
+///
+/// It is removed when dropped, so a run that fails half way doesn't leave
+/// a partial copy behind, unless it was kept.
+pub struct TempProfile {
+    path: PathBuf,
+    // temp dir the profile is created in, `None` for a profile at a given path
+    dir: Option<TempDir>,
+    kept: bool,
+}
+
+impl TempProfile {
+    /// Profile named `name` inside of a new temp dir, the folder itself
+    /// is not created.
+    pub fn new(name: &str) -> io::Result<TempProfile> {
+        let dir = TempDir::new()?;
+        Ok(TempProfile {
+            path: dir.path().join(name),
+            dir: Some(dir),
+            kept: false,
+        })
+    }
+
+    /// Profile at a path that outlives the run once it is kept,
+    /// like a staged or cached copy.
+    pub fn at(path: PathBuf) -> TempProfile {
+        TempProfile {
+            path,
+            dir: None,
+            kept: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the profile on disk after the guard is dropped.
+    pub fn keep(&mut self) {
+        if let Some(dir) = self.dir.take() {
+            let _ = dir.keep();
+        }
+        self.kept = true;
+    }
+
+    /// Removes the profile now, unless it was kept, reporting errors
+    /// that dropping it ignores.
+    pub fn close(mut self) -> io::Result<()> {
+        if self.kept {
+            return Ok(());
+        }
+        // nothing is left for drop
+        self.kept = true;
+        match self.dir.take() {
+            Some(dir) => dir.close(),
+            None if self.path.exists() => fs::remove_dir_all(&self.path),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TempProfile {
+    fn drop(&mut self) {
+        // temp dir removes itself
+        if !self.kept && self.dir.is_none() && self.path.exists() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
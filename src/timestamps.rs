@@ -0,0 +1,139 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::FfError;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Firefox timestamp (PRTime, microseconds since unix epoch) of the time.
+pub fn to_firefox_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_micros() as i64,
+        Err(e) => -(e.duration().as_micros() as i64),
+    }
+}
+
+/// Time of a Firefox timestamp (PRTime, microseconds since unix epoch).
+pub fn from_firefox_timestamp(timestamp: i64) -> SystemTime {
+    let duration = Duration::from_micros(timestamp.unsigned_abs());
+    if timestamp >= 0 {
+        UNIX_EPOCH + duration
+    } else {
+        UNIX_EPOCH - duration
+    }
+}
+
+/// Parses ISO 8601 date or date and time into a Firefox timestamp.
+///
+/// Accepts `2024-01-31`, `2024-01-31T10:00`, `2024-01-31T10:00:30` with an
+/// optional fraction of seconds, and `Z` or `+02:00` offset. Time without
+/// an offset is taken as UTC, a date alone is midnight UTC.
+pub fn parse_firefox_timestamp(value: &str) -> Result<i64, FfError> {
+    let invalid = || {
+        FfError::Message(format!(
+            "Invalid date `{}`, expected ISO 8601 like 2024-01-31T10:00:00Z",
+            value
+        ))
+    };
+    let (date, time) = match value.split_once(['T', ' ']) {
+        None => (value, None),
+        Some((date, time)) => (date, Some(time)),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let mut next_number = |len: usize| -> Result<i64, FfError> {
+        match date_parts.next() {
+            Some(part) if part.len() == len && part.bytes().all(|b| b.is_ascii_digit()) => {
+                Ok(part.parse().map_err(|_| invalid())?)
+            }
+            _ => Err(invalid()),
+        }
+    };
+    let (year, month, day) = (next_number(4)?, next_number(2)?, next_number(2)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    let mut seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY;
+    let mut micros = 0;
+
+    if let Some(time) = time {
+        // offset is split off first, `-` of the date is already gone
+        let (time, offset) = match time.find(['Z', '+', '-']) {
+            None => (time, None),
+            Some(i) => (&time[..i], Some(&time[i..])),
+        };
+        let (time, fraction) = match time.split_once('.') {
+            None => (time, None),
+            Some((time, fraction)) => (time, Some(fraction)),
+        };
+        let parts = time
+            .split(':')
+            .map(
+                |part| match part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit()) {
+                    true => part.parse::<i64>().map_err(|_| invalid()),
+                    false => Err(invalid()),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let (hour, minute, second) = match parts.as_slice() {
+            [hour, minute] => (*hour, *minute, 0),
+            [hour, minute, second] => (*hour, *minute, *second),
+            _ => return Err(invalid()),
+        };
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+        seconds += hour * 3600 + minute * 60 + second;
+
+        if let Some(fraction) = fraction {
+            if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            // only microseconds are kept, like firefox does
+            let digits: String = fraction.chars().chain("000000".chars()).take(6).collect();
+            micros = digits.parse().map_err(|_| invalid())?;
+        }
+
+        match offset {
+            None | Some("Z") => {}
+            Some(offset) => {
+                let sign = if offset.starts_with('-') { 1 } else { -1 };
+                let (hours, minutes) = offset[1..].split_once(':').ok_or_else(invalid)?;
+                let hours: i64 = hours.parse().map_err(|_| invalid())?;
+                let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+                if hours > 23 || minutes > 59 {
+                    return Err(invalid());
+                }
+                seconds += sign * (hours * 3600 + minutes * 60);
+            }
+        }
+    }
+
+    Ok(seconds * MICROS_PER_SECOND + micros)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since unix epoch of the date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // years start in march, so the leap day is the last day of a year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,97 @@
+#![cfg(unix)]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// files firefox writes to while it runs, databases with their sidecars included
+const WRITTEN_FILES: [&str; 10] = [
+    "prefs.js",
+    "places.sqlite",
+    "places.sqlite-shm",
+    "formhistory.sqlite-wal",
+    "cert9.db",
+    "key4.db",
+    "logins.json",
+    "handlers.json",
+    "datareporting/state.json",
+    "storage/default/moz-extension/idb/data.sqlite",
+];
+
+// browser that appends to every written file of the profile it got,
+// and lists the symlinks of the profile into `links`
+fn writing_browser(dir: &Path) -> PathBuf {
+    let browser = dir.join("firefox");
+    let mut script = "#!/bin/sh\n".to_string();
+    for file in &WRITTEN_FILES {
+        script.push_str(&format!("echo changed >> \"$2/{}\"\n", file));
+    }
+    script.push_str(&format!(
+        "find \"$2\" -maxdepth 1 -type l -exec basename {{}} \\; > {}/links\n",
+        dir.display()
+    ));
+    fs::write(&browser, script).unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    browser
+}
+
+// contents of every file under the folder by their relative path
+fn snapshot(folder: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = vec![];
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let content = fs::read(&path).unwrap();
+                files.push((path.strip_prefix(folder).unwrap().to_path_buf(), content));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+// single test, a script written while another test spawns a process
+// can fail to start with "text file busy"
+#[test]
+fn attached_profile_leaves_the_source_untouched() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    for file in &WRITTEN_FILES {
+        let path = profile.path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("original {}\n", file)).unwrap();
+    }
+    fs::create_dir(profile.path().join("extensions")).unwrap();
+    fs::write(
+        profile.path().join("extensions/addon@example.com.xpi"),
+        "xpi",
+    )
+    .unwrap();
+    fs::create_dir(profile.path().join("gmp-widevinecdm")).unwrap();
+    let before = snapshot(profile.path());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg(writing_browser(config.path()))
+        .arg("--attach")
+        .env("XDG_CONFIG_HOME", config.path())
+        .env("XDG_CACHE_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(snapshot(profile.path()), before);
+    let links = fs::read_to_string(config.path().join("links")).unwrap();
+    let mut links: Vec<_> = links.lines().collect();
+    links.sort_unstable();
+    assert_eq!(links, vec!["extensions", "gmp-widevinecdm"]);
+}
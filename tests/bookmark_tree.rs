@@ -0,0 +1,187 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks::{self, BookmarkNode};
+
+fn folder(title: &str, children: Vec<BookmarkNode>) -> BookmarkNode {
+    BookmarkNode::Folder {
+        title: Some(title.to_string()),
+        children,
+    }
+}
+
+fn link(title: &str, url: &str) -> BookmarkNode {
+    BookmarkNode::Link {
+        title: Some(title.to_string()),
+        url: Some(url.to_string()),
+    }
+}
+
+// children of the root, the tags root is never part of the tree
+fn roots(
+    menu: Vec<BookmarkNode>,
+    toolbar: Vec<BookmarkNode>,
+    unfiled: Vec<BookmarkNode>,
+    mobile: Vec<BookmarkNode>,
+) -> BookmarkNode {
+    folder(
+        "",
+        vec![
+            folder("menu", menu),
+            folder("toolbar", toolbar),
+            folder("unfiled", unfiled),
+            folder("mobile", mobile),
+        ],
+    )
+}
+
+fn tree(conn: &Connection) -> BookmarkNode {
+    bookmarks::build_bookmark_tree(conn).unwrap()
+}
+
+#[test]
+fn empty_profile_has_only_roots() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+
+    assert_eq!(tree(&conn), roots(vec![], vec![], vec![], vec![]));
+}
+
+#[test]
+fn nested_folders_under_every_root() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_folder(&conn, 7, Some(3), 0, "News", "folder000007");
+    common::insert_link(&conn, 8, 7, 0, "Daily", "https://news.example.com/");
+    common::insert_folder(&conn, 9, Some(7), 1, "Local", "folder000009");
+    common::insert_link(&conn, 10, 9, 0, "City", "https://city.example.com/");
+    common::insert_link(&conn, 11, 2, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&conn, 12, 5, 0, "Later", "https://later.example.com/");
+    common::insert_link(&conn, 13, 6, 0, "Phone", "https://phone.example.com/");
+
+    assert_eq!(
+        tree(&conn),
+        roots(
+            vec![link("Docs", "https://docs.example.com/")],
+            vec![folder(
+                "News",
+                vec![
+                    link("Daily", "https://news.example.com/"),
+                    folder("Local", vec![link("City", "https://city.example.com/")]),
+                ]
+            )],
+            vec![link("Later", "https://later.example.com/")],
+            vec![link("Phone", "https://phone.example.com/")],
+        )
+    );
+}
+
+#[test]
+fn children_are_ordered_by_position_with_missing_positions_last() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_link(&conn, 7, 3, 2, "Third", "https://c.example.com/");
+    common::insert_link(&conn, 8, 3, 0, "First", "https://a.example.com/");
+    common::insert_link(&conn, 9, 3, 0, "Unknown", "https://d.example.com/");
+    common::insert_separator(&conn, 10, 3, 1);
+    common::insert_link(&conn, 11, 3, 0, "Unknown too", "https://e.example.com/");
+    conn.execute(
+        "update moz_bookmarks set position = null where id in (9, 11)",
+        params![],
+    )
+    .unwrap();
+
+    assert_eq!(
+        tree(&conn),
+        roots(
+            vec![],
+            vec![
+                link("First", "https://a.example.com/"),
+                BookmarkNode::Separator,
+                link("Third", "https://c.example.com/"),
+                link("Unknown", "https://d.example.com/"),
+                link("Unknown too", "https://e.example.com/"),
+            ],
+            vec![],
+            vec![],
+        )
+    );
+}
+
+#[test]
+fn tags_are_skipped() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_folder(&conn, 7, Some(4), 0, "rust", "folder000007");
+    common::insert_link(&conn, 8, 7, 0, "", "https://www.rust-lang.org/");
+    common::insert_link(&conn, 9, 2, 0, "Rust", "https://www.rust-lang.org/");
+
+    assert_eq!(
+        tree(&conn),
+        roots(
+            vec![link("Rust", "https://www.rust-lang.org/")],
+            vec![],
+            vec![],
+            vec![],
+        )
+    );
+}
+
+#[test]
+fn link_without_a_place_has_no_url() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_link(&conn, 7, 2, 0, "Gone", "https://gone.example.com/");
+    conn.execute("delete from moz_places", params![]).unwrap();
+
+    assert_eq!(
+        tree(&conn),
+        roots(
+            vec![BookmarkNode::Link {
+                title: Some("Gone".to_string()),
+                url: None,
+            }],
+            vec![],
+            vec![],
+            vec![],
+        )
+    );
+}
+
+#[test]
+fn cycles_and_missing_parents_are_left_out() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    // two folders that are each other's parent, and one that is its own
+    common::insert_folder(&conn, 7, Some(8), 0, "Loop a", "folder000007");
+    common::insert_folder(&conn, 8, Some(7), 0, "Loop b", "folder000008");
+    common::insert_folder(&conn, 9, Some(9), 0, "Self", "folder000009");
+    common::insert_link(&conn, 10, 7, 0, "In loop", "https://loop.example.com/");
+    common::insert_link(&conn, 11, 999, 0, "Lost", "https://lost.example.com/");
+    common::insert_folder(&conn, 12, None, 0, "No parent", "folder000012");
+    common::insert_link(&conn, 13, 2, 0, "Kept", "https://kept.example.com/");
+
+    assert_eq!(
+        tree(&conn),
+        roots(
+            vec![link("Kept", "https://kept.example.com/")],
+            vec![],
+            vec![],
+            vec![],
+        )
+    );
+}
+
+#[test]
+fn missing_root_is_an_error() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    conn.execute("delete from moz_bookmarks where id = 1", params![])
+        .unwrap();
+
+    assert!(bookmarks::build_bookmark_tree(&conn).is_err());
+}
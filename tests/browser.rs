@@ -0,0 +1,37 @@
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::browser;
+
+#[test]
+fn first_existing_install_is_used() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("snap/firefox").display().to_string();
+    let installed = dir.path().join("opt-firefox").display().to_string();
+    fs::write(&installed, "").unwrap();
+    let other = dir.path().join("other-firefox").display().to_string();
+    fs::write(&other, "").unwrap();
+
+    let command = browser::resolve_browser_binary_from(&[
+        &[missing.as_str()],
+        &[installed.as_str(), "--new-instance"],
+        &[other.as_str()],
+    ]);
+
+    assert_eq!(
+        command,
+        Some(vec![installed.clone(), "--new-instance".to_string()])
+    );
+}
+
+#[test]
+fn nothing_is_resolved_without_installs() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("firefox").display().to_string();
+
+    assert_eq!(
+        browser::resolve_browser_binary_from(&[&[missing.as_str()]]),
+        None
+    );
+}
@@ -0,0 +1,42 @@
+use tempfile::TempDir;
+
+use std::collections::HashSet;
+use std::fs;
+
+use fftemplates::cache;
+
+#[test]
+fn fingerprint_changes_with_content_size() {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "a").unwrap();
+    let ignore = HashSet::new();
+    let before = cache::profile_fingerprint(profile.path(), &ignore).unwrap();
+
+    assert_eq!(
+        before,
+        cache::profile_fingerprint(profile.path(), &ignore).unwrap()
+    );
+
+    fs::write(profile.path().join("prefs.js"), "ab").unwrap();
+    assert_ne!(
+        before,
+        cache::profile_fingerprint(profile.path(), &ignore).unwrap()
+    );
+}
+
+#[test]
+fn fingerprint_skips_ignored_entries() {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "a").unwrap();
+    let mut ignore = HashSet::new();
+    ignore.insert("cache2".to_string());
+    let before = cache::profile_fingerprint(profile.path(), &ignore).unwrap();
+
+    fs::create_dir(profile.path().join("cache2")).unwrap();
+    fs::write(profile.path().join("cache2").join("entry"), "data").unwrap();
+
+    assert_eq!(
+        before,
+        cache::profile_fingerprint(profile.path(), &ignore).unwrap()
+    );
+}
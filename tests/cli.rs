@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use fftemplates::cli::into_config;
+use fftemplates::cli::split_command;
+use fftemplates::cli::Args;
+use fftemplates::cli::SessionMode;
+
+#[test]
+fn defaults_to_default_profile() {
+    let config = into_config(Args::default()).unwrap();
+
+    assert_eq!(config.profile_name, "default");
+    assert!(config.profile_folder.ends_with(".mozilla/firefox"));
+    assert_eq!(config.session_mode, SessionMode::None);
+}
+
+#[test]
+fn load_and_save_same_file() {
+    let args = Args {
+        save_load_session: Some("work.jsonlz4".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(
+        config.session_mode,
+        SessionMode::LoadAndSave("work.jsonlz4".to_string())
+    );
+    assert!(!config.session_mode.fail_if_load_missing());
+}
+
+#[test]
+fn load_and_save_different_files() {
+    let args = Args {
+        load_session: Some("in.jsonlz4".to_string()),
+        save_session: Some("out.jsonlz4".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(config.session_mode.load_file(), Some("in.jsonlz4"));
+    assert_eq!(config.session_mode.save_file(), Some("out.jsonlz4"));
+    assert!(config.session_mode.fail_if_load_missing());
+}
+
+#[test]
+fn parses_numbers() {
+    let args = Args {
+        timeout: Some("30".to_string()),
+        depth: Some("2".to_string()),
+        profile_index: Some("1".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+    assert_eq!(config.tree_depth, Some(2));
+    assert_eq!(config.profile_index, Some(1));
+    // profile is given, there is nothing to pick
+    assert!(!config.pick_profile);
+}
+
+#[test]
+fn rejects_invalid_timeout() {
+    let args = Args {
+        timeout: Some("soon".to_string()),
+        ..Args::default()
+    };
+
+    assert!(into_config(args).is_err());
+}
+
+#[test]
+fn parses_env_variables() {
+    let args = Args {
+        env: vec!["MOZ_ENABLE_WAYLAND=1".to_string(), "EMPTY=".to_string()],
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(
+        config.env,
+        vec![
+            ("MOZ_ENABLE_WAYLAND".to_string(), "1".to_string()),
+            ("EMPTY".to_string(), "".to_string())
+        ]
+    );
+}
+
+#[test]
+fn rejects_invalid_env_variables() {
+    for variable in &["NO_VALUE", "=1"] {
+        let args = Args {
+            env: vec![variable.to_string()],
+            ..Args::default()
+        };
+
+        assert!(into_config(args).is_err());
+    }
+}
+
+#[test]
+fn resolves_session_target() {
+    let args = Args {
+        session_target: Some("recovery".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(
+        config.session_target.as_deref(),
+        Some("sessionstore-backups/recovery.jsonlz4")
+    );
+}
+
+#[test]
+fn profile_root_and_path() {
+    let args = Args {
+        profile_root: Some("/srv/profiles".to_string()),
+        profile_path: Some("/srv/profiles/abc.work".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(config.profile_folder, PathBuf::from("/srv/profiles"));
+    assert_eq!(
+        config.profile_path,
+        Some(PathBuf::from("/srv/profiles/abc.work"))
+    );
+}
+
+#[test]
+fn validates_temp_name() {
+    let args = Args {
+        temp_name: Some("work-browser".to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+    assert_eq!(config.temp_name.as_deref(), Some("work-browser"));
+
+    for name in &["", "..", "a/b", "a\\b"] {
+        let args = Args {
+            temp_name: Some(name.to_string()),
+            ..Args::default()
+        };
+        assert!(into_config(args).is_err(), "{} should be rejected", name);
+    }
+}
+
+#[test]
+fn splits_wrapper_command() {
+    let args = Args {
+        wrapper: Some(r#"firejail --private --name="my firefox" 'a b'\ c"#.to_string()),
+        ..Args::default()
+    };
+    let config = into_config(args).unwrap();
+
+    assert_eq!(
+        config.wrapper,
+        vec!["firejail", "--private", "--name=my firefox", "a b c"]
+    );
+    assert!(split_command("strace 'unclosed").is_err());
+    assert!(into_config(Args {
+        wrapper: Some("  ".to_string()),
+        ..Args::default()
+    })
+    .is_err());
+}
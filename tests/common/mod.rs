@@ -0,0 +1,200 @@
+// helpers shared by integration tests, not every test uses all of them
+#![allow(dead_code)]
+
+use rusqlite::{params, Connection};
+
+use std::path::Path;
+
+use fftemplates::bookmarks;
+
+const PLACES_SCHEMA: &str = "
+    CREATE TABLE moz_origins (
+        id INTEGER PRIMARY KEY, prefix TEXT NOT NULL, host TEXT NOT NULL,
+        frecency INTEGER NOT NULL, UNIQUE (prefix, host));
+    CREATE TABLE moz_places (
+        id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR, rev_host LONGVARCHAR,
+        visit_count INTEGER DEFAULT 0, hidden INTEGER DEFAULT 0 NOT NULL,
+        typed INTEGER DEFAULT 0 NOT NULL, favicon_id INTEGER,
+        frecency INTEGER DEFAULT -1 NOT NULL, last_visit_date INTEGER, guid TEXT,
+        foreign_count INTEGER DEFAULT 0 NOT NULL, url_hash INTEGER DEFAULT 0 NOT NULL,
+        description TEXT, preview_image_url TEXT,
+        origin_id INTEGER REFERENCES moz_origins(id));
+    CREATE TABLE moz_bookmarks (
+        id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER DEFAULT NULL, parent INTEGER,
+        position INTEGER, title LONGVARCHAR, keyword_id INTEGER, folder_type TEXT,
+        dateAdded INTEGER, lastModified INTEGER, guid TEXT,
+        syncStatus INTEGER NOT NULL DEFAULT 0, syncChangeCounter INTEGER NOT NULL DEFAULT 1);
+    CREATE TABLE moz_historyvisits (
+        id INTEGER PRIMARY KEY, from_visit INTEGER, place_id INTEGER, visit_date INTEGER,
+        visit_type INTEGER, session INTEGER, source INTEGER DEFAULT 0 NOT NULL,
+        triggeringPlaceId INTEGER);
+";
+
+/// Creates places.sqlite in the folder with the standard roots:
+/// root 1, menu 2, toolbar 3, tags 4, unfiled 5, mobile 6.
+pub fn create_places(profile_folder: &Path) -> Connection {
+    let conn = create_empty_places(profile_folder);
+
+    insert_folder(&conn, 1, None, 0, "", bookmarks::ROOT_GUID);
+    insert_folder(&conn, 2, Some(1), 0, "menu", bookmarks::MENU_GUID);
+    insert_folder(&conn, 3, Some(1), 1, "toolbar", bookmarks::TOOLBAR_GUID);
+    insert_folder(&conn, 4, Some(1), 2, "tags", bookmarks::TAGS_GUID);
+    insert_folder(&conn, 5, Some(1), 3, "unfiled", bookmarks::UNFILED_GUID);
+    insert_folder(&conn, 6, Some(1), 4, "mobile", bookmarks::MOBILE_GUID);
+
+    conn
+}
+
+/// Creates places.sqlite in the folder without any rows.
+pub fn create_empty_places(profile_folder: &Path) -> Connection {
+    let conn = Connection::open(profile_folder.join("places.sqlite")).unwrap();
+    conn.execute_batch(PLACES_SCHEMA).unwrap();
+
+    conn
+}
+
+pub fn insert_folder(
+    conn: &Connection,
+    id: i64,
+    parent: Option<i64>,
+    position: i64,
+    title: &str,
+    guid: &str,
+) {
+    conn.execute(
+        "insert into moz_bookmarks (id, type, parent, position, title, guid)
+        values (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, bookmarks::TYPE_FOLDER, parent, position, title, guid],
+    )
+    .unwrap();
+}
+
+/// Inserts a link bookmark together with its place and origin.
+pub fn insert_link(conn: &Connection, id: i64, parent: i64, position: i64, title: &str, url: &str) {
+    let host = url.split("://").nth(1).unwrap_or(url).split('/').next();
+    conn.execute(
+        "insert or ignore into moz_origins (prefix, host, frecency) values ('https://', ?1, 1)",
+        params![host],
+    )
+    .unwrap();
+    conn.execute(
+        "insert into moz_places (url, title, guid, foreign_count, origin_id)
+        select ?1, ?2, ?3, 1, id from moz_origins where host = ?4",
+        params![url, title, format!("place{:07}", id), host],
+    )
+    .unwrap();
+    conn.execute(
+        "insert into moz_bookmarks (id, type, fk, parent, position, title, guid)
+        values (?1, ?2, last_insert_rowid(), ?3, ?4, ?5, ?6)",
+        params![
+            id,
+            bookmarks::TYPE_BOOKMARK,
+            parent,
+            position,
+            title,
+            format!("bookmark{:04}", id)
+        ],
+    )
+    .unwrap();
+}
+
+pub fn insert_separator(conn: &Connection, id: i64, parent: i64, position: i64) {
+    conn.execute(
+        "insert into moz_bookmarks (id, type, parent, position, guid)
+        values (?1, ?2, ?3, ?4, ?5)",
+        params![
+            id,
+            bookmarks::TYPE_SEPARATOR,
+            parent,
+            position,
+            format!("separat{:05}", id)
+        ],
+    )
+    .unwrap();
+}
+
+/// Creates moz_keywords, which older profiles and `create_places` don't have.
+pub fn create_keywords(conn: &Connection) {
+    conn.execute_batch(
+        "CREATE TABLE moz_keywords (
+            id INTEGER PRIMARY KEY AUTOINCREMENT, keyword TEXT UNIQUE,
+            place_id INTEGER, post_data TEXT);",
+    )
+    .unwrap();
+}
+
+/// Adds keyword to the place of the bookmark with `bookmark_id`.
+pub fn insert_keyword(conn: &Connection, keyword: &str, bookmark_id: i64, post_data: Option<&str>) {
+    conn.execute(
+        "insert into moz_keywords (keyword, place_id, post_data)
+        select ?1, fk, ?3 from moz_bookmarks where id = ?2",
+        params![keyword, bookmark_id, post_data],
+    )
+    .unwrap();
+}
+
+/// Inserts a visit of the place, returns id of the visit.
+pub fn insert_visit(
+    conn: &Connection,
+    place_id: i64,
+    from_visit: i64,
+    visit_date: i64,
+    visit_type: i64,
+) -> i64 {
+    conn.execute(
+        "insert into moz_historyvisits (from_visit, place_id, visit_date, visit_type, session)
+        values (?1, ?2, ?3, ?4, 0)",
+        params![from_visit, place_id, visit_date, visit_type],
+    )
+    .unwrap();
+
+    conn.last_insert_rowid()
+}
+
+/// Adds `count` links to the toolbar, in folders of 100 links each, with
+/// hosts shared between links. Used by stress tests and benchmarks.
+pub fn insert_many_links(conn: &Connection, count: usize) {
+    conn.execute_batch("begin").unwrap();
+    let mut id = conn
+        .query_row("select max(id) + 1 from moz_bookmarks", params![], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    let mut folder = 3;
+    for i in 0..count {
+        if i % 100 == 0 {
+            folder = id;
+            let title = format!("Folder {}", i / 100);
+            let guid = format!("folder{:06}", id);
+            insert_folder(conn, folder, Some(3), (i / 100) as i64, &title, &guid);
+            id += 1;
+        }
+        let url = format!("https://host{}.example.com/{}", i % 500, i);
+        insert_link(
+            conn,
+            id,
+            folder,
+            (i % 100) as i64,
+            &format!("Link {}", i),
+            &url,
+        );
+        id += 1;
+    }
+    conn.execute_batch("commit").unwrap();
+}
+
+/// Writes `count` small files into nested folders of the profile,
+/// like the cache and storage folders of a long used profile.
+pub fn create_many_files(profile_folder: &Path, count: usize) {
+    for i in 0..count {
+        let folder = profile_folder
+            .join("storage")
+            .join(format!("{:02}", i % 50));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(
+            folder.join(format!("{}.bin", i)),
+            vec![(i % 256) as u8; 512],
+        )
+        .unwrap();
+    }
+}
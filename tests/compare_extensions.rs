@@ -0,0 +1,103 @@
+use serde_json::json;
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use fftemplates::extensions;
+use fftemplates::extensions::InstalledExtension;
+
+fn write_extensions(profile: &Path, addons: &[(&str, &str, &str)]) {
+    let addons: Vec<_> = addons
+        .iter()
+        .map(|(id, version, location)| json!({"id": id, "version": version, "location": location}))
+        .collect();
+    fs::write(
+        profile.join(extensions::EXTENSIONS_JSON),
+        json!({ "schemaVersion": 35, "addons": addons }).to_string(),
+    )
+    .unwrap();
+}
+
+fn extension(id: &str, version: &str) -> InstalledExtension {
+    InstalledExtension {
+        id: id.to_string(),
+        version: version.to_string(),
+    }
+}
+
+#[test]
+fn compares_ids_and_versions() {
+    let a = TempDir::new().unwrap();
+    let b = TempDir::new().unwrap();
+    write_extensions(
+        a.path(),
+        &[
+            ("ublock@example.org", "1.2", "app-profile"),
+            ("only-a@example.org", "1.0", "app-profile"),
+            ("same@example.org", "3.0", "app-profile"),
+            // comes with firefox, differs with its version
+            ("builtin@mozilla.org", "1.0", "app-builtin"),
+        ],
+    );
+    write_extensions(
+        b.path(),
+        &[
+            ("same@example.org", "3.0", "app-profile"),
+            ("ublock@example.org", "1.3", "app-profile"),
+            ("only-b@example.org", "2.0", "app-profile"),
+        ],
+    );
+
+    let diff = extensions::compare_extensions(a.path(), b.path()).unwrap();
+
+    assert_eq!(diff.only_in_a, vec![extension("only-a@example.org", "1.0")]);
+    assert_eq!(diff.only_in_b, vec![extension("only-b@example.org", "2.0")]);
+    assert_eq!(
+        diff.different_versions,
+        vec![(
+            "ublock@example.org".to_string(),
+            "1.2".to_string(),
+            "1.3".to_string()
+        )]
+    );
+}
+
+#[test]
+fn profile_without_extensions_json_has_no_extensions() {
+    let a = TempDir::new().unwrap();
+    let b = TempDir::new().unwrap();
+    write_extensions(b.path(), &[("ublock@example.org", "1.2", "app-profile")]);
+
+    let diff = extensions::compare_extensions(a.path(), b.path()).unwrap();
+
+    assert!(diff.only_in_a.is_empty());
+    assert_eq!(diff.only_in_b, vec![extension("ublock@example.org", "1.2")]);
+    assert!(extensions::compare_extensions(a.path(), a.path())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn compare_extensions_prints_differences() {
+    let a = TempDir::new().unwrap();
+    let b = TempDir::new().unwrap();
+    write_extensions(a.path(), &[("ublock@example.org", "1.2", "app-profile")]);
+    write_extensions(b.path(), &[("ublock@example.org", "1.3", "app-profile")]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(a.path())
+        .arg("--compare-extensions")
+        .arg(b.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        stdout.contains("different versions :\n  ublock@example.org 1.2 -> 1.3"),
+        "{}",
+        stdout
+    );
+}
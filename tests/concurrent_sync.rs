@@ -0,0 +1,166 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::{EntryFilter, NewEntries};
+use fftemplates::cli::ConflictPolicy;
+use fftemplates::history;
+use fftemplates::history::NewHistory;
+
+// new entries of a session that added links with ids in the range
+fn session_entries(source: &TempDir, ids: std::ops::Range<i64>) -> NewEntries {
+    let conn = common::create_places(source.path());
+    let folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+    for (position, id) in ids.enumerate() {
+        let url = format!("https://example.com/{}", id);
+        common::insert_link(&conn, id, 3, position as i64, &id.to_string(), &url);
+    }
+
+    bookmarks::get_new_entries(folder, &latest, &EntryFilter::default()).unwrap()
+}
+
+// two sessions of the same base profile exiting at the same time
+#[test]
+fn concurrent_syncs_into_the_same_profile_take_turns() {
+    let target = TempDir::new().unwrap();
+    common::create_places(target.path());
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    let sessions = vec![
+        session_entries(&first, 7..207),
+        session_entries(&second, 207..407),
+    ];
+
+    let start = Arc::new(Barrier::new(sessions.len()));
+    let syncs: Vec<_> = sessions
+        .into_iter()
+        .map(|(new_bookmarks, new_places, new_origins)| {
+            let target = target.path().to_str().unwrap().to_string();
+            let start = Arc::clone(&start);
+            thread::spawn(move || {
+                start.wait();
+                bookmarks::insert_new_entries_into(
+                    &target,
+                    new_bookmarks.as_deref(),
+                    new_places.as_ref(),
+                    new_origins.as_ref(),
+                    None,
+                    ConflictPolicy::Skip,
+                )
+                .unwrap()
+                .inserted_bookmarks()
+            })
+        })
+        .collect();
+    let inserted: usize = syncs.into_iter().map(|sync| sync.join().unwrap()).sum();
+
+    assert_eq!(inserted, 400);
+    let conn = Connection::open(target.path().join("places.sqlite")).unwrap();
+    let mut statement = conn
+        .prepare(
+            "select b.guid, p.url from moz_bookmarks b join moz_places p on p.id = b.fk
+            order by b.guid",
+        )
+        .unwrap();
+    let links: Vec<(String, String)> = statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    let expected: Vec<(String, String)> = (7..407)
+        .map(|id| {
+            (
+                format!("bookmark{:04}", id),
+                format!("https://example.com/{}", id),
+            )
+        })
+        .collect();
+    assert_eq!(links, expected);
+    let origins: i64 = conn
+        .query_row("select count(*) from moz_origins", params![], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(origins, 1);
+}
+
+// new history of a session that visited links with ids in the range
+fn session_history(source: &TempDir, ids: std::ops::Range<i64>) -> NewHistory {
+    let conn = common::create_places(source.path());
+    let folder = source.path().to_str().unwrap();
+    let baseline = history::get_latest_visit_id(folder).unwrap();
+    for (position, id) in ids.enumerate() {
+        let url = format!("https://example.com/{}", id);
+        common::insert_link(&conn, id, 3, position as i64, &id.to_string(), &url);
+        let place_id: i64 = conn
+            .query_row(
+                "select fk from moz_bookmarks where id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        common::insert_visit(&conn, place_id, 0, id, 1);
+    }
+
+    history::get_new_history(folder, baseline).unwrap()
+}
+
+#[test]
+fn concurrent_history_syncs_into_the_same_profile_take_turns() {
+    let target = TempDir::new().unwrap();
+    common::create_places(target.path());
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    let sessions = vec![
+        session_history(&first, 7..207),
+        session_history(&second, 207..407),
+    ];
+
+    let start = Arc::new(Barrier::new(sessions.len()));
+    let syncs: Vec<_> = sessions
+        .into_iter()
+        .map(|(new_visits, new_places, new_origins)| {
+            let target = target.path().to_str().unwrap().to_string();
+            let start = Arc::clone(&start);
+            thread::spawn(move || {
+                start.wait();
+                history::insert_new_history(
+                    &target,
+                    &new_visits,
+                    new_places.as_ref(),
+                    new_origins.as_ref(),
+                )
+                .unwrap()
+                .inserted_visits()
+            })
+        })
+        .collect();
+    let inserted: usize = syncs.into_iter().map(|sync| sync.join().unwrap()).sum();
+
+    assert_eq!(inserted, 400);
+    let conn = Connection::open(target.path().join("places.sqlite")).unwrap();
+    let mut statement = conn
+        .prepare(
+            "select v.visit_date, p.url from moz_historyvisits v
+            join moz_places p on p.id = v.place_id
+            order by v.visit_date",
+        )
+        .unwrap();
+    let visits: Vec<(i64, String)> = statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    let expected: Vec<(i64, String)> = (7..407)
+        .map(|id| (id, format!("https://example.com/{}", id)))
+        .collect();
+    assert_eq!(visits, expected);
+}
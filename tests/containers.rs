@@ -0,0 +1,171 @@
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+
+use fftemplates::containers;
+
+fn identity(id: u64, name: &str) -> Value {
+    json!({
+        "userContextId": id,
+        "public": true,
+        "icon": "briefcase",
+        "color": "red",
+        "name": name,
+    })
+}
+
+// default personal container, known by its localization id
+fn personal() -> Value {
+    json!({
+        "userContextId": 1,
+        "public": true,
+        "icon": "fingerprint",
+        "color": "blue",
+        "l10nID": "userContextPersonal.label",
+    })
+}
+
+fn write_containers(profile: &Path, last_id: u64, identities: Vec<Value>) {
+    let containers = json!({
+        "version": 4,
+        "lastUserContextId": last_id,
+        "identities": identities,
+    });
+    fs::write(
+        profile.join("containers.json"),
+        serde_json::to_string(&containers).unwrap(),
+    )
+    .unwrap();
+}
+
+fn read_containers(profile: &Path) -> Value {
+    let content = fs::read_to_string(profile.join("containers.json")).unwrap();
+    serde_json::from_str(&content).unwrap()
+}
+
+// (id, name) of every container in the profile
+fn identities(profile: &Path) -> Vec<(u64, String)> {
+    read_containers(profile)["identities"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|identity| {
+            let name = identity["name"]
+                .as_str()
+                .or_else(|| identity["l10nID"].as_str())
+                .unwrap();
+            (
+                identity["userContextId"].as_u64().unwrap(),
+                name.to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn new_container_keeps_its_id() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(from.path(), 5, vec![personal(), identity(5, "Banking")]);
+    write_containers(to.path(), 1, vec![personal()]);
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 1);
+    assert_eq!(
+        identities(to.path()),
+        vec![
+            (1, "userContextPersonal.label".to_string()),
+            (5, "Banking".to_string())
+        ]
+    );
+    assert_eq!(read_containers(to.path())["lastUserContextId"], 5);
+}
+
+#[test]
+fn container_with_a_lower_id_keeps_the_last_id() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(from.path(), 3, vec![identity(3, "Shopping")]);
+    write_containers(to.path(), 8, vec![identity(8, "Work")]);
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 1);
+    assert_eq!(
+        identities(to.path()),
+        vec![(8, "Work".to_string()), (3, "Shopping".to_string())]
+    );
+    assert_eq!(read_containers(to.path())["lastUserContextId"], 8);
+}
+
+#[test]
+fn container_with_an_existing_name_is_skipped() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(from.path(), 6, vec![personal(), identity(6, "Work")]);
+    write_containers(to.path(), 4, vec![personal(), identity(4, "Work")]);
+    let before = fs::read(to.path().join("containers.json")).unwrap();
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 0);
+    assert_eq!(fs::read(to.path().join("containers.json")).unwrap(), before);
+}
+
+#[test]
+fn container_with_an_existing_id_is_not_renumbered() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(from.path(), 4, vec![identity(4, "Banking")]);
+    write_containers(to.path(), 4, vec![identity(4, "Work")]);
+    let before = fs::read(to.path().join("containers.json")).unwrap();
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 0);
+    assert_eq!(fs::read(to.path().join("containers.json")).unwrap(), before);
+}
+
+#[test]
+fn renamed_container_is_not_added_twice() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(
+        from.path(),
+        4,
+        vec![
+            personal(),
+            identity(4, "Work projects"),
+            identity(5, "Travel"),
+        ],
+    );
+    write_containers(to.path(), 4, vec![personal(), identity(4, "Work")]);
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 1);
+    assert_eq!(
+        identities(to.path()),
+        vec![
+            (1, "userContextPersonal.label".to_string()),
+            (4, "Work".to_string()),
+            (5, "Travel".to_string())
+        ]
+    );
+    assert_eq!(read_containers(to.path())["lastUserContextId"], 5);
+}
+
+#[test]
+fn missing_target_file_is_copied() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    write_containers(from.path(), 5, vec![personal(), identity(5, "Banking")]);
+
+    let added = containers::sync_containers(from.path(), to.path()).unwrap();
+
+    assert_eq!(added, 2);
+    assert_eq!(read_containers(to.path()), read_containers(from.path()));
+}
@@ -0,0 +1,87 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::PathBuf;
+
+use fftemplates::profiles;
+use fftemplates::profiles::DiscoveredProfile;
+
+#[test]
+fn discovers_profiles_of_every_root() {
+    let firefox = TempDir::new().unwrap();
+    let librewolf = TempDir::new().unwrap();
+    fs::create_dir(firefox.path().join("abc123.default")).unwrap();
+    fs::create_dir(firefox.path().join("def456.work")).unwrap();
+    fs::write(firefox.path().join("installs.ini"), "").unwrap();
+    fs::create_dir(librewolf.path().join("xyz789.default")).unwrap();
+    fs::write(
+        librewolf.path().join("profiles.ini"),
+        "[Profile0]\nName=default\nIsRelative=1\nPath=xyz789.default\n",
+    )
+    .unwrap();
+    let roots = vec![
+        ("firefox".to_string(), firefox.path().to_path_buf()),
+        ("librewolf".to_string(), librewolf.path().to_path_buf()),
+        (
+            "waterfox".to_string(),
+            PathBuf::from("/nonexistent/waterfox"),
+        ),
+    ];
+
+    let discovered = profiles::discover_profiles(&roots);
+
+    let profile = |browser: &str, name: &str, path: PathBuf| DiscoveredProfile {
+        browser: browser.to_string(),
+        name: name.to_string(),
+        path,
+    };
+    assert_eq!(
+        discovered,
+        vec![
+            profile("firefox", "default", firefox.path().join("abc123.default")),
+            profile("firefox", "work", firefox.path().join("def456.work")),
+            profile(
+                "librewolf",
+                "default",
+                librewolf.path().join("xyz789.default")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn picks_profile_by_number() {
+    let candidates: Vec<_> = ["default", "work"]
+        .iter()
+        .map(|name| DiscoveredProfile {
+            browser: "firefox".to_string(),
+            name: name.to_string(),
+            path: PathBuf::from(format!("/profiles/abc.{}", name)),
+        })
+        .collect();
+
+    let mut output = vec![];
+    // invalid answers are asked again
+    let picked = profiles::pick_profile(
+        &candidates,
+        "default",
+        &mut &b"3\nwork\n2\n"[..],
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(picked.as_ref(), Some(&candidates[1]));
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains("  2) firefox work (/profiles/abc.work)"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("`3` is not a number from the list"),
+        "{}",
+        output
+    );
+
+    let picked = profiles::pick_profile(&candidates, "default", &mut &b"\n"[..], &mut vec![]);
+    assert_eq!(picked.unwrap(), None);
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::{EntryFilter, NewEntries};
+use fftemplates::cli::ConflictPolicy;
+
+// every bookmark of the source, as merging takes them
+fn all_entries(source: &TempDir) -> NewEntries {
+    bookmarks::get_all_entries(source.path().to_str().unwrap(), &EntryFilter::default()).unwrap()
+}
+
+fn sync_sql(source: &TempDir, target: &TempDir) -> Vec<String> {
+    let (new_bookmarks, new_places, new_origins) = all_entries(source);
+    bookmarks::new_entries_sql(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+        None,
+        ConflictPolicy::Skip,
+    )
+    .unwrap()
+}
+
+fn links(conn: &Connection) -> Vec<(i64, String, String)> {
+    let mut statement = conn
+        .prepare(
+            "select b.id, b.title, p.url from moz_bookmarks b join moz_places p on p.id = b.fk
+            order by b.id",
+        )
+        .unwrap();
+    statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+}
+
+#[test]
+fn statements_are_printed_instead_of_run() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    common::insert_link(
+        &source_conn,
+        9,
+        3,
+        0,
+        "Rust's book",
+        "https://rust-lang.org/",
+    );
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let statements = sync_sql(&source, &target);
+
+    assert_eq!(
+        statements[0],
+        "insert into moz_origins (id, prefix, host, frecency) values(2, 'https://', 'rust-lang.org', 1);"
+    );
+    // id is remapped after the rows of the target, parent by its guid,
+    // and it goes after the link the toolbar already has
+    assert!(statements
+        .iter()
+        .any(|s| s.starts_with("insert into moz_bookmarks")
+            && s.contains("values( 8, 1, 2, 3, 1, 'Rust''s book',")));
+    assert!(statements
+        .iter()
+        .any(|s| s.starts_with("update moz_places set foreign_count")));
+    assert_eq!(links(&target_conn).len(), 1);
+}
+
+#[test]
+fn printed_statements_sync_the_same_as_running_them() {
+    let source = TempDir::new().unwrap();
+    let printed = TempDir::new().unwrap();
+    let synced = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    common::insert_folder(&source_conn, 7, Some(3), 0, "Work", "workfolder01");
+    common::insert_link(&source_conn, 8, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&source_conn, 9, 2, 0, "Rust", "https://rust-lang.org/");
+    for target in [&printed, &synced] {
+        let conn = common::create_places(target.path());
+        common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+    }
+
+    let statements = sync_sql(&source, &printed);
+    let printed_conn = Connection::open(printed.path().join("places.sqlite")).unwrap();
+    printed_conn.execute_batch(&statements.join("\n")).unwrap();
+    let (new_bookmarks, new_places, new_origins) = all_entries(&source);
+    bookmarks::insert_new_entries(
+        synced.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+
+    let synced_conn = Connection::open(synced.path().join("places.sqlite")).unwrap();
+    assert_eq!(links(&printed_conn).len(), 3);
+    assert_eq!(links(&printed_conn), links(&synced_conn));
+}
+
+#[test]
+fn merge_with_dry_run_sql_leaves_destination_alone() {
+    let root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let source = root.path().join("abc123.source");
+    let dest = root.path().join("def456.dest");
+    fs::create_dir(&source).unwrap();
+    fs::create_dir(&dest).unwrap();
+    let source_conn = common::create_places(&source);
+    common::insert_link(&source_conn, 7, 3, 0, "Rust", "https://rust-lang.org/");
+    common::create_places(&dest);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-root")
+        .arg(root.path())
+        .arg("--merge-bookmarks")
+        .arg("source")
+        .arg("dest")
+        .arg("--dry-run-sql")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.starts_with("begin transaction;\n"), "{}", stdout);
+    assert!(stdout.contains("'https://rust-lang.org/'"), "{}", stdout);
+    assert!(stdout.ends_with("commit;\n"), "{}", stdout);
+    let dest_conn = Connection::open(dest.join("places.sqlite")).unwrap();
+    assert!(links(&dest_conn).is_empty());
+    // nothing is written, so nothing is backed up either
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+}
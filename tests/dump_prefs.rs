@@ -0,0 +1,67 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn dump_prefs_prints_adjusted_prefs_without_launching() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let prefs =
+        "user_pref(\"places.history.enabled\", false);\nuser_pref(\"browser.startup.page\", 1);\n";
+    fs::write(profile.path().join("prefs.js"), prefs).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        // fails the run if it was launched
+        .arg("false")
+        .arg("--dump-prefs")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- user_pref(\"places.history.enabled\", false);"));
+    assert!(stdout.contains("+ user_pref(\"places.history.enabled\", true);"));
+    // set by the user and no session is loaded, so it is kept
+    assert!(!stdout.contains("browser.startup.page"));
+    // session isn't saved, so sanitizing on shutdown is left as it is
+    assert!(!stdout.contains("sanitizeOnShutdown"));
+    assert_eq!(
+        fs::read_to_string(profile.path().join("prefs.js")).unwrap(),
+        prefs
+    );
+}
+
+#[test]
+fn session_prefs_can_be_left_unchanged() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(
+        profile.path().join("prefs.js"),
+        "user_pref(\"places.history.enabled\", false);\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("false")
+        .arg("--dump-prefs")
+        .arg("--no-session-pref-changes")
+        .arg("-s")
+        .arg(config.path().join("session.jsonlz4"))
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "No prefs were adjusted\n"
+    );
+}
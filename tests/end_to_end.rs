@@ -0,0 +1,148 @@
+#![cfg(all(unix, feature = "bookmarks"))]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use serde_json::json;
+use tempfile::TempDir;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fftemplates::session;
+
+const PROFILES_INI: &str = "
+[Profile0]
+Name=work
+IsRelative=1
+Path=abcd1234.work
+
+[General]
+StartWithLastProfile=1
+Version=2
+";
+
+// profile registered in profiles.ini, with every file the launch touches
+fn create_profile(root: &Path) -> PathBuf {
+    fs::write(root.join("profiles.ini"), PROFILES_INI).unwrap();
+    let profile = root.join("abcd1234.work");
+    fs::create_dir(&profile).unwrap();
+    fs::write(
+        profile.join("prefs.js"),
+        "user_pref(\"places.history.enabled\", false);\n",
+    )
+    .unwrap();
+    fs::write(
+        profile.join("extensions.json"),
+        r#"{"addons":[{"path":"/old/profile/extensions/a@b.xpi"}]}"#,
+    )
+    .unwrap();
+    let conn = common::create_places(&profile);
+    common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+    profile
+}
+
+fn write_session(path: &Path, url: &str) {
+    let json = json!({"windows": [{"tabs": [{"entries": [{"url": url}], "index": 1}]}]});
+    fs::write(
+        path,
+        session::compress_session(&serde_json::to_vec(&json).unwrap()),
+    )
+    .unwrap();
+}
+
+// browser that tells which profile it got and runs until told to exit
+fn fake_browser(dir: &Path) -> PathBuf {
+    let browser = dir.join("firefox");
+    fs::write(
+        &browser,
+        format!(
+            "#!/bin/sh\necho \"$2\" > {0}/launched\nwhile [ ! -e {0}/exit ]; do sleep 0.1; done\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    browser
+}
+
+fn wait_for_launch(dir: &Path) -> PathBuf {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Ok(path) = fs::read_to_string(dir.join("launched")) {
+            if !path.trim().is_empty() {
+                return PathBuf::from(path.trim());
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("browser wasn't launched");
+}
+
+// whole launch of a profile found in profiles.ini: copy, prefs, extensions,
+// session, and bookmarks added while the browser runs synced back on exit
+#[test]
+fn launch_prepares_profile_and_syncs_back() {
+    let root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let profile = create_profile(root.path());
+    let session_file = config.path().join("work.jsonlz4");
+    write_session(&session_file, "https://example.com/session");
+
+    let child = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("work")
+        .arg("--profile-root")
+        .arg(root.path())
+        .arg("--browser")
+        .arg(fake_browser(config.path()))
+        .arg("-b")
+        .arg("-l")
+        .arg(&session_file)
+        .env("XDG_CONFIG_HOME", config.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let copy = wait_for_launch(config.path());
+
+    assert!(copy.starts_with(std::env::temp_dir()));
+    assert!(copy.join("places.sqlite").exists());
+    let prefs = fs::read_to_string(copy.join("prefs.js")).unwrap();
+    assert!(prefs.contains("user_pref(\"places.history.enabled\", true);"));
+    assert!(prefs.contains("user_pref(\"browser.startup.page\", 3);"));
+    let extensions = fs::read_to_string(copy.join("extensions.json")).unwrap();
+    assert!(extensions.contains(&copy.join("extensions/a@b.xpi").display().to_string()));
+    assert_eq!(
+        session::read_session_urls(copy.join(session::SESSIONSTORE_DEFAULT_NAME)).unwrap(),
+        vec![vec!["https://example.com/session".to_string()]]
+    );
+
+    let conn = Connection::open(copy.join("places.sqlite")).unwrap();
+    common::insert_link(&conn, 8, 2, 0, "Rust", "https://rust-lang.org/");
+    drop(conn);
+    fs::write(config.path().join("exit"), "").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Synced 1 bookmarks"));
+
+    let conn = Connection::open(profile.join("places.sqlite")).unwrap();
+    let (parent, url): (i64, String) = conn
+        .query_row(
+            "select b.parent, p.url from moz_bookmarks b join moz_places p on p.id = b.fk
+            where b.guid = 'bookmark0008'",
+            params![],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(parent, 2);
+    assert_eq!(url, "https://rust-lang.org/");
+    // base profile keeps its own files, the copy is removed
+    assert!(fs::read_to_string(profile.join("prefs.js"))
+        .unwrap()
+        .contains("false"));
+    assert!(!copy.exists());
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::params;
+use tempfile::TempDir;
+
+use fftemplates::error;
+use fftemplates::error::FfError;
+
+#[test]
+fn keeps_sqlite_error_code_in_chain() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+
+    let e = conn
+        .execute(
+            "insert into moz_bookmarks (id, type, parent) values (1, 2, null)",
+            params![],
+        )
+        .unwrap_err();
+    let e = FfError::context("Error during insert new bookmarks", e);
+
+    assert_eq!(e.to_string(), "Error during insert new bookmarks");
+    assert_eq!(
+        error::display_chain(&e),
+        "Error during insert new bookmarks : UNIQUE constraint failed: moz_bookmarks.id \
+         : Error code 1555: A PRIMARY KEY constraint failed"
+    );
+}
@@ -0,0 +1,58 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// runs without launching, returns folder of the kept temp profile
+fn prepare_copy(profile: &TempDir, extra_args: &[&str]) -> PathBuf {
+    let config = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--print-command")
+        .args(extra_args)
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    PathBuf::from(stdout.trim().rsplit(' ').next().unwrap())
+}
+
+fn create_profile() -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    fs::write(
+        profile.path().join("extensions.json"),
+        r#"{"addons":[{"path":"/old/profile/extensions/a@b.xpi"}]}"#,
+    )
+    .unwrap();
+    profile
+}
+
+#[test]
+fn rewrites_extensions_json_by_default() {
+    let profile = create_profile();
+
+    let copy = prepare_copy(&profile, &[]);
+
+    let content = fs::read_to_string(copy.join("extensions.json")).unwrap();
+    assert!(content.contains(&copy.join("extensions/a@b.xpi").display().to_string()));
+    fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn removes_extensions_json_without_rewrite() {
+    let profile = create_profile();
+
+    let copy = prepare_copy(&profile, &["--no-extension-rewrite"]);
+
+    assert!(copy.join("prefs.js").exists());
+    assert!(!copy.join("extensions.json").exists());
+    // base profile keeps its file
+    assert!(profile.path().join("extensions.json").exists());
+    fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,125 @@
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use std::fs;
+use std::time::Duration;
+use std::time::Instant;
+
+use fftemplates::extensions;
+
+#[test]
+fn rewrites_paths_into_profile() {
+    let profile = TempDir::new().unwrap();
+    let file = profile.path().join(extensions::EXTENSIONS_JSON);
+    fs::write(
+        &file,
+        r#"{"addons":[{"path":"/home/user/.mozilla/firefox/abc.default/extensions/ublock@example.org.xpi"}]}"#,
+    )
+    .unwrap();
+
+    assert!(extensions::adjust_extensions_json(&file).unwrap());
+
+    let expected = profile.path().join("extensions/ublock@example.org.xpi");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        format!(r#"{{"addons":[{{"path":"{}"}}]}}"#, expected.display())
+    );
+}
+
+#[test]
+fn round_trip_keeps_other_fields() {
+    let profile = TempDir::new().unwrap();
+    let file = profile.path().join(extensions::EXTENSIONS_JSON);
+    let original = json!({
+        "schemaVersion": 35,
+        "addons": [
+            {
+                "id": "foo@bar",
+                "version": "1.2.0",
+                "location": "app-profile",
+                "path": "/old/profile/extensions/foo@bar.xpi",
+                "rootURI": "jar:file:///old/profile/extensions/foo@bar.xpi!/",
+                "sourceURI": "https://addons.example.org/foo.xpi",
+                "active": true
+            },
+            {
+                "id": "{3550f703-e582-4d05-9a08-453d09bdfdc6}",
+                "version": "4.0",
+                "location": "app-profile",
+                "path": "C:\\Users\\me\\AppData\\Roaming\\Mozilla\\Firefox\\Profiles\\abc.default\\extensions\\{3550f703-e582-4d05-9a08-453d09bdfdc6}.xpi",
+                "active": false
+            },
+            {
+                "id": "screenshots@mozilla.org",
+                "version": "39.0.1",
+                "location": "app-builtin",
+                "path": "/usr/lib/firefox/browser/features/screenshots@mozilla.org.xpi",
+                "active": true
+            }
+        ]
+    });
+    // firefox writes the file without any whitespace
+    fs::write(&file, serde_json::to_string(&original).unwrap()).unwrap();
+
+    assert!(extensions::adjust_extensions_json(&file).unwrap());
+
+    let adjusted: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    let addons = adjusted["addons"].as_array().unwrap();
+    assert_eq!(addons.len(), 3);
+    assert_eq!(
+        addons[0]["path"],
+        profile
+            .path()
+            .join("extensions/foo@bar.xpi")
+            .display()
+            .to_string()
+    );
+    // separators of the old system stay, only the profile part is replaced
+    let windows_path = addons[1]["path"].as_str().unwrap();
+    assert!(
+        windows_path.starts_with(&profile.path().display().to_string()),
+        "{}",
+        windows_path
+    );
+    assert!(windows_path.ends_with("extensions\\{3550f703-e582-4d05-9a08-453d09bdfdc6}.xpi"));
+    // outside of an extensions folder, not a profile path
+    assert_eq!(addons[2]["path"], original["addons"][2]["path"]);
+    for (adjusted, original) in addons.iter().zip(original["addons"].as_array().unwrap()) {
+        for (key, value) in original.as_object().unwrap() {
+            if key != "path" {
+                assert_eq!(&adjusted[key], value, "{} changed", key);
+            }
+        }
+    }
+    assert_eq!(adjusted["schemaVersion"], 35);
+}
+
+#[test]
+fn large_file_completes_quickly() {
+    let profile = TempDir::new().unwrap();
+    let file = profile.path().join(extensions::EXTENSIONS_JSON);
+    // long values that almost match, the worst case for a backtracking regex
+    let addon = format!(r#"{{"path":"{}extensions"}},"#, "/a".repeat(1000));
+    let content = format!(r#"{{"addons":[{}{{}}]}}"#, addon.repeat(4000));
+    fs::write(&file, &content).unwrap();
+
+    let start = Instant::now();
+    assert!(extensions::adjust_extensions_json(&file).unwrap());
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+}
+
+#[test]
+fn oversized_file_is_left_unmodified() {
+    let profile = TempDir::new().unwrap();
+    let file = profile.path().join(extensions::EXTENSIONS_JSON);
+    let addon = r#"{"path":"/old/extensions/a.xpi"},"#;
+    let count = extensions::MAX_EXTENSIONS_JSON_SIZE as usize / addon.len() + 1;
+    let content = addon.repeat(count);
+    fs::write(&file, &content).unwrap();
+
+    assert!(!extensions::adjust_extensions_json(&file).unwrap());
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+#[test]
+fn synced_bookmark_is_found_by_guid() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    common::insert_link(&source_conn, 8, 3, 0, "Docs", "https://example.com/docs");
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    bookmarks::insert_new_entries(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+
+    let bookmark = bookmarks::get_bookmark_by_guid(&target_conn, "bookmark0008")
+        .unwrap()
+        .unwrap();
+    assert_eq!(bookmark.title.as_deref(), Some("Docs"));
+    assert_eq!(
+        bookmark.parent_guid.as_deref(),
+        Some(bookmarks::TOOLBAR_GUID)
+    );
+
+    let place = bookmarks::get_place_by_guid(&target_conn, "place0000008")
+        .unwrap()
+        .unwrap();
+    assert_eq!(bookmark.fk, Some(place.id));
+    assert_eq!(place.url.as_deref(), Some("https://example.com/docs"));
+
+    assert_eq!(
+        bookmarks::get_bookmark_by_guid(&target_conn, "missing_guid").unwrap(),
+        None
+    );
+    assert_eq!(
+        bookmarks::get_place_by_guid(&target_conn, "missing_guid").unwrap(),
+        None
+    );
+}
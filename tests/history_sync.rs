@@ -0,0 +1,163 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::history;
+
+// link and reload visit types
+const LINK: i64 = 1;
+const RELOAD: i64 = 9;
+
+fn place_id(conn: &Connection, url: &str) -> i64 {
+    conn.query_row(
+        "select id from moz_places where url = ?1",
+        params![url],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn syncs_visits_with_remapped_places() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    // target has an unrelated place first, so ids of the same place differ
+    common::insert_link(&target_conn, 8, 3, 0, "Other", "https://other.com/");
+    common::insert_link(&target_conn, 7, 3, 1, "Home", "https://example.com/");
+    common::insert_visit(
+        &target_conn,
+        place_id(&target_conn, "https://other.com/"),
+        0,
+        10,
+        LINK,
+    );
+    common::insert_link(&source_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let source_folder = source.path().to_str().unwrap();
+    let baseline = history::get_latest_visit_id(source_folder).unwrap();
+    assert_eq!(baseline, None);
+
+    let home = place_id(&source_conn, "https://example.com/");
+    let first = common::insert_visit(&source_conn, home, 0, 100, LINK);
+    common::insert_visit(&source_conn, home, 0, 300, RELOAD);
+    common::insert_link(&source_conn, 9, 3, 1, "Docs", "https://example.com/docs");
+    let docs = place_id(&source_conn, "https://example.com/docs");
+    common::insert_visit(&source_conn, docs, first, 200, LINK);
+
+    let (visits, places, origins) = history::get_new_history(source_folder, baseline).unwrap();
+    assert_eq!(visits.len(), 3);
+    let plan = history::insert_new_history(
+        target.path().to_str().unwrap(),
+        &visits,
+        places.as_ref(),
+        origins.as_ref(),
+    )
+    .unwrap();
+    assert_eq!(plan.inserted_visits(), 3);
+    assert_eq!(plan.inserted_places(), 1);
+    assert_eq!(plan.reused_places(), 1);
+
+    let target_home = place_id(&target_conn, "https://example.com/");
+    let target_docs = place_id(&target_conn, "https://example.com/docs");
+    let mut statement = target_conn
+        .prepare("select id, from_visit, place_id from moz_historyvisits order by id")
+        .unwrap();
+    let rows: Vec<(i64, i64, i64)> = statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (1, 0, place_id(&target_conn, "https://other.com/")),
+            (2, 0, target_home),
+            (3, 0, target_home),
+            // first synced visit got id 2 in the target
+            (4, 2, target_docs),
+        ]
+    );
+
+    // reload is not counted, same as firefox does it
+    let (visit_count, last_visit_date): (i64, i64) = target_conn
+        .query_row(
+            "select visit_count, last_visit_date from moz_places where id = ?1",
+            params![target_home],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((visit_count, last_visit_date), (1, 100));
+}
+
+#[test]
+fn only_visits_after_baseline_are_new() {
+    let source = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    common::insert_link(&source_conn, 7, 3, 0, "Home", "https://example.com/");
+    let home = place_id(&source_conn, "https://example.com/");
+    common::insert_visit(&source_conn, home, 0, 100, LINK);
+
+    let source_folder = source.path().to_str().unwrap();
+    let baseline = history::get_latest_visit_id(source_folder).unwrap();
+    assert_eq!(baseline, Some(1));
+    let (visits, _, _) = history::get_new_history(source_folder, baseline).unwrap();
+    assert!(visits.is_empty());
+
+    common::insert_visit(&source_conn, home, 1, 200, LINK);
+    let (visits, places, _) = history::get_new_history(source_folder, baseline).unwrap();
+    assert_eq!(visits.len(), 1);
+    assert_eq!(visits[0].visit_date, 200);
+    assert!(places.unwrap().contains_key(&home));
+}
+
+#[test]
+fn failed_sync_leaves_the_history_unchanged() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+    common::insert_link(&source_conn, 7, 3, 0, "Home", "https://example.com/");
+    let source_folder = source.path().to_str().unwrap();
+    let baseline = history::get_latest_visit_id(source_folder).unwrap();
+    let home = place_id(&source_conn, "https://example.com/");
+    common::insert_visit(&source_conn, home, 0, 100, LINK);
+    common::insert_visit(&source_conn, home, 0, 200, LINK);
+    // second visit fails to be written, after the first one was
+    target_conn
+        .execute_batch(
+            "create trigger fail_second_visit before insert on moz_historyvisits
+            when new.visit_date = 200
+            begin select raise(abort, 'visit rejected'); end;",
+        )
+        .unwrap();
+
+    let (visits, places, origins) = history::get_new_history(source_folder, baseline).unwrap();
+    let result = history::insert_new_history(
+        target.path().to_str().unwrap(),
+        &visits,
+        places.as_ref(),
+        origins.as_ref(),
+    );
+
+    assert!(result.is_err());
+    let visits: i64 = target_conn
+        .query_row("select count(*) from moz_historyvisits", params![], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(visits, 0);
+    let visit_count: Option<i64> = target_conn
+        .query_row(
+            "select visit_count from moz_places where url = 'https://example.com/'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(visit_count.unwrap_or(0), 0);
+}
@@ -0,0 +1,117 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use fftemplates::cli::into_config;
+use fftemplates::cli::Args;
+use fftemplates::config_file;
+
+const PROFILES_INI: &str = "
+[Profile0]
+Name=personal
+IsRelative=1
+Path=abcd1234.personal
+
+[Profile1]
+Name=work
+IsRelative=1
+Path=efgh5678.work
+Default=1
+";
+
+fn init_config(root: &Path, config: &Path, extra_args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--init-config")
+        .arg("--profile-root")
+        .arg(root)
+        .arg("--browser")
+        .arg("true")
+        .args(extra_args)
+        .env("XDG_CONFIG_HOME", config)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn default_config_is_a_valid_template() {
+    let browser = vec!["/usr/bin/firefox".to_string()];
+    let content = config_file::default_config("work", Some(&browser));
+
+    let file_config = config_file::parse_file_config(&content).unwrap();
+    let settings = config_file::resolve_template("work", &file_config);
+    assert_eq!(settings.profile_name, "work");
+    assert_eq!(settings.browser.as_deref(), Some("/usr/bin/firefox"));
+    assert_eq!(settings.ignore, config_file::DEFAULT_TEMPLATE_IGNORE);
+    assert!(settings.extra_args.is_empty());
+    assert!(settings.session.is_none());
+}
+
+#[test]
+fn default_config_quotes_profile_names() {
+    let flatpak: Vec<_> = ["flatpak", "run", "org.mozilla.firefox"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let content = config_file::default_config("dev edition.1", Some(&flatpak));
+
+    let file_config = config_file::parse_file_config(&content).unwrap();
+    let settings = config_file::resolve_template("dev edition.1", &file_config);
+    assert_eq!(settings.profile_name, "dev edition.1");
+    // found on every run instead
+    assert!(settings.browser.is_none());
+    assert!(content.contains("flatpak run org.mozilla.firefox"));
+}
+
+#[test]
+fn template_browser_is_used_unless_given() {
+    let file_config =
+        config_file::parse_file_config("[template.dev]\nbrowser = \"/opt/librewolf/librewolf\"\n")
+            .unwrap();
+    let settings = config_file::resolve_template("dev", &file_config);
+
+    let mut config = into_config(Args {
+        base_profile: Some("dev".to_string()),
+        ..Args::default()
+    })
+    .unwrap();
+    config_file::apply_template(&mut config, settings.clone());
+    assert_eq!(config.browser_binary, "/opt/librewolf/librewolf");
+    assert_eq!(config.browser_name.as_deref(), Some("librewolf"));
+
+    let mut config = into_config(Args {
+        base_profile: Some("dev".to_string()),
+        browser: Some("firefox".to_string()),
+        ..Args::default()
+    })
+    .unwrap();
+    config_file::apply_template(&mut config, settings);
+    assert_eq!(config.browser_binary, "firefox");
+}
+
+#[test]
+fn init_config_writes_detected_profile_and_keeps_existing_config() {
+    let root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(root.path().join("profiles.ini"), PROFILES_INI).unwrap();
+    let path = config.path().join("fftemplates").join("config.toml");
+
+    let output = init_config(root.path(), config.path(), &[]);
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&path.display().to_string()));
+    let file_config = config_file::read_file_config(&path).unwrap();
+    let settings = config_file::resolve_template("work", &file_config);
+    assert_eq!(settings.profile_name, "work");
+    assert!(settings.browser.unwrap().ends_with("true"));
+
+    fs::write(&path, "# edited\n").unwrap();
+    let output = init_config(root.path(), config.path(), &[]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already exists"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# edited\n");
+
+    init_config(root.path(), config.path(), &["--force"]);
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains("[template.work]"));
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+const SEARCH_URL: &str = "https://search.example.com/find?lang=en&q=%s";
+const POST_DATA: &str = "q=%s&safe=off";
+
+fn sync(source: &TempDir, target: &TempDir, latest: &bookmarks::Bookmark) {
+    let (new_bookmarks, new_places, new_origins) = bookmarks::get_new_entries(
+        source.path().to_str().unwrap(),
+        latest,
+        &EntryFilter::default(),
+    )
+    .unwrap();
+    bookmarks::insert_new_entries(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+}
+
+// url and post data firefox sends for the keyword followed by `terms`
+fn resolve_keyword(conn: &Connection, keyword: &str, terms: &str) -> (String, Option<String>) {
+    let (url, post_data): (String, Option<String>) = conn
+        .query_row(
+            "select p.url, k.post_data from moz_keywords k join moz_places p on p.id = k.place_id
+            where k.keyword = ?1",
+            params![keyword],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+
+    (
+        url.replace("%s", terms),
+        post_data.map(|data| data.replace("%s", terms)),
+    )
+}
+
+// source gets a search bookmark with a keyword posting the form after the baseline,
+// it refers to the keyword by id as well, like older firefox versions did
+fn profiles(target_keywords: bool) -> (TempDir, TempDir, bookmarks::Bookmark) {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    common::create_keywords(&source_conn);
+    let target_conn = common::create_places(target.path());
+    if target_keywords {
+        common::create_keywords(&target_conn);
+    }
+    let latest = bookmarks::get_latest_bookmark(source.path().to_str().unwrap())
+        .unwrap()
+        .unwrap();
+    common::insert_link(&source_conn, 7, 3, 0, "Search", SEARCH_URL);
+    common::insert_keyword(&source_conn, "ex", 7, Some(POST_DATA));
+    source_conn
+        .execute(
+            "update moz_bookmarks set keyword_id = 40 where id = 7",
+            params![],
+        )
+        .unwrap();
+
+    (source, target, latest)
+}
+
+#[test]
+fn post_keyword_search_works_in_the_target() {
+    let (source, target, latest) = profiles(true);
+
+    sync(&source, &target, &latest);
+
+    let conn = Connection::open(target.path().join("places.sqlite")).unwrap();
+    assert_eq!(
+        resolve_keyword(&conn, "ex", "rust"),
+        (
+            "https://search.example.com/find?lang=en&q=rust".to_string(),
+            Some("q=rust&safe=off".to_string())
+        )
+    );
+    // template is kept as it is, and the keyword counts as a reference
+    let (url, foreign_count, keyword_id): (String, i64, Option<i64>) = conn
+        .query_row(
+            "select p.url, p.foreign_count, b.keyword_id
+            from moz_bookmarks b join moz_places p on p.id = b.fk
+            where b.guid = 'bookmark0007'",
+            params![],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(url, SEARCH_URL);
+    assert_eq!(foreign_count, 2);
+    // id of the source keyword isn't one of the target
+    assert_eq!(keyword_id, None);
+
+    // already there, so syncing again adds nothing
+    sync(&source, &target, &latest);
+    let keywords: i64 = conn
+        .query_row("select count(*) from moz_keywords", params![], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(keywords, 1);
+}
+
+#[test]
+fn keyword_of_another_page_in_the_target_is_kept() {
+    let (source, target, latest) = profiles(true);
+    let conn = Connection::open(target.path().join("places.sqlite")).unwrap();
+    common::insert_link(&conn, 20, 2, 0, "Other", "https://other.example.com/?q=%s");
+    common::insert_keyword(&conn, "ex", 20, None);
+
+    sync(&source, &target, &latest);
+
+    assert_eq!(
+        resolve_keyword(&conn, "ex", "rust"),
+        ("https://other.example.com/?q=rust".to_string(), None)
+    );
+    // bookmark is synced without it
+    let synced: i64 = conn
+        .query_row(
+            "select count(*) from moz_bookmarks where guid = 'bookmark0007'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(synced, 1);
+}
+
+#[test]
+fn keywords_are_skipped_without_a_keywords_table_in_the_target() {
+    let (source, target, latest) = profiles(false);
+
+    sync(&source, &target, &latest);
+
+    let conn = Connection::open(target.path().join("places.sqlite")).unwrap();
+    let (synced, foreign_count): (i64, i64) = conn
+        .query_row(
+            "select count(*), max(p.foreign_count)
+            from moz_bookmarks b join moz_places p on p.id = b.fk
+            where b.guid = 'bookmark0007'",
+            params![],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((synced, foreign_count), (1, 1));
+}
@@ -0,0 +1,60 @@
+#![cfg(unix)]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// timeout given to fftemplates and how long it waits after SIGTERM
+const TIMEOUT: Duration = Duration::from_secs(1);
+const TERMINATE_GRACE: Duration = Duration::from_secs(10);
+
+// browser that ignores SIGTERM and keeps running until it is killed,
+// exec so no child is left holding stdout after that
+fn stubborn_browser(dir: &Path) -> PathBuf {
+    let browser = dir.join("firefox");
+    fs::write(&browser, "#!/bin/sh\ntrap '' TERM\nexec sleep 60\n").unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    browser
+}
+
+// single test, a script written while another test spawns a process
+// can fail to start with "text file busy"
+#[test]
+fn browser_ignoring_sigterm_is_killed_after_the_grace_period() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg(stubborn_browser(config.path()))
+        .arg("--timeout")
+        .arg(TIMEOUT.as_secs().to_string())
+        .env("XDG_CONFIG_HOME", config.path())
+        .env("XDG_CACHE_HOME", config.path())
+        .output()
+        .unwrap();
+    let elapsed = started.elapsed();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Firefox was closed after reaching the timeout"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("didn't exit cleanly"), "{}", stdout);
+    assert!(elapsed >= TIMEOUT + TERMINATE_GRACE, "{:?}", elapsed);
+    // leaves room for draining stderr and a slow machine
+    assert!(
+        elapsed < TIMEOUT + TERMINATE_GRACE + Duration::from_secs(5),
+        "{:?}",
+        elapsed
+    );
+}
@@ -0,0 +1,98 @@
+use serde_json::json;
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::extensions;
+use fftemplates::extensions::ExtensionInfo;
+
+fn write_addons(profile: &TempDir, addons: serde_json::Value) {
+    fs::write(
+        profile.path().join(extensions::EXTENSIONS_JSON),
+        json!({ "schemaVersion": 35, "addons": addons }).to_string(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn lists_extensions_of_every_schema() {
+    let profile = TempDir::new().unwrap();
+    write_addons(
+        &profile,
+        json!([
+            {
+                "id": "ublock@example.org",
+                "version": "1.2",
+                "location": "app-profile",
+                "active": true,
+                "defaultLocale": {"name": "uBlock"}
+            },
+            // older files have the name and only userDisabled
+            {"id": "old@example.org", "version": "0.9", "name": "Old", "userDisabled": true},
+            {"id": "bare@example.org"},
+            {"id": "builtin@mozilla.org", "version": "1.0", "location": "app-builtin"},
+            {"version": "1.0"}
+        ]),
+    );
+
+    let listed = extensions::list_extensions(profile.path()).unwrap();
+
+    assert_eq!(
+        listed,
+        vec![
+            ExtensionInfo {
+                id: "bare@example.org".to_string(),
+                name: None,
+                version: None,
+                enabled: None,
+            },
+            ExtensionInfo {
+                id: "old@example.org".to_string(),
+                name: Some("Old".to_string()),
+                version: Some("0.9".to_string()),
+                enabled: Some(false),
+            },
+            ExtensionInfo {
+                id: "ublock@example.org".to_string(),
+                name: Some("uBlock".to_string()),
+                version: Some("1.2".to_string()),
+                enabled: Some(true),
+            },
+        ]
+    );
+}
+
+#[test]
+fn profile_without_extensions_json_has_none() {
+    let profile = TempDir::new().unwrap();
+
+    assert!(extensions::list_extensions(profile.path())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn list_extensions_prints_them() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    write_addons(
+        &profile,
+        json!([{"id": "ublock@example.org", "version": "1.2", "active": false, "defaultLocale": {"name": "uBlock"}}]),
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--list-extensions")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("ublock@example.org 1.2 (uBlock) disabled"),
+        "{}",
+        stdout
+    );
+}
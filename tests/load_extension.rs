@@ -0,0 +1,85 @@
+use serde_json::Value;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+
+use fftemplates::extensions;
+
+fn addons(profile: &TempDir) -> Vec<Value> {
+    let content = fs::read_to_string(profile.path().join(extensions::EXTENSIONS_JSON)).unwrap();
+    let database: Value = serde_json::from_str(&content).unwrap();
+    database["addons"].as_array().unwrap().clone()
+}
+
+#[test]
+fn loads_unpacked_extension_with_its_id() {
+    let profile = TempDir::new().unwrap();
+    let source = TempDir::new().unwrap();
+    fs::write(
+        profile.path().join(extensions::EXTENSIONS_JSON),
+        r#"{"schemaVersion":35,"addons":[{"id":"dev@example.org","version":"0.1"},{"id":"other@example.org"}]}"#,
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("manifest.json"),
+        r#"{"manifest_version":2,"name":"Dev","version":"0.2",
+        "browser_specific_settings":{"gecko":{"id":"dev@example.org"}}}"#,
+    )
+    .unwrap();
+
+    let loaded = extensions::load_extension(profile.path(), source.path()).unwrap();
+
+    assert_eq!(loaded.id, "dev@example.org");
+    assert_eq!(loaded.version, "0.2");
+    assert_eq!(
+        loaded.path,
+        profile.path().join("extensions/dev@example.org")
+    );
+    assert!(loaded.path.join("manifest.json").exists());
+    // previous entry of the same extension is replaced
+    let addons = addons(&profile);
+    assert_eq!(addons.len(), 2);
+    assert_eq!(addons[1]["id"], "dev@example.org");
+    assert_eq!(addons[1]["version"], "0.2");
+    assert_eq!(addons[1]["path"], loaded.path.display().to_string());
+}
+
+#[test]
+fn loads_xpi_without_id() {
+    let profile = TempDir::new().unwrap();
+    let source = TempDir::new().unwrap();
+    let xpi = source.path().join("dev.xpi");
+    let mut writer = ZipWriter::new(File::create(&xpi).unwrap());
+    writer
+        .start_file("manifest.json", FileOptions::default())
+        .unwrap();
+    writer
+        .write_all(br#"{"manifest_version":2,"name":"Dev","version":"1.0"}"#)
+        .unwrap();
+    writer.finish().unwrap();
+
+    let loaded = extensions::load_extension(profile.path(), &xpi).unwrap();
+
+    assert!(
+        loaded.id.ends_with("@fftemplates.temporary"),
+        "{}",
+        loaded.id
+    );
+    assert_eq!(
+        loaded.path,
+        profile
+            .path()
+            .join("extensions")
+            .join(format!("{}.xpi", loaded.id))
+    );
+    assert!(loaded.path.is_file());
+    // profile without extensions.json gets a new one
+    assert_eq!(addons(&profile)[0]["id"], loaded.id.as_str());
+
+    let prefs = extensions::allow_loaded_extensions(profile.path()).unwrap();
+    assert!(prefs.contains(&"extensions.autoDisableScopes".to_string()));
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use std::collections::BTreeSet;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+fn merge(source: &TempDir, dest: &TempDir) -> fftemplates::sync::SyncPlan {
+    let (all_bookmarks, all_places, all_origins) =
+        bookmarks::get_all_entries(source.path().to_str().unwrap(), &EntryFilter::default())
+            .unwrap();
+    bookmarks::insert_new_entries(
+        dest.path().to_str().unwrap(),
+        all_bookmarks.as_deref(),
+        all_places.as_ref(),
+        all_origins.as_ref(),
+    )
+    .unwrap()
+}
+
+fn guids(conn: &Connection) -> BTreeSet<String> {
+    let mut statement = conn.prepare("select guid from moz_bookmarks").unwrap();
+    let guids = statement
+        .query_map(params![], |row| row.get(0))
+        .unwrap()
+        .map(|guid| guid.unwrap())
+        .collect();
+    guids
+}
+
+fn count(conn: &Connection, table: &str) -> i64 {
+    conn.query_row(
+        &format!("select count(*) from {}", table),
+        params![],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn merges_union_of_guids() {
+    let source = TempDir::new().unwrap();
+    let dest = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let dest_conn = common::create_places(dest.path());
+    // same bookmark in both profiles
+    common::insert_link(&source_conn, 7, 3, 0, "Home", "https://example.com/");
+    common::insert_link(&dest_conn, 7, 3, 0, "Home", "https://example.com/");
+    common::insert_link(&dest_conn, 8, 2, 0, "Blog", "https://blog.example.com/");
+    // ids of the source overlap with the ones assigned in the destination
+    common::insert_folder(&source_conn, 9, Some(3), 1, "Work", "workfolder01");
+    common::insert_link(&source_conn, 10, 9, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&source_conn, 11, 9, 1, "Rust", "https://rust-lang.org/");
+    common::insert_link(&source_conn, 12, 2, 0, "News", "https://news.example.com/");
+    let expected: BTreeSet<_> = guids(&source_conn)
+        .union(&guids(&dest_conn))
+        .cloned()
+        .collect();
+
+    let plan = merge(&source, &dest);
+
+    assert_eq!(plan.inserted_bookmarks(), 4);
+    assert_eq!(plan.reused_bookmarks(), 1);
+    assert_eq!(guids(&dest_conn), expected);
+    let work_id: i64 = dest_conn
+        .query_row(
+            "select id from moz_bookmarks where guid = 'workfolder01'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let mut statement = dest_conn
+        .prepare("select parent from moz_bookmarks where title in ('Docs', 'Rust')")
+        .unwrap();
+    let parents: Vec<i64> = statement
+        .query_map(params![], |row| row.get(0))
+        .unwrap()
+        .map(|parent| parent.unwrap())
+        .collect();
+    assert_eq!(parents, vec![work_id, work_id]);
+}
+
+#[test]
+fn merging_again_changes_nothing() {
+    let source = TempDir::new().unwrap();
+    let dest = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let dest_conn = common::create_places(dest.path());
+    common::insert_folder(&source_conn, 7, Some(3), 0, "Work", "workfolder01");
+    common::insert_link(&source_conn, 8, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&dest_conn, 20, 2, 0, "Blog", "https://blog.example.com/");
+
+    merge(&source, &dest);
+    let counts: Vec<_> = ["moz_bookmarks", "moz_places", "moz_origins"]
+        .iter()
+        .map(|table| count(&dest_conn, table))
+        .collect();
+    let plan = merge(&source, &dest);
+
+    assert_eq!(plan.inserted_bookmarks(), 0);
+    assert_eq!(plan.inserted_places(), 0);
+    assert_eq!(plan.inserted_origins(), 0);
+    assert_eq!(
+        ["moz_bookmarks", "moz_places", "moz_origins"]
+            .iter()
+            .map(|table| count(&dest_conn, table))
+            .collect::<Vec<_>>(),
+        counts
+    );
+}
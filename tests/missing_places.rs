@@ -0,0 +1,30 @@
+#![cfg(feature = "bookmarks")]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn skips_sync_without_places_database() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(profile.path().join("times.json"), "{}").unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("-b")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!stdout.contains("Error"), "{}", stdout);
+    assert!(stderr.contains("skipping bookmark sync"), "{}", stderr);
+    assert!(!profile.path().join("places.sqlite").exists());
+}
@@ -0,0 +1,36 @@
+#![cfg(all(unix, feature = "bookmarks"))]
+
+mod common;
+
+use tempfile::TempDir;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::process::Command;
+
+#[test]
+fn sync_into_non_unicode_profile_path_is_reported() {
+    let home = TempDir::new().unwrap();
+    let profile = home.path().join("profiles").join("abcd1234.work");
+    fs::create_dir_all(&profile).unwrap();
+    // sqlite can't open it once the path isn't unicode
+    drop(common::create_places(&profile));
+    let root = home.path().join(OsStr::from_bytes(b"profiles-\xff"));
+    fs::rename(home.path().join("profiles"), &root).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("work")
+        .arg("--browser")
+        .arg("true")
+        .arg("-b")
+        .env("FFTEMPLATES_PROFILE_ROOT", &root)
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CACHE_HOME", home.path())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("is not valid unicode"), "{}", stdout);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+}
@@ -0,0 +1,20 @@
+#![cfg(feature = "bookmarks")]
+
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::error::FfError;
+
+#[test]
+fn missing_places_is_reported_and_not_created() {
+    let profile = TempDir::new().unwrap();
+
+    let e = bookmarks::get_latest_bookmark(profile.path().to_str().unwrap()).unwrap_err();
+
+    let places = profile.path().join("places.sqlite");
+    match e {
+        FfError::PlacesDbMissing(path) => assert_eq!(path, places),
+        e => panic!("unexpected error : {}", e),
+    }
+    assert!(!places.exists());
+}
@@ -0,0 +1,391 @@
+#![cfg(feature = "bookmarks")]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use fftemplates::bookmarks::{self, Bookmark, EntryFilter, Keyword, Origin, Place};
+use fftemplates::cli::ConflictPolicy;
+use fftemplates::error::FfError;
+use fftemplates::history::Visit;
+use fftemplates::store::{PlacesStore, Table};
+
+// places kept in memory, enough of a database for the sync
+#[derive(Default)]
+struct MemoryStore {
+    bookmarks: RefCell<Vec<Bookmark>>,
+    places: RefCell<HashMap<i64, Place>>,
+    origins: RefCell<HashMap<i64, Origin>>,
+    visits: RefCell<Vec<Visit>>,
+}
+
+impl MemoryStore {
+    // root folders with ids starting at `first_id`
+    fn with_roots(first_id: i64) -> Self {
+        let store = MemoryStore::default();
+        let guids = [
+            bookmarks::ROOT_GUID,
+            bookmarks::MENU_GUID,
+            bookmarks::TOOLBAR_GUID,
+            bookmarks::TAGS_GUID,
+            bookmarks::UNFILED_GUID,
+            bookmarks::MOBILE_GUID,
+        ];
+        for (i, guid) in guids.iter().enumerate() {
+            let id = first_id + i as i64;
+            let parent = if i == 0 { None } else { Some(first_id) };
+            store.add_bookmark(id, parent, None, guid, None);
+        }
+        store
+    }
+
+    fn add_bookmark(
+        &self,
+        id: i64,
+        parent: Option<i64>,
+        fk: Option<i64>,
+        guid: &str,
+        title: Option<&str>,
+    ) {
+        let parent_guid = parent.and_then(|parent| self.guid_of(parent));
+        self.bookmarks.borrow_mut().push(Bookmark {
+            id,
+            r#type: Some(if fk.is_some() {
+                bookmarks::TYPE_BOOKMARK
+            } else {
+                bookmarks::TYPE_FOLDER
+            }),
+            fk,
+            parent,
+            position: Some(0),
+            title: title.map(|title| title.to_string()),
+            keyword_id: None,
+            folder_type: None,
+            date_added: Some(1),
+            last_modified: Some(1),
+            guid: Some(guid.to_string()),
+            sync_status: 0,
+            sync_change_counter: 0,
+            parent_guid,
+        });
+    }
+
+    fn add_place(&self, id: i64, url: &str, origin_id: i64) {
+        self.places.borrow_mut().insert(
+            id,
+            Place {
+                id,
+                url: Some(url.to_string()),
+                title: None,
+                rev_host: None,
+                visit_count: Some(0),
+                hidden: 0,
+                typed: 0,
+                favicon_id: None,
+                frecency: 0,
+                last_visit_date: None,
+                guid: Some(format!("place{:07}", id)),
+                foreign_count: 1,
+                url_hash: 0,
+                description: None,
+                preview_image_url: None,
+                origin_id: Some(origin_id),
+                keywords: vec![],
+            },
+        );
+    }
+
+    fn add_origin(&self, id: i64, host: &str) {
+        self.origins.borrow_mut().insert(
+            id,
+            Origin {
+                id,
+                prefix: "https://".to_string(),
+                host: host.to_string(),
+                frecency: 0,
+            },
+        );
+    }
+
+    fn guid_of(&self, id: i64) -> Option<String> {
+        let bookmarks = self.bookmarks.borrow();
+        bookmarks
+            .iter()
+            .find(|b| b.id == id)
+            .and_then(|b| b.guid.clone())
+    }
+
+    fn bookmark_by_guid(&self, guid: &str) -> Option<Bookmark> {
+        let bookmarks = self.bookmarks.borrow();
+        bookmarks
+            .iter()
+            .find(|b| b.guid.as_deref() == Some(guid))
+            .cloned()
+    }
+}
+
+impl PlacesStore for MemoryStore {
+    fn latest_bookmark(&self) -> Result<Option<Bookmark>, FfError> {
+        Ok(self.bookmarks.borrow().iter().max_by_key(|b| b.id).cloned())
+    }
+
+    fn bookmarks_after(&self, after_id: i64, until_id: i64) -> Result<Vec<Bookmark>, FfError> {
+        Ok(self
+            .all_bookmarks()?
+            .into_iter()
+            .filter(|b| b.id > after_id && b.id <= until_id)
+            .collect())
+    }
+
+    fn all_bookmarks(&self) -> Result<Vec<Bookmark>, FfError> {
+        let mut bookmarks = self.bookmarks.borrow().clone();
+        bookmarks.sort_by_key(|b| b.id);
+        Ok(bookmarks)
+    }
+
+    fn places_for(&self, ids: &[i64]) -> Result<HashMap<i64, Place>, FfError> {
+        let places = self.places.borrow();
+        Ok(ids
+            .iter()
+            .filter_map(|id| places.get(id).map(|place| (*id, place.clone())))
+            .collect())
+    }
+
+    fn origins_for(&self, ids: &[i64]) -> Result<HashMap<i64, Origin>, FfError> {
+        let origins = self.origins.borrow();
+        Ok(ids
+            .iter()
+            .filter_map(|id| origins.get(id).map(|origin| (*id, origin.clone())))
+            .collect())
+    }
+
+    fn root_folder_ids(&self) -> Result<HashMap<String, i64>, FfError> {
+        Ok(self
+            .bookmarks
+            .borrow()
+            .iter()
+            .filter_map(|b| match &b.guid {
+                Some(guid) if bookmarks::is_root_guid(guid) => Some((guid.clone(), b.id)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn next_id(&self, table: Table) -> Result<i64, FfError> {
+        let max_id = match table {
+            Table::Origins => self.origins.borrow().keys().max().cloned(),
+            Table::Places => self.places.borrow().keys().max().cloned(),
+            Table::Bookmarks => self.bookmarks.borrow().iter().map(|b| b.id).max(),
+            Table::Visits => self.visits.borrow().iter().map(|v| v.id).max(),
+        };
+        Ok(max_id.unwrap_or(0) + 1)
+    }
+
+    fn id_by_guid(&self, table: Table, guid: &str) -> Result<Option<i64>, FfError> {
+        Ok(match table {
+            Table::Places => self
+                .places
+                .borrow()
+                .values()
+                .find(|p| p.guid.as_deref() == Some(guid))
+                .map(|p| p.id),
+            Table::Bookmarks => self.bookmark_by_guid(guid).map(|b| b.id),
+            _ => None,
+        })
+    }
+
+    fn place_id_by_url(&self, url_hash: i64, url: &str) -> Result<Option<i64>, FfError> {
+        Ok(self
+            .places
+            .borrow()
+            .values()
+            .find(|p| p.url_hash == url_hash && p.url.as_deref() == Some(url))
+            .map(|p| p.id))
+    }
+
+    fn origin_id(&self, prefix: &str, host: &str) -> Result<Option<i64>, FfError> {
+        Ok(self
+            .origins
+            .borrow()
+            .values()
+            .find(|o| o.prefix == prefix && o.host == host)
+            .map(|o| o.id))
+    }
+
+    fn find_folder_by_title(&self, title: &str) -> Result<Option<i64>, FfError> {
+        Ok(self
+            .all_bookmarks()?
+            .iter()
+            .find(|b| b.r#type == Some(bookmarks::TYPE_FOLDER) && b.title.as_deref() == Some(title))
+            .map(|b| b.id))
+    }
+
+    fn next_child_position(&self, folder_id: i64) -> Result<i64, FfError> {
+        Ok(self
+            .bookmarks
+            .borrow()
+            .iter()
+            .filter(|b| b.parent == Some(folder_id))
+            .filter_map(|b| b.position)
+            .max()
+            .map_or(0, |position| position + 1))
+    }
+
+    fn has_keywords(&self) -> Result<bool, FfError> {
+        Ok(true)
+    }
+
+    fn keyword_place_id(&self, keyword: &str) -> Result<Option<i64>, FfError> {
+        Ok(self
+            .places
+            .borrow()
+            .values()
+            .find(|p| p.keywords.iter().any(|k| k.keyword == keyword))
+            .map(|p| p.id))
+    }
+
+    fn insert_origin(&self, origin: &Origin) -> Result<(), FfError> {
+        self.origins.borrow_mut().insert(origin.id, origin.clone());
+        Ok(())
+    }
+
+    fn insert_place(&self, place: &Place) -> Result<(), FfError> {
+        self.places.borrow_mut().insert(place.id, place.clone());
+        Ok(())
+    }
+
+    fn insert_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError> {
+        self.bookmarks.borrow_mut().push(bookmark.clone());
+        Ok(())
+    }
+
+    fn update_bookmark(&self, bookmark: &Bookmark) -> Result<(), FfError> {
+        let mut bookmarks = self.bookmarks.borrow_mut();
+        if let Some(existing) = bookmarks.iter_mut().find(|b| b.id == bookmark.id) {
+            *existing = bookmark.clone();
+        }
+        Ok(())
+    }
+
+    fn insert_keyword(&self, place_id: i64, keyword: &Keyword) -> Result<(), FfError> {
+        if let Some(place) = self.places.borrow_mut().get_mut(&place_id) {
+            place.keywords.push(keyword.clone());
+        }
+        Ok(())
+    }
+
+    fn insert_visit(&self, visit: &Visit) -> Result<(), FfError> {
+        self.visits.borrow_mut().push(visit.clone());
+        Ok(())
+    }
+
+    fn update_foreign_count(&self, place_id: i64) -> Result<(), FfError> {
+        let references = self
+            .bookmarks
+            .borrow()
+            .iter()
+            .filter(|b| b.fk == Some(place_id))
+            .count() as i64;
+        if let Some(place) = self.places.borrow_mut().get_mut(&place_id) {
+            place.foreign_count = references + place.keywords.len() as i64;
+        }
+        Ok(())
+    }
+
+    fn update_origin_frecency(&self, origin_id: i64) -> Result<(), FfError> {
+        let frecency = self
+            .places
+            .borrow()
+            .values()
+            .filter(|p| p.origin_id == Some(origin_id) && p.frecency > 0)
+            .map(|p| p.frecency)
+            .sum();
+        if let Some(origin) = self.origins.borrow_mut().get_mut(&origin_id) {
+            origin.frecency = frecency;
+        }
+        Ok(())
+    }
+
+    fn add_place_visits(
+        &self,
+        place_id: i64,
+        visit_count: i64,
+        _last_visit_date: Option<i64>,
+    ) -> Result<(), FfError> {
+        if let Some(place) = self.places.borrow_mut().get_mut(&place_id) {
+            place.visit_count = Some(place.visit_count.unwrap_or(0) + visit_count);
+        }
+        Ok(())
+    }
+}
+
+// source with a folder and a link in it added after the baseline,
+// target has the same origin and its roots at other ids
+fn stores() -> (MemoryStore, Bookmark, MemoryStore) {
+    let source = MemoryStore::with_roots(1);
+    let baseline = source.latest_bookmark().unwrap().unwrap();
+    source.add_origin(1, "example.com");
+    source.add_place(1, "https://example.com/", 1);
+    source.add_bookmark(7, Some(2), None, "folder000007", Some("Work"));
+    source.add_bookmark(8, Some(7), Some(1), "link00000008", Some("Example"));
+
+    let target = MemoryStore::with_roots(10);
+    target.add_origin(4, "example.com");
+    target.add_place(9, "https://example.com/other", 4);
+
+    (source, baseline, target)
+}
+
+#[test]
+fn syncs_between_memory_stores() {
+    let (source, baseline, target) = stores();
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries_from(&source, &baseline, &EntryFilter::default()).unwrap();
+    let plan = bookmarks::insert_new_entries_into_store(
+        &target,
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+        None,
+        ConflictPolicy::Skip,
+    )
+    .unwrap();
+
+    assert_eq!(plan.inserted_bookmarks(), 2);
+    assert_eq!(plan.inserted_places(), 1);
+    assert_eq!(plan.reused_origins(), 1);
+    let folder = target.bookmark_by_guid("folder000007").unwrap();
+    let link = target.bookmark_by_guid("link00000008").unwrap();
+    // menu root of the target
+    assert_eq!(folder.parent, Some(11));
+    assert_eq!(link.parent, Some(folder.id));
+    let place = &target.places.borrow()[&link.fk.unwrap()];
+    assert_eq!(place.url.as_deref(), Some("https://example.com/"));
+    assert_eq!(place.origin_id, Some(4));
+}
+
+#[test]
+fn syncing_again_reuses_everything() {
+    let (source, baseline, target) = stores();
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries_from(&source, &baseline, &EntryFilter::default()).unwrap();
+    let sync = || {
+        bookmarks::insert_new_entries_into_store(
+            &target,
+            new_bookmarks.as_deref(),
+            new_places.as_ref(),
+            new_origins.as_ref(),
+            None,
+            ConflictPolicy::Skip,
+        )
+        .unwrap()
+    };
+    sync();
+
+    let plan = sync();
+
+    assert_eq!(plan.inserted_bookmarks(), 0);
+    assert_eq!(plan.inserted_places(), 0);
+    assert_eq!(plan.reused_bookmarks(), 2);
+    assert_eq!(target.bookmarks.borrow().len(), 8);
+}
@@ -0,0 +1,88 @@
+use tempfile::TempDir;
+
+use std::collections::HashSet;
+use std::fs;
+use std::process::Command;
+
+use fftemplates::profiles;
+
+fn create_profile() -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "user_pref(\"a\", 1);\n").unwrap();
+    fs::write(profile.path().join("lock"), "").unwrap();
+    fs::write(profile.path().join("places.sqlite-wal"), "").unwrap();
+    fs::create_dir(profile.path().join("extensions")).unwrap();
+    fs::write(profile.path().join("extensions/a.xpi"), "xpi").unwrap();
+    profile
+}
+
+#[test]
+fn plans_entries_without_ignored_ones() {
+    let profile = create_profile();
+    let ignore: HashSet<String> = ["lock", "places.sqlite-wal"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    let entries = profiles::plan_copy(profile.path(), &ignore).unwrap();
+
+    assert_eq!(
+        entries,
+        vec![
+            profile.path().join("extensions"),
+            profile.path().join("prefs.js")
+        ]
+    );
+}
+
+#[test]
+fn lists_files_without_copying() {
+    let profile = create_profile();
+    let config = TempDir::new().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("missing-browser")
+        .arg("--list-files")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let lines: Vec<&str> = stdout.lines().map(|line| line.trim()).collect();
+    assert_eq!(lines.len(), 3, "{}", stdout);
+    assert_eq!(lines[0], "3 B  extensions/");
+    assert_eq!(lines[1], "19 B  prefs.js");
+    assert!(lines[2].starts_with("22 B  total from"), "{}", stdout);
+}
+
+#[test]
+fn lock_files_are_not_listed() {
+    let profile = create_profile();
+    let config = TempDir::new().unwrap();
+    for name in profiles::LOCK_FILES {
+        fs::write(profile.path().join(name), "").unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--list-files")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    for name in profiles::LOCK_FILES {
+        assert!(
+            !stdout
+                .lines()
+                .any(|line| line.ends_with(&format!("  {}", name))),
+            "{}",
+            stdout
+        );
+    }
+    assert!(stdout.contains("prefs.js"), "{}", stdout);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+// titles of the children of the folder with their positions, in order
+fn children(conn: &Connection, parent_guid: &str) -> Vec<(String, Option<i64>)> {
+    let mut statement = conn
+        .prepare(
+            "select b.title, b.position
+            from moz_bookmarks b
+            join moz_bookmarks f on f.id = b.parent
+            where f.guid = ?1
+            order by b.position",
+        )
+        .unwrap();
+    statement
+        .query_map(params![parent_guid], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|child| child.unwrap())
+        .collect()
+}
+
+#[test]
+fn null_positions_are_put_after_the_other_children() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 20, 3, 0, "Home", "https://example.com/");
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    common::insert_folder(&source_conn, 7, Some(3), 0, "Work", "workfolder01");
+    common::insert_link(&source_conn, 8, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&source_conn, 9, 7, 0, "Wiki", "https://wiki.example.com/");
+    common::insert_link(&source_conn, 10, 3, 0, "Rust", "https://rust-lang.org/");
+    source_conn
+        .execute(
+            "update moz_bookmarks set position = null where id in (8, 10)",
+            params![],
+        )
+        .unwrap();
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    bookmarks::insert_new_entries(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        children(&target_conn, bookmarks::TOOLBAR_GUID),
+        vec![
+            ("Home".to_string(), Some(0)),
+            ("Work".to_string(), Some(1)),
+            ("Rust".to_string(), Some(2)),
+        ]
+    );
+    assert_eq!(
+        children(&target_conn, "workfolder01"),
+        vec![("Wiki".to_string(), Some(0)), ("Docs".to_string(), Some(1))]
+    );
+}
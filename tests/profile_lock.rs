@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::process;
+
+use tempfile::TempDir;
+
+use fftemplates::profiles;
+
+#[test]
+fn lock_of_running_process_is_detected() {
+    let profile = TempDir::new().unwrap();
+    assert!(!profiles::is_profile_locked(profile.path()));
+
+    symlink(
+        format!("127.0.0.1:+{}", process::id()),
+        profile.path().join("lock"),
+    )
+    .unwrap();
+    assert!(profiles::is_profile_locked(profile.path()));
+}
+
+#[test]
+fn stale_lock_is_ignored() {
+    let profile = TempDir::new().unwrap();
+    // pids are far below this on linux, the process can't exist
+    symlink("127.0.0.1:+2147483646", profile.path().join("lock")).unwrap();
+    assert!(!profiles::is_profile_locked(profile.path()));
+}
+
+#[test]
+fn left_parentlock_is_ignored() {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join(".parentlock"), "").unwrap();
+
+    assert!(!profiles::is_profile_locked(profile.path()));
+}
+
+// open file description locks conflict with the fcntl lock firefox uses,
+// even when held by this process
+#[cfg(target_os = "linux")]
+#[test]
+fn held_parentlock_is_detected() {
+    use std::os::unix::io::AsRawFd;
+
+    let profile = TempDir::new().unwrap();
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(profile.path().join(".parentlock"))
+        .unwrap();
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    assert_eq!(
+        unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &lock) },
+        0
+    );
+
+    assert!(profiles::is_profile_locked(profile.path()));
+}
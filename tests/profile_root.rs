@@ -0,0 +1,78 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::cli::PROFILE_ROOT_VARIABLE;
+
+#[test]
+fn profiles_are_looked_up_in_env_root() {
+    let root = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("abc123.work")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("missing")
+        .env(PROFILE_ROOT_VARIABLE, root.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        stdout.contains("No profile named `missing` found. Available : work"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn profile_root_argument_overrides_env() {
+    let root = TempDir::new().unwrap();
+    let other_root = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("abc123.work")).unwrap();
+    fs::create_dir(other_root.path().join("def456.home")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("missing")
+        .arg("--profile-root")
+        .arg(other_root.path())
+        .env(PROFILE_ROOT_VARIABLE, root.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Available : home"), "{}", stdout);
+}
+
+#[test]
+fn profile_is_selected_by_section_index() {
+    let root = TempDir::new().unwrap();
+    fs::write(
+        root.path().join("profiles.ini"),
+        "[Profile0]\nName=work\nIsRelative=1\nPath=abc123.work\n\n[Profile1]\nName=work\nIsRelative=1\nPath=def456.work\n",
+    )
+    .unwrap();
+    fs::create_dir(root.path().join("abc123.work")).unwrap();
+    fs::create_dir(root.path().join("def456.work")).unwrap();
+
+    let dry_run = |index: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+            .arg("--profile-root")
+            .arg(root.path())
+            .arg("--profile-index")
+            .arg(index)
+            .arg("--dry-run")
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // both profiles have the same name
+    let stdout = dry_run("1");
+    assert!(stdout.contains("def456.work"), "{}", stdout);
+    let stdout = dry_run("2");
+    assert!(
+        stdout.contains("There is no [Profile2] section"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,77 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::archive::ProfileSource;
+use fftemplates::cli::PROFILE_ROOT_VARIABLE;
+use fftemplates::profiles;
+
+#[test]
+fn profile_is_found_in_a_later_root() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    fs::create_dir(first.path().join("abc123.home")).unwrap();
+    fs::create_dir(second.path().join("def456.work")).unwrap();
+    let missing = first.path().join("missing");
+
+    let found = profiles::find_profile(&[&missing, first.path(), second.path()], "work")
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(found.root, second.path());
+    assert_eq!(found.entry_name, "def456.work");
+    assert_eq!(
+        found.source,
+        ProfileSource::Directory(second.path().join("def456.work").canonicalize().unwrap())
+    );
+}
+
+#[test]
+fn first_root_wins_when_both_have_the_profile() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    fs::write(
+        second.path().join("profiles.ini"),
+        "[Profile0]\nName=work\nIsRelative=1\nPath=def456.work\n",
+    )
+    .unwrap();
+    fs::create_dir(first.path().join("abc123.work")).unwrap();
+    fs::create_dir(second.path().join("def456.work")).unwrap();
+
+    let found = profiles::find_profile(&[first.path(), second.path()], "work")
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(found.root, first.path());
+    assert!(
+        profiles::find_profile(&[first.path(), second.path()], "other")
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn profile_missing_from_profile_root_is_found_in_env_root() {
+    let root = TempDir::new().unwrap();
+    let env_root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::create_dir(env_root.path().join("def456.work")).unwrap();
+    fs::write(env_root.path().join("def456.work").join("prefs.js"), "").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("work")
+        .arg("--profile-root")
+        .arg(root.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--print-command")
+        .env(PROFILE_ROOT_VARIABLE, env_root.path())
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(!stdout.contains("Error"), "{}", stdout);
+    assert!(stdout.starts_with("true "), "{}", stdout);
+}
@@ -0,0 +1,205 @@
+use fftemplates::profiles;
+
+use std::fs;
+use std::path::Path;
+
+const PROFILES_INI: &str = "
+[Install4F96D1932A9F858E]
+Default=abcd1234.work
+Locked=1
+
+[Profile1]
+Name=portable
+IsRelative=0
+Path=/mnt/drive/firefox/efgh5678.portable
+
+[Profile0]
+Name=work
+IsRelative=1
+Path=abcd1234.work
+
+[General]
+StartWithLastProfile=1
+Version=2
+";
+
+#[test]
+fn resolves_relative_and_absolute_paths() {
+    let root = Path::new("/home/user/.mozilla/firefox");
+    let entries = profiles::parse_profiles_ini(root, PROFILES_INI);
+
+    assert_eq!(entries.len(), 2);
+    let portable = entries.iter().find(|e| e.name == "portable").unwrap();
+    assert_eq!(portable.section, "Profile1");
+    assert!(!portable.is_relative);
+    assert_eq!(
+        portable.path,
+        Path::new("/mnt/drive/firefox/efgh5678.portable")
+    );
+    let work = entries.iter().find(|e| e.name == "work").unwrap();
+    assert_eq!(work.section, "Profile0");
+    assert!(work.is_relative);
+    assert_eq!(work.path, root.join("abcd1234.work"));
+}
+
+#[test]
+fn default_comes_from_install_section() {
+    let root = Path::new("/home/user/.mozilla/firefox");
+    let entries = profiles::parse_profiles_ini(root, PROFILES_INI);
+
+    let defaults: Vec<_> = entries
+        .iter()
+        .filter(|e| e.is_default)
+        .map(|e| e.name.as_str())
+        .collect();
+    assert_eq!(defaults, vec!["work"]);
+}
+
+#[test]
+fn default_falls_back_to_profile_flag_without_install_section() {
+    let root = Path::new("/home/user/.mozilla/firefox");
+    let content = "
+[Profile0]
+Name=default
+IsRelative=1
+Path=aaaa.default
+Default=1
+
+[Profile1]
+Name=other
+IsRelative=1
+Path=bbbb.other
+";
+    let entries = profiles::parse_profiles_ini(root, content);
+
+    assert!(entries[0].is_default);
+    assert!(!entries[1].is_default);
+}
+
+#[test]
+fn reads_profiles_ini_from_root() {
+    let root = tempfile::tempdir().unwrap();
+    assert_eq!(profiles::read_profiles_ini(root.path()).unwrap(), None);
+
+    fs::write(root.path().join("profiles.ini"), PROFILES_INI).unwrap();
+    let entries = profiles::read_profiles_ini(root.path()).unwrap().unwrap();
+    assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn finds_profile_by_section_index() {
+    let root = Path::new("/home/user/.mozilla/firefox");
+    let entries = profiles::parse_profiles_ini(root, PROFILES_INI);
+
+    let portable = profiles::find_profile_by_index(&entries, 1).unwrap();
+    assert_eq!(portable.name, "portable");
+    assert_eq!(
+        profiles::find_profile_by_index(&entries, 0).unwrap().name,
+        "work"
+    );
+    assert_eq!(profiles::find_profile_by_index(&entries, 2), None);
+}
+
+#[test]
+fn added_profile_creates_missing_file() {
+    let root = tempfile::tempdir().unwrap();
+
+    let section = profiles::add_profile_to_ini(root.path(), "work", "abcd1234.work").unwrap();
+
+    assert_eq!(section, "Profile0");
+    let entries = profiles::read_profiles_ini(root.path()).unwrap().unwrap();
+    assert_eq!(
+        entries,
+        vec![profiles::ProfileEntry {
+            section: "Profile0".to_string(),
+            name: "work".to_string(),
+            path: root.path().join("abcd1234.work"),
+            is_relative: true,
+            is_default: false,
+        }]
+    );
+    let content = fs::read_to_string(root.path().join("profiles.ini")).unwrap();
+    assert!(content.starts_with("[General]\n"), "{}", content);
+}
+
+#[test]
+fn added_profile_follows_the_highest_section() {
+    let root = tempfile::tempdir().unwrap();
+    let content = "
+[Profile0]
+Name=work
+IsRelative=1
+Path=abcd1234.work
+
+[Profile3]
+Name=games
+IsRelative=1
+Path=ijkl9012.games
+";
+    fs::write(root.path().join("profiles.ini"), content).unwrap();
+
+    let section = profiles::add_profile_to_ini(root.path(), "test", "mnop3456.test").unwrap();
+
+    assert_eq!(section, "Profile4");
+    let entries = profiles::read_profiles_ini(root.path()).unwrap().unwrap();
+    let sections: Vec<_> = entries
+        .iter()
+        .map(|e| (e.section.as_str(), e.name.as_str()))
+        .collect();
+    assert_eq!(
+        sections,
+        vec![
+            ("Profile0", "work"),
+            ("Profile3", "games"),
+            ("Profile4", "test")
+        ]
+    );
+}
+
+#[test]
+fn added_profile_after_missing_trailing_newline() {
+    let root = tempfile::tempdir().unwrap();
+    let content = "[Profile0]\nName=work\nIsRelative=1\nPath=abcd1234.work";
+    fs::write(root.path().join("profiles.ini"), content).unwrap();
+
+    profiles::add_profile_to_ini(root.path(), "test", "mnop3456.test").unwrap();
+
+    let entries = profiles::read_profiles_ini(root.path()).unwrap().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, root.path().join("abcd1234.work"));
+    assert_eq!(entries[1].name, "test");
+    assert_eq!(entries[1].path, root.path().join("mnop3456.test"));
+}
+
+#[test]
+fn added_profile_with_taken_name_is_rejected() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join("profiles.ini"), PROFILES_INI).unwrap();
+
+    let result = profiles::add_profile_to_ini(root.path(), "work", "mnop3456.work");
+
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read_to_string(root.path().join("profiles.ini")).unwrap(),
+        PROFILES_INI
+    );
+}
+
+#[test]
+fn generated_hash_fits_folder_names() {
+    let hash = profiles::generate_profile_hash();
+
+    assert_eq!(hash.len(), 8);
+    assert!(
+        hash.chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()),
+        "{}",
+        hash
+    );
+    let folder = format!("{}{}work", hash, profiles::HASH_NAME_SPLIT_CHAR);
+    let root = Path::new("/home/user/.mozilla/firefox");
+    let content = format!("[Profile0]\nName=work\nIsRelative=1\nPath={}\n", folder);
+    let entries = profiles::parse_profiles_ini(root, &content);
+    assert_eq!(entries[0].path, root.join(&folder));
+    assert_ne!(profiles::generate_profile_hash(), hash);
+}
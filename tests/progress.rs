@@ -0,0 +1,36 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::progress::format_bytes;
+
+#[test]
+fn formats_bytes_with_binary_units() {
+    assert_eq!(format_bytes(512), "512 B");
+    assert_eq!(format_bytes(1536), "1.5 KiB");
+    assert_eq!(format_bytes(27 * 1024 * 1024), "27.0 MiB");
+    assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+#[test]
+fn progress_bar_is_not_drawn_into_a_pipe() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    fs::write(profile.path().join("places.sqlite"), vec![0; 64 * 1024]).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--progress")
+        .env("XDG_CONFIG_HOME", config.path())
+        .env("XDG_CACHE_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!output.stderr.contains(&b'\r'));
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// browser that writes to both streams and exits with the given code
+fn noisy_browser(dir: &Path, code: i32) -> PathBuf {
+    let browser = dir.join("firefox");
+    fs::write(
+        &browser,
+        format!(
+            "#!/bin/sh\necho out-noise\necho err-noise >&2\nexit {}\n",
+            code
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    browser
+}
+
+fn launch(code: i32) -> Output {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg(noisy_browser(config.path(), code))
+        .arg("--quiet-firefox")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap()
+}
+
+// single test, a script written while another test spawns a process
+// can fail to start with "text file busy"
+#[test]
+fn output_is_hidden_unless_launch_fails() {
+    let output = launch(0);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("noise"));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("noise"));
+
+    let output = launch(3);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("didn't exit cleanly"), "{}", stdout);
+    assert!(stdout.contains("  err-noise"), "{}", stdout);
+    assert!(!stdout.contains("out-noise"), "{}", stdout);
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+
+#[test]
+fn detects_that_every_bookmark_was_removed() {
+    let profile = TempDir::new().unwrap();
+    let folder = profile.path().to_str().unwrap();
+    let conn = common::create_places(profile.path());
+    let root_only = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+    common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+    common::insert_link(&conn, 8, 3, 1, "Rust", "https://rust-lang.org/");
+    let baseline = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+
+    conn.execute("delete from moz_bookmarks where id = 8", params![])
+        .unwrap();
+    assert!(!bookmarks::all_bookmarks_removed(folder, &baseline).unwrap());
+
+    conn.execute("delete from moz_bookmarks where id = 7", params![])
+        .unwrap();
+    assert!(bookmarks::all_bookmarks_removed(folder, &baseline).unwrap());
+    // there was nothing to remove
+    assert!(!bookmarks::all_bookmarks_removed(folder, &root_only).unwrap());
+}
+
+#[test]
+fn removes_bookmarks_and_keeps_folders_with_new_ones() {
+    let profile = TempDir::new().unwrap();
+    let folder = profile.path().to_str().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_folder(&conn, 7, Some(2), 0, "Work", "folder000007");
+    common::insert_link(&conn, 8, 7, 0, "Home", "https://example.com/");
+    common::insert_link(&conn, 9, 3, 0, "Rust", "https://rust-lang.org/");
+    common::insert_link(&conn, 10, 3, 1, "Docs", "https://docs.rs/");
+    let guids = bookmarks::get_bookmark_guids(folder).unwrap();
+    assert_eq!(guids.len(), 4);
+    // added after the copy was made
+    common::insert_link(&conn, 11, 7, 1, "New", "https://example.org/");
+    conn.execute("delete from moz_bookmarks where id = 10", params![])
+        .unwrap();
+    drop(conn);
+
+    let removed = bookmarks::remove_bookmarks(folder, &guids).unwrap();
+
+    assert_eq!(removed, 2);
+    let conn = Connection::open(profile.path().join("places.sqlite")).unwrap();
+    let position = |id: i64| -> Option<i64> {
+        conn.query_row(
+            "select position from moz_bookmarks where id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .ok()
+    };
+    assert_eq!(position(7), Some(0));
+    assert_eq!(position(8), None);
+    assert_eq!(position(9), None);
+    assert_eq!(position(11), Some(0));
+    let foreign_count: i64 = conn
+        .query_row(
+            "select foreign_count from moz_places where url = 'https://rust-lang.org/'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(foreign_count, 0);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::params;
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::RepairReport;
+
+#[test]
+fn fixes_orphaned_places_and_origins() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_link(&conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&conn, 8, 3, 1, "Rust", "https://rust-lang.org/");
+    // search page that is only kept by its keyword
+    common::insert_link(&conn, 9, 3, 2, "Search", "https://docs.example.com/search");
+    common::create_keywords(&conn);
+    common::insert_keyword(&conn, "d", 9, None);
+    conn.execute("delete from moz_bookmarks where id = 9", params![])
+        .unwrap();
+    // bookmark insert of the last sync failed after its place was added
+    conn.execute("delete from moz_bookmarks where id = 8", params![])
+        .unwrap();
+    conn.execute(
+        "insert into moz_origins (prefix, host, frecency) values ('https://', 'unused.org', 1)",
+        params![],
+    )
+    .unwrap();
+
+    let report = bookmarks::repair_places(&conn).unwrap();
+
+    assert_eq!(
+        report,
+        RepairReport {
+            orphaned_places: 1,
+            foreign_counts_fixed: 1,
+            removed_origins: 1,
+        }
+    );
+    let foreign_count: i64 = conn
+        .query_row(
+            "select foreign_count from moz_places where url = 'https://rust-lang.org/'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(foreign_count, 0);
+    let foreign_count: i64 = conn
+        .query_row(
+            "select foreign_count from moz_places where url = 'https://docs.example.com/search'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(foreign_count, 1);
+    assert_eq!(
+        bookmarks::repair_places(&conn).unwrap(),
+        RepairReport::default()
+    );
+}
+
+#[test]
+fn backs_up_places() {
+    let profile = TempDir::new().unwrap();
+    common::create_places(profile.path());
+
+    let backup_file = bookmarks::backup_places(profile.path().to_str().unwrap()).unwrap();
+
+    assert_eq!(
+        backup_file,
+        profile.path().join("places.sqlite.fftemplates-backup")
+    );
+    assert!(backup_file.exists());
+}
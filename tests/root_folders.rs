@@ -0,0 +1,128 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+// roots in a different order than in a fresh profile, so ids don't match
+fn create_shuffled_places(profile: &TempDir) -> Connection {
+    let conn = common::create_empty_places(profile.path());
+    common::insert_folder(&conn, 1, None, 0, "", bookmarks::ROOT_GUID);
+    common::insert_folder(&conn, 2, Some(1), 4, "mobile", bookmarks::MOBILE_GUID);
+    common::insert_folder(&conn, 3, Some(1), 3, "unfiled", bookmarks::UNFILED_GUID);
+    common::insert_folder(&conn, 4, Some(1), 1, "toolbar", bookmarks::TOOLBAR_GUID);
+    common::insert_folder(&conn, 5, Some(1), 2, "tags", bookmarks::TAGS_GUID);
+    common::insert_folder(&conn, 6, Some(1), 0, "menu", bookmarks::MENU_GUID);
+    conn
+}
+
+fn sync(source: &TempDir, target: &TempDir, add: impl Fn(&Connection)) {
+    sync_with_base(source, target, |_| {}, add);
+}
+
+// `base` entries exist before the temp profile is made, `add` ones are new
+fn sync_with_base(
+    source: &TempDir,
+    target: &TempDir,
+    base: impl Fn(&Connection),
+    add: impl Fn(&Connection),
+) {
+    let source_conn = common::create_places(source.path());
+    base(&source_conn);
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    add(&source_conn);
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    bookmarks::insert_new_entries(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+}
+
+fn parent_of(conn: &Connection, title: &str) -> i64 {
+    conn.query_row(
+        "select parent from moz_bookmarks where title = ?1",
+        params![title],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn attaches_to_target_toolbar() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = create_shuffled_places(&target);
+
+    sync(&source, &target, |conn| {
+        common::insert_link(conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+    });
+
+    assert_eq!(parent_of(&target_conn, "Docs"), 4);
+    let toolbars: i64 = target_conn
+        .query_row(
+            "select count(*) from moz_bookmarks where guid = ?1",
+            params![bookmarks::TOOLBAR_GUID],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(toolbars, 1);
+}
+
+#[test]
+fn attaches_to_target_mobile_root() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = create_shuffled_places(&target);
+
+    sync(&source, &target, |conn| {
+        common::insert_link(conn, 7, 6, 0, "Phone", "https://phone.example.com/");
+    });
+
+    assert_eq!(parent_of(&target_conn, "Phone"), 2);
+}
+
+#[test]
+fn attaches_to_existing_folder_with_another_id() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = create_shuffled_places(&target);
+    common::insert_link(&target_conn, 7, 4, 0, "Home", "https://example.com/");
+    common::insert_folder(&target_conn, 8, Some(4), 1, "Team", "teamfolder__");
+
+    sync_with_base(
+        &source,
+        &target,
+        |conn| common::insert_folder(conn, 7, Some(3), 0, "Team", "teamfolder__"),
+        |conn| common::insert_link(conn, 8, 7, 0, "Docs", "https://docs.example.com/"),
+    );
+
+    assert_eq!(parent_of(&target_conn, "Docs"), 8);
+}
+
+#[test]
+fn missing_folder_goes_to_unfiled_root() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = create_shuffled_places(&target);
+
+    sync_with_base(
+        &source,
+        &target,
+        |conn| common::insert_folder(conn, 7, Some(3), 0, "Team", "teamfolder__"),
+        |conn| common::insert_link(conn, 8, 7, 0, "Docs", "https://docs.example.com/"),
+    );
+
+    assert_eq!(parent_of(&target_conn, "Docs"), 3);
+}
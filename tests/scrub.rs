@@ -0,0 +1,83 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::scrub;
+
+// profile with telemetry and crash report state next to the files it keeps
+fn create_profile() -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::create_dir_all(profile.path().join("datareporting/archived")).unwrap();
+    fs::write(profile.path().join("datareporting/state.json"), "{}").unwrap();
+    fs::create_dir_all(profile.path().join("crashes/events")).unwrap();
+    fs::create_dir(profile.path().join("saved-telemetry-pings")).unwrap();
+    fs::write(profile.path().join("Telemetry.FailedProfileLocks.txt"), "3").unwrap();
+    fs::write(profile.path().join("places.sqlite"), "").unwrap();
+    fs::write(
+        profile.path().join("prefs.js"),
+        "user_pref(\"toolkit.telemetry.cachedClientID\", \"1234-abcd\");\n\
+         user_pref(\"toolkit.telemetry.enabled\", true);\n\
+         user_pref(\"browser.startup.page\", 1);\n",
+    )
+    .unwrap();
+    profile
+}
+
+#[test]
+fn scrub_removes_state_and_turns_off_telemetry() {
+    let profile = create_profile();
+
+    let set = scrub::scrub_profile(profile.path()).unwrap();
+
+    for name in scrub::SCRUBBED_PATHS {
+        assert!(!profile.path().join(name).exists(), "{} is left", name);
+    }
+    assert!(profile.path().join("places.sqlite").exists());
+    let prefs = fs::read_to_string(profile.path().join("prefs.js")).unwrap();
+    assert!(!prefs.contains("cachedClientID"));
+    assert!(prefs.contains("user_pref(\"toolkit.telemetry.enabled\", false);"));
+    assert!(!prefs.contains("user_pref(\"toolkit.telemetry.enabled\", true);"));
+    assert!(prefs.contains("user_pref(\"datareporting.healthreport.uploadEnabled\", false);"));
+    assert!(prefs.contains("user_pref(\"browser.startup.page\", 1);"));
+    assert_eq!(set.len(), scrub::SCRUB_PREFS.len());
+}
+
+#[test]
+fn scrub_of_clean_profile_only_sets_prefs() {
+    let profile = TempDir::new().unwrap();
+
+    scrub::scrub_profile(profile.path()).unwrap();
+
+    let prefs = fs::read_to_string(profile.path().join("prefs.js")).unwrap();
+    assert!(prefs.contains("user_pref(\"toolkit.telemetry.unified\", false);"));
+}
+
+#[test]
+fn scrub_changes_only_the_copy() {
+    let profile = create_profile();
+    let config = TempDir::new().unwrap();
+    let prefs = fs::read_to_string(profile.path().join("prefs.js")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        // fails the run if it was launched
+        .arg("false")
+        .arg("--dump-prefs")
+        .arg("--scrub")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- user_pref(\"toolkit.telemetry.cachedClientID\", \"1234-abcd\");"));
+    assert!(stdout.contains("+ user_pref(\"toolkit.telemetry.enabled\", false);"));
+    assert_eq!(
+        fs::read_to_string(profile.path().join("prefs.js")).unwrap(),
+        prefs
+    );
+    assert!(profile.path().join("datareporting/state.json").exists());
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::params;
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+#[test]
+fn syncs_separator_between_bookmarks() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    common::insert_folder(&source_conn, 7, Some(3), 0, "Work", "workfolder01");
+    common::insert_link(&source_conn, 8, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_separator(&source_conn, 9, 7, 1);
+    common::insert_link(&source_conn, 10, 7, 2, "Rust", "https://rust-lang.org/");
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    bookmarks::insert_new_entries(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+    )
+    .unwrap();
+
+    let mut statement = target_conn
+        .prepare(
+            "select b.type, b.fk, b.position
+            from moz_bookmarks b
+            join moz_bookmarks f on f.id = b.parent
+            where f.guid = 'workfolder01'
+            order by b.position",
+        )
+        .unwrap();
+    let children: Vec<(i64, Option<i64>, i64)> = statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|child| child.unwrap())
+        .collect();
+    assert_eq!(children.len(), 3);
+    assert_eq!(
+        children
+            .iter()
+            .map(|(kind, _, position)| (*kind, *position))
+            .collect::<Vec<_>>(),
+        vec![
+            (bookmarks::TYPE_BOOKMARK, 0),
+            (bookmarks::TYPE_SEPARATOR, 1),
+            (bookmarks::TYPE_BOOKMARK, 2)
+        ]
+    );
+    assert!(children[0].1.is_some());
+    assert_eq!(children[1].1, None);
+    assert!(children[2].1.is_some());
+}
@@ -0,0 +1,78 @@
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fftemplates::session;
+
+fn write_session(dir: &Path, session: Value) -> PathBuf {
+    let path = dir.join("session.jsonlz4");
+    fs::write(
+        &path,
+        session::compress_session(&serde_json::to_vec(&session).unwrap()),
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn well_formed_session_has_no_warnings() {
+    let dir = TempDir::new().unwrap();
+    let tab =
+        json!({"entries": [{"url": "https://a.com/"}, {"url": "https://b.com/"}], "index": 2});
+    let path = write_session(
+        dir.path(),
+        json!({"windows": [{"tabs": [tab.clone(), tab]}], "selectedWindow": 1}),
+    );
+
+    let report = session::validate_session(&path).unwrap();
+
+    assert_eq!(report.windows, 1);
+    assert_eq!(report.tabs, 2);
+    assert!(report.warnings.is_empty());
+}
+
+#[test]
+fn reports_what_would_not_be_restored() {
+    let dir = TempDir::new().unwrap();
+    let path = write_session(
+        dir.path(),
+        json!({
+            "windows": [
+                {"tabs": [
+                    {"entries": [], "index": 1},
+                    {"entries": [{"url": "https://a.com/"}], "index": 3},
+                    {"index": 1},
+                ]},
+                {"tabs": []},
+                {},
+            ],
+            "selectedWindow": 4,
+        }),
+    );
+
+    let report = session::validate_session(&path).unwrap();
+
+    assert_eq!(report.windows, 3);
+    assert_eq!(report.tabs, 3);
+    assert_eq!(
+        report.warnings,
+        vec![
+            "tab 1 of window 1 has no entries",
+            "tab 2 of window 1 has `index` 3 out of range of 1 entries",
+            "tab 3 of window 1 has no `entries` list",
+            "window 2 is empty",
+            "window 3 has no `tabs` list",
+            "`selectedWindow` 4 is out of range of 3 windows",
+        ]
+    );
+}
+
+#[test]
+fn session_without_windows_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    let path = write_session(dir.path(), json!({"selectedWindow": 1}));
+
+    assert!(session::validate_session(&path).is_err());
+}
@@ -0,0 +1,144 @@
+use serde_json::json;
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use fftemplates::session;
+
+// writes a mozlz4 session where every tab has a single entry
+fn write_session(dir: &Path, name: &str, windows: &[&[&str]]) -> PathBuf {
+    let windows: Vec<_> = windows
+        .iter()
+        .map(|tabs| {
+            let tabs: Vec<_> = tabs
+                .iter()
+                .map(|url| json!({"entries": [{"url": url}], "index": 1}))
+                .collect();
+            json!({ "tabs": tabs })
+        })
+        .collect();
+    let json = serde_json::to_vec(&json!({ "windows": windows })).unwrap();
+
+    let path = dir.join(name);
+    fs::write(&path, session::compress_session(&json)).unwrap();
+    path
+}
+
+#[test]
+fn reports_added_and_removed_tabs() {
+    let dir = TempDir::new().unwrap();
+    let a = write_session(dir.path(), "a", &[&["https://a.com/", "https://b.com/"]]);
+    let b = write_session(dir.path(), "b", &[&["https://a.com/", "https://c.com/"]]);
+
+    let diff = session::diff_sessions(&a, &b).unwrap();
+
+    assert_eq!(diff.windows.len(), 1);
+    assert_eq!(diff.windows[0].added, vec!["https://c.com/"]);
+    assert_eq!(diff.windows[0].removed, vec!["https://b.com/"]);
+    assert!(!diff.windows[0].reordered);
+}
+
+#[test]
+fn reports_reordered_tabs() {
+    let dir = TempDir::new().unwrap();
+    let a = write_session(dir.path(), "a", &[&["https://a.com/", "https://b.com/"]]);
+    let b = write_session(dir.path(), "b", &[&["https://b.com/", "https://a.com/"]]);
+
+    let diff = session::diff_sessions(&a, &b).unwrap();
+
+    assert!(diff.windows[0].added.is_empty());
+    assert!(diff.windows[0].removed.is_empty());
+    assert!(diff.windows[0].reordered);
+}
+
+#[test]
+fn surplus_windows_are_wholly_added_or_removed() {
+    let dir = TempDir::new().unwrap();
+    let a = write_session(dir.path(), "a", &[&["https://a.com/"]]);
+    let b = write_session(
+        dir.path(),
+        "b",
+        &[&["https://a.com/"], &["https://x.com/", "https://x.com/"]],
+    );
+
+    let added = session::diff_sessions(&a, &b).unwrap();
+    let removed = session::diff_sessions(&b, &a).unwrap();
+
+    assert!(added.windows[0].is_empty());
+    assert_eq!(
+        added.windows[1].added,
+        vec!["https://x.com/", "https://x.com/"]
+    );
+    assert_eq!(
+        removed.windows[1].removed,
+        vec!["https://x.com/", "https://x.com/"]
+    );
+}
+
+#[test]
+fn same_sessions_are_empty_diff() {
+    let dir = TempDir::new().unwrap();
+    let a = write_session(dir.path(), "a", &[&["https://a.com/"], &[]]);
+
+    assert!(session::diff_sessions(&a, &a).unwrap().is_empty());
+}
+
+#[test]
+fn unchanged_session_is_not_saved() {
+    let dir = TempDir::new().unwrap();
+    let profile = dir.path().join("profile");
+    fs::create_dir(&profile).unwrap();
+    write_session(
+        &profile,
+        session::SESSIONSTORE_DEFAULT_NAME,
+        &[&["https://a.com/"]],
+    );
+    // same tabs, only details other than urls differ
+    let saved = dir.path().join("saved.jsonlz4");
+    let json = json!({"windows": [{"tabs": [{"entries": [{"url": "https://a.com/", "title": "A"}], "index": 1}]}]});
+    fs::write(
+        &saved,
+        session::compress_session(&serde_json::to_vec(&json).unwrap()),
+    )
+    .unwrap();
+    let before = fs::read(&saved).unwrap();
+
+    let stored = session::save_changed_sessionstore_file(
+        saved.to_str().unwrap(),
+        profile.to_str().unwrap(),
+        None,
+    )
+    .unwrap();
+
+    assert!(!stored);
+    assert_eq!(fs::read(&saved).unwrap(), before);
+}
+
+#[test]
+fn changed_or_unreadable_session_is_saved() {
+    let dir = TempDir::new().unwrap();
+    let profile = dir.path().join("profile");
+    fs::create_dir(&profile).unwrap();
+    let current = write_session(
+        &profile,
+        session::SESSIONSTORE_DEFAULT_NAME,
+        &[&["https://a.com/", "https://b.com/"]],
+    );
+    let saved = write_session(dir.path(), "saved.jsonlz4", &[&["https://a.com/"]]);
+    let broken = dir.path().join("broken.jsonlz4");
+    fs::write(&broken, b"not a session").unwrap();
+
+    for file in [&saved, &broken] {
+        let stored = session::save_changed_sessionstore_file(
+            file.to_str().unwrap(),
+            profile.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+
+        assert!(stored);
+        assert_eq!(fs::read(file).unwrap(), fs::read(&current).unwrap());
+    }
+}
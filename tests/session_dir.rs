@@ -0,0 +1,34 @@
+use filetime::FileTime;
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::session;
+
+#[test]
+fn picks_most_recent_jsonlz4() {
+    let dir = TempDir::new().unwrap();
+    for (name, mtime) in &[
+        ("old.jsonlz4", 1_000),
+        ("new.jsonlz4", 3_000),
+        ("middle.jsonlz4", 2_000),
+        // newest, but not a session
+        ("notes.txt", 4_000),
+    ] {
+        let path = dir.path().join(name);
+        fs::write(&path, b"mozLz40\0").unwrap();
+        filetime::set_file_mtime(&path, FileTime::from_unix_time(*mtime, 0)).unwrap();
+    }
+
+    assert_eq!(
+        session::latest_session_in(dir.path()).unwrap(),
+        Some(dir.path().join("new.jsonlz4"))
+    );
+}
+
+#[test]
+fn empty_folder_has_no_session() {
+    let dir = TempDir::new().unwrap();
+
+    assert_eq!(session::latest_session_in(dir.path()).unwrap(), None);
+}
@@ -0,0 +1,126 @@
+use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::session;
+use fftemplates::session::SessionLoadMode;
+
+const JSON: &[u8] = br#"{"windows":[{"tabs":[{"entries":[{"url":"https://a.com/"}],"index":1}]}]}"#;
+
+#[test]
+fn mozlz4_round_trip() {
+    let compressed = session::compress_session(JSON);
+
+    assert!(compressed.starts_with(b"mozLz40\0"));
+    assert_eq!(session::decompress_session(&compressed).unwrap(), JSON);
+}
+
+#[test]
+fn reads_zstd_frame() {
+    let compressed = compress_to_vec(JSON, CompressionLevel::Fastest);
+
+    assert_eq!(session::decompress_session(&compressed).unwrap(), JSON);
+}
+
+#[test]
+fn reads_raw_json() {
+    let mut raw = b"\n  ".to_vec();
+    raw.extend_from_slice(JSON);
+
+    assert_eq!(session::decompress_session(&raw).unwrap(), raw);
+}
+
+#[test]
+fn rejects_unknown_format() {
+    assert!(session::decompress_session(b"not a session").is_err());
+    assert!(session::decompress_session(b"mozLz40\0").is_err());
+}
+
+#[test]
+fn plain_json_session_is_compressed_on_load() {
+    let dir = TempDir::new().unwrap();
+    let profile = TempDir::new().unwrap();
+    let source = dir.path().join("work.json");
+    fs::write(&source, JSON).unwrap();
+
+    let target = session::add_sessionstore_file(
+        source.to_str().unwrap(),
+        profile.path().to_str().unwrap(),
+        None,
+        true,
+        SessionLoadMode::Replace,
+    )
+    .unwrap()
+    .unwrap();
+
+    let bytes = fs::read(&target).unwrap();
+    assert!(bytes.starts_with(b"mozLz40\0"));
+    assert_eq!(session::decompress_session(&bytes).unwrap(), JSON);
+}
+
+#[test]
+fn rejects_plain_json_without_windows() {
+    let dir = TempDir::new().unwrap();
+    let profile = TempDir::new().unwrap();
+    let source = dir.path().join("broken.json");
+    fs::write(&source, br#"{"tabs":[]}"#).unwrap();
+
+    let e = session::add_sessionstore_file(
+        source.to_str().unwrap(),
+        profile.path().to_str().unwrap(),
+        None,
+        true,
+        SessionLoadMode::Replace,
+    )
+    .unwrap_err();
+
+    assert!(e.to_string().contains("`windows` list is missing"), "{}", e);
+}
+
+#[test]
+fn export_and_import_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let compressed = dir.path().join("work.jsonlz4");
+    fs::write(&compressed, session::compress_session(JSON)).unwrap();
+    let exported = dir.path().join("work.json");
+    let imported = dir.path().join("imported.jsonlz4");
+
+    session::export_session(&compressed, &exported).unwrap();
+    session::import_session(&exported, &imported).unwrap();
+
+    let pretty = fs::read_to_string(&exported).unwrap();
+    assert!(pretty.contains("\n  \"windows\": ["), "{}", pretty);
+    assert_eq!(
+        session::decompress_session(&fs::read(&imported).unwrap()).unwrap(),
+        JSON
+    );
+}
+
+#[test]
+fn exports_plain_json_session() {
+    let dir = TempDir::new().unwrap();
+    let plain = dir.path().join("plain.json");
+    fs::write(&plain, JSON).unwrap();
+    let exported = dir.path().join("pretty.json");
+
+    session::export_session(&plain, &exported).unwrap();
+
+    let exported: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&exported).unwrap()).unwrap();
+    let original: serde_json::Value = serde_json::from_slice(JSON).unwrap();
+    assert_eq!(exported, original);
+}
+
+#[test]
+fn export_rejects_non_session() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("notes.txt");
+    fs::write(&input, "not a session").unwrap();
+    let output = dir.path().join("out.json");
+
+    let e = session::export_session(&input, &output).unwrap_err();
+
+    assert!(e.to_string().contains("is not a session file"), "{}", e);
+    assert!(!output.exists());
+}
@@ -0,0 +1,79 @@
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::session;
+use fftemplates::session::SessionLoadMode;
+
+fn window(urls: &[&str]) -> Value {
+    let tabs: Vec<Value> = urls
+        .iter()
+        .map(|url| json!({"entries": [{"url": url}], "index": 1}))
+        .collect();
+    json!({"tabs": tabs, "selected": tabs.len()})
+}
+
+// loads a session with the mode into a profile that has its own session
+fn load(mode: SessionLoadMode) -> Vec<Vec<String>> {
+    let dir = TempDir::new().unwrap();
+    let profile = TempDir::new().unwrap();
+    let own = json!({"windows": [window(&["https://home.example.com/"])]});
+    fs::write(
+        profile.path().join("sessionstore.jsonlz4"),
+        session::compress_session(own.to_string().as_bytes()),
+    )
+    .unwrap();
+    let loaded = json!({"windows": [
+        window(&["https://home.example.com/", "https://docs.example.com/"]),
+        window(&["https://home.example.com/"]),
+    ]});
+    let source = dir.path().join("work.json");
+    fs::write(&source, loaded.to_string()).unwrap();
+
+    let target = session::add_sessionstore_file(
+        source.to_str().unwrap(),
+        profile.path().to_str().unwrap(),
+        None,
+        true,
+        mode,
+    )
+    .unwrap()
+    .unwrap();
+
+    session::read_session_urls(target).unwrap()
+}
+
+#[test]
+fn replace_overwrites_profile_session() {
+    assert_eq!(
+        load(SessionLoadMode::Replace),
+        vec![
+            vec!["https://home.example.com/", "https://docs.example.com/"],
+            vec!["https://home.example.com/"],
+        ]
+    );
+}
+
+#[test]
+fn append_adds_windows() {
+    assert_eq!(
+        load(SessionLoadMode::Append),
+        vec![
+            vec!["https://home.example.com/"],
+            vec!["https://home.example.com/", "https://docs.example.com/"],
+            vec!["https://home.example.com/"],
+        ]
+    );
+}
+
+#[test]
+fn merge_skips_open_tabs() {
+    assert_eq!(
+        load(SessionLoadMode::Merge),
+        vec![
+            vec!["https://home.example.com/"],
+            vec!["https://docs.example.com/"],
+        ]
+    );
+}
@@ -0,0 +1,254 @@
+use fftemplates::session;
+
+const PREFS: &str = r#"// Mozilla User Preferences
+user_pref("browser.startup.homepage", "https://example.com/(home)");
+user_pref("places.history.enabled", false);
+user_pref("privacy.sanitize.sanitizeOnShutdown", true);
+"#;
+
+#[test]
+fn ensure_pref_replaces_existing_value() {
+    let content = session::ensure_pref(PREFS, "places.history.enabled", "true").unwrap();
+
+    assert!(content.contains(r#"user_pref("places.history.enabled", true);"#));
+    assert!(!content.contains(r#"user_pref("places.history.enabled", false);"#));
+    assert_eq!(content.lines().count(), PREFS.lines().count());
+}
+
+#[test]
+fn ensure_pref_appends_missing_pref() {
+    let content = session::ensure_pref(PREFS, "browser.startup.page", "3").unwrap();
+
+    assert!(content.starts_with(PREFS));
+    assert!(content.ends_with("user_pref(\"browser.startup.page\", 3);\n"));
+}
+
+#[test]
+fn ensure_pref_replaces_string_values() {
+    let content =
+        session::ensure_pref(PREFS, "browser.startup.homepage", r#""about:blank""#).unwrap();
+
+    assert!(content.contains(r#"user_pref("browser.startup.homepage", "about:blank");"#));
+    assert!(!content.contains("(home)"));
+}
+
+#[test]
+fn ensure_pref_twice_is_unchanged() {
+    let prefs = [
+        ("places.history.enabled", "true"),
+        ("privacy.sanitize.sanitizeOnShutdown", "false"),
+        ("browser.startup.page", "3"),
+        ("browser.startup.homepage", r#""about:home""#),
+    ];
+    for (name, value) in prefs.iter() {
+        let once = session::ensure_pref(PREFS, name, value).unwrap();
+        let twice = session::ensure_pref(&once, name, value).unwrap();
+
+        assert_eq!(once, twice);
+    }
+}
+
+#[test]
+fn is_pref_set_checks_only_given_name() {
+    assert!(session::is_pref_set(PREFS, "places.history.enabled").unwrap());
+    assert!(!session::is_pref_set(PREFS, "places.history").unwrap());
+    assert!(!session::is_pref_set(PREFS, "browser.startup.page").unwrap());
+}
+
+const OVERLAY: &str = r#"// applied to every template
+user_pref("toolkit.telemetry.enabled", false);
+user_pref("places.history.enabled", true);
+"#;
+
+fn write_overlay(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let path = dir.path().join(session::OVERLAY_FILE_NAME);
+    std::fs::write(&path, OVERLAY).unwrap();
+    path
+}
+
+#[test]
+fn overlay_appends_only_missing_prefs() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut content = PREFS.to_string();
+
+    let applied = session::apply_overlay_prefs(&mut content, &write_overlay(&dir), false).unwrap();
+
+    assert_eq!(applied, vec!["toolkit.telemetry.enabled"]);
+    assert!(content.contains(r#"user_pref("toolkit.telemetry.enabled", false);"#));
+    assert!(content.contains(r#"user_pref("places.history.enabled", false);"#));
+}
+
+#[test]
+fn overlay_overrides_existing_prefs() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut content = PREFS.to_string();
+
+    let applied = session::apply_overlay_prefs(&mut content, &write_overlay(&dir), true).unwrap();
+
+    assert_eq!(applied.len(), 2);
+    assert!(content.contains(r#"user_pref("places.history.enabled", true);"#));
+    assert!(!content.contains(r#"user_pref("places.history.enabled", false);"#));
+}
+
+#[test]
+fn missing_overlay_is_noop() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut content = PREFS.to_string();
+
+    let applied =
+        session::apply_overlay_prefs(&mut content, &dir.path().join("missing.js"), true).unwrap();
+
+    assert!(applied.is_empty());
+    assert_eq!(content, PREFS);
+}
+
+#[test]
+fn replace_pref_bool_cases() {
+    let line = r#"user_pref("places.history.enabled", true);"#;
+    let cases = [
+        ("present and true", "user_pref(\"places.history.enabled\", true);\n"),
+        ("present and false", "user_pref(\"places.history.enabled\", false);\n"),
+        ("absent", "user_pref(\"browser.startup.page\", 3);\n"),
+        (
+            "present multiple times",
+            "user_pref(\"places.history.enabled\", false);\nuser_pref(\"places.history.enabled\", false);\n",
+        ),
+    ];
+    for (case, content) in cases.iter() {
+        let replaced = session::replace_pref_bool(content, "places.history.enabled", true);
+
+        assert!(replaced.contains(line), "{}", case);
+        assert!(!replaced.contains("enabled\", false"), "{}", case);
+        assert_eq!(
+            session::replace_pref_bool(&replaced, "places.history.enabled", true),
+            replaced,
+            "{}",
+            case
+        );
+    }
+}
+
+#[test]
+fn replace_pref_int_cases() {
+    let cases = [
+        ("present", "user_pref(\"browser.startup.page\", 1);\n"),
+        ("absent", ""),
+        (
+            "present multiple times",
+            "user_pref(\"browser.startup.page\", 1);\nuser_pref(\"browser.startup.page\", 0);\n",
+        ),
+    ];
+    for (case, content) in cases.iter() {
+        let replaced = session::replace_pref_int(content, "browser.startup.page", 3);
+
+        assert!(
+            replaced.contains(r#"user_pref("browser.startup.page", 3);"#),
+            "{}",
+            case
+        );
+        assert!(!replaced.contains("page\", 1"), "{}", case);
+        assert!(!replaced.contains("page\", 0"), "{}", case);
+    }
+}
+
+#[test]
+fn replace_pref_string_quotes_value() {
+    let cases = [
+        ("plain", "about:blank", r#""about:blank""#),
+        ("quotes", r#"say "hi""#, r#""say \"hi\"""#),
+        ("backslash", r"C:\profiles", r#""C:\\profiles""#),
+    ];
+    for (case, value, written) in cases.iter() {
+        let replaced = session::replace_pref_string(PREFS, "browser.startup.homepage", value);
+
+        assert!(
+            replaced.contains(&format!(
+                "user_pref(\"browser.startup.homepage\", {});",
+                written
+            )),
+            "{} : {}",
+            case,
+            replaced
+        );
+        assert!(!replaced.contains("(home)"), "{}", case);
+    }
+}
+
+#[test]
+fn diff_prefs_lists_changed_added_and_removed_prefs() {
+    let adjusted = format!(
+        "{}user_pref(\"browser.startup.page\", 3);\n",
+        PREFS
+            .replace("enabled\", false", "enabled\", true")
+            .replace(
+                "user_pref(\"privacy.sanitize.sanitizeOnShutdown\", true);\n",
+                ""
+            )
+    );
+
+    let changes = session::diff_prefs(PREFS, &adjusted).unwrap();
+
+    let change = |name: &str, old: Option<&str>, new: Option<&str>| session::PrefChange {
+        name: name.to_string(),
+        old: old.map(|old| old.to_string()),
+        new: new.map(|new| new.to_string()),
+    };
+    assert_eq!(
+        changes,
+        vec![
+            change("places.history.enabled", Some("false"), Some("true")),
+            change("browser.startup.page", None, Some("3")),
+            change("privacy.sanitize.sanitizeOnShutdown", Some("true"), None),
+        ]
+    );
+    assert!(session::diff_prefs(PREFS, PREFS).unwrap().is_empty());
+}
+
+#[test]
+fn read_prefs_keeps_last_value() {
+    let content = format!("{}user_pref(\"places.history.enabled\", true);\n", PREFS);
+
+    let prefs = session::read_prefs(&content).unwrap();
+
+    assert_eq!(prefs.len(), 3);
+    assert_eq!(
+        prefs[1],
+        ("places.history.enabled".to_string(), "true".to_string())
+    );
+    assert_eq!(prefs[0].1, r#""https://example.com/(home)""#);
+}
+
+#[test]
+fn session_restore_replaces_configured_startup_page() {
+    for value in ["0", "1"].iter() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let prefs = dir.path().join(session::PROFILE_FILE_NAME);
+        std::fs::write(
+            &prefs,
+            format!("user_pref(\"browser.startup.page\", {});\n", value),
+        )
+        .unwrap();
+
+        let adjusted =
+            session::adjust_profile_settings(dir.path().to_str().unwrap(), false, true).unwrap();
+
+        let content = std::fs::read_to_string(&prefs).unwrap();
+        assert!(content.contains(r#"user_pref("browser.startup.page", 3);"#));
+        assert_eq!(content.matches("browser.startup.page").count(), 1);
+        assert!(adjusted.contains(&"browser.startup.page".to_string()));
+    }
+}
+
+#[test]
+fn configured_startup_page_is_kept_without_session() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let prefs = dir.path().join(session::PROFILE_FILE_NAME);
+    std::fs::write(&prefs, "user_pref(\"browser.startup.page\", 1);\n").unwrap();
+
+    let adjusted =
+        session::adjust_profile_settings(dir.path().to_str().unwrap(), false, false).unwrap();
+
+    let content = std::fs::read_to_string(&prefs).unwrap();
+    assert!(content.contains(r#"user_pref("browser.startup.page", 1);"#));
+    assert!(!adjusted.contains(&"browser.startup.page".to_string()));
+}
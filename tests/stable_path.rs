@@ -0,0 +1,105 @@
+#![cfg(unix)]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::fs::File;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// browser that appends the profile it got to `launched`, and its files to `listing`
+fn fake_browser(dir: &Path) -> PathBuf {
+    let browser = dir.join("firefox");
+    fs::write(
+        &browser,
+        format!(
+            "#!/bin/sh\necho \"$2\" >> {0}/launched\nls \"$2\" >> {0}/listing\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    browser
+}
+
+fn launch(profile: &Path, home: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile)
+        .arg("--browser")
+        .arg(fake_browser(home))
+        .arg("--stable-path")
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .output()
+        .unwrap()
+}
+
+fn launched(home: &Path) -> Vec<PathBuf> {
+    fs::read_to_string(home.join("launched"))
+        .unwrap()
+        .lines()
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn create_profile() -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    profile
+}
+
+#[test]
+fn profile_is_launched_from_the_same_path() {
+    let profile = create_profile();
+    let home = TempDir::new().unwrap();
+
+    assert!(launch(profile.path(), home.path()).status.success());
+    assert!(launch(profile.path(), home.path()).status.success());
+
+    let paths = launched(home.path());
+    assert_eq!(paths.len(), 2);
+    assert_eq!(paths[0], paths[1]);
+    assert!(paths[0].starts_with(home.path().join("cache").join("fftemplates").join("stable")));
+    // still ephemeral, nothing is left after the run
+    assert!(!paths[0].exists());
+}
+
+#[test]
+fn leftovers_of_a_previous_launch_are_removed() {
+    let profile = create_profile();
+    let home = TempDir::new().unwrap();
+    assert!(launch(profile.path(), home.path()).status.success());
+    let stable = launched(home.path()).remove(0);
+    fs::create_dir_all(&stable).unwrap();
+    fs::write(stable.join("scratch.txt"), "").unwrap();
+
+    assert!(launch(profile.path(), home.path()).status.success());
+
+    let listing = fs::read_to_string(home.path().join("listing")).unwrap();
+    assert!(listing.contains("prefs.js"));
+    assert!(!listing.contains("scratch.txt"));
+    assert!(!stable.exists());
+}
+
+#[test]
+fn launch_of_a_name_in_use_gets_a_unique_path() {
+    let profile = create_profile();
+    let home = TempDir::new().unwrap();
+    assert!(launch(profile.path(), home.path()).status.success());
+    let stable = launched(home.path()).remove(0);
+    let mut lock_name = stable.file_name().unwrap().to_os_string();
+    lock_name.push(".lock");
+    // as another launch holds it
+    let lock = File::open(stable.with_file_name(lock_name)).unwrap();
+    lock.lock().unwrap();
+
+    let output = launch(profile.path(), home.path());
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("used by another launch"));
+    let paths = launched(home.path());
+    assert_ne!(paths[1], stable);
+    assert!(paths[1].starts_with(std::env::temp_dir()));
+}
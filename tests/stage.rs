@@ -0,0 +1,95 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn launch(profile: &Path, stage: &Path) -> Output {
+    launch_with(profile, stage, &[])
+}
+
+fn launch_with(profile: &Path, stage: &Path, extra_args: &[&str]) -> Output {
+    let config = TempDir::new().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile)
+        .arg("--browser")
+        .arg("true")
+        .arg("--stage")
+        .arg(stage)
+        .args(extra_args)
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap()
+}
+
+fn create_profile() -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    fs::write(
+        profile.path().join("extensions.json"),
+        r#"{"addons":[{"path":"/old/profile/extensions/a@b.xpi"}]}"#,
+    )
+    .unwrap();
+    profile
+}
+
+#[test]
+fn staged_profile_is_reused_until_base_changes() {
+    let profile = create_profile();
+    let dir = TempDir::new().unwrap();
+    let stage = dir.path().join("scratch");
+
+    assert!(launch(profile.path(), &stage).status.success());
+    assert!(stage.join("prefs.js").exists());
+    let extensions = fs::read_to_string(stage.join("extensions.json")).unwrap();
+    assert!(extensions.contains(&stage.join("extensions/a@b.xpi").display().to_string()));
+
+    // left by the previous launch, like anything firefox writes there
+    fs::write(stage.join("scratch.txt"), "").unwrap();
+    let output = launch(profile.path(), &stage);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("reusing staged profile"));
+    assert!(stage.join("scratch.txt").exists());
+    let reused = fs::read_to_string(stage.join("extensions.json")).unwrap();
+    assert_eq!(reused, extensions);
+
+    fs::write(profile.path().join("user.js"), "").unwrap();
+    let output = launch(profile.path(), &stage);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Refreshing staged profile"));
+    assert!(!stage.join("scratch.txt").exists());
+    assert!(stage.join("user.js").exists());
+    let extensions = fs::read_to_string(stage.join("extensions.json")).unwrap();
+    assert!(extensions.contains(&stage.join("extensions/a@b.xpi").display().to_string()));
+}
+
+#[test]
+fn folder_that_was_not_staged_is_left_alone() {
+    let profile = create_profile();
+    let stage = TempDir::new().unwrap();
+    fs::write(stage.path().join("notes.txt"), "keep me").unwrap();
+
+    let output = launch(profile.path(), stage.path());
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("isn't a staged profile"));
+    assert_eq!(
+        fs::read_to_string(stage.path().join("notes.txt")).unwrap(),
+        "keep me"
+    );
+    assert!(!stage.path().join("prefs.js").exists());
+}
+
+#[test]
+fn failed_launch_leaves_no_partial_stage() {
+    let profile = create_profile();
+    let dir = TempDir::new().unwrap();
+    let stage = dir.path().join("scratch");
+    let missing = dir.path().join("missing.jsonlz4");
+
+    let output = launch_with(profile.path(), &stage, &["-l", missing.to_str().unwrap()]);
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("doesn't exist"));
+    assert!(!stage.exists());
+    // so the next launch doesn't take it for a folder that wasn't staged
+    assert!(launch(profile.path(), &stage).status.success());
+    assert!(stage.join("prefs.js").exists());
+}
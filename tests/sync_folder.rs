@@ -0,0 +1,77 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+
+use tempfile::TempDir;
+
+#[test]
+fn syncs_only_bookmarks_under_the_folder() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_folder(&conn, 7, Some(3), 0, "Team", "teamfolder__");
+    common::insert_folder(&conn, 8, Some(3), 1, "Personal", "personalfold");
+    let folder = profile.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+
+    common::insert_link(&conn, 9, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&conn, 10, 8, 0, "News", "https://news.example.com/");
+    common::insert_folder(&conn, 11, Some(7), 1, "Nested", "nestedfolder");
+    common::insert_link(&conn, 12, 11, 0, "Wiki", "https://wiki.example.com/");
+
+    let filter = EntryFilter {
+        folder: Some("Team".to_string()),
+        ..EntryFilter::default()
+    };
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(folder, &latest, &filter).unwrap();
+
+    let ids: Vec<i64> = new_bookmarks.unwrap().iter().map(|b| b.id).collect();
+    assert_eq!(ids, vec![9, 11, 12]);
+    let mut urls: Vec<String> = new_places
+        .unwrap()
+        .values()
+        .map(|p| p.url.clone().unwrap())
+        .collect();
+    urls.sort();
+    assert_eq!(
+        urls,
+        vec!["https://docs.example.com/", "https://wiki.example.com/"]
+    );
+    assert_eq!(new_origins.unwrap().len(), 2);
+}
+
+#[test]
+fn syncs_everything_without_folder() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_folder(&conn, 7, Some(3), 0, "Team", "teamfolder__");
+    let folder = profile.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+
+    common::insert_link(&conn, 8, 7, 0, "Docs", "https://docs.example.com/");
+    common::insert_link(&conn, 9, 3, 1, "News", "https://news.example.com/");
+
+    let (new_bookmarks, _, _) =
+        bookmarks::get_new_entries(folder, &latest, &EntryFilter::default()).unwrap();
+
+    assert_eq!(new_bookmarks.unwrap().len(), 2);
+}
+
+#[test]
+fn fails_for_unknown_folder() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    let folder = profile.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+    common::insert_link(&conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+
+    let filter = EntryFilter {
+        folder: Some("Missing".to_string()),
+        ..EntryFilter::default()
+    };
+
+    assert!(bookmarks::get_new_entries(folder, &latest, &filter).is_err());
+}
@@ -0,0 +1,369 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use std::collections::HashMap;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+use fftemplates::cli::ConflictPolicy;
+use fftemplates::store::SqlitePlacesStore;
+use fftemplates::sync;
+use fftemplates::sync::SyncStep;
+
+fn plan(source: &TempDir, target: &Connection, add: impl Fn(&Connection)) -> sync::SyncPlan {
+    plan_with(source, target, ConflictPolicy::Skip, add)
+}
+
+fn plan_with(
+    source: &TempDir,
+    target: &Connection,
+    on_conflict: ConflictPolicy,
+    add: impl Fn(&Connection),
+) -> sync::SyncPlan {
+    let source_conn = common::create_places(source.path());
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    add(&source_conn);
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    sync::plan_sync_into(
+        &SqlitePlacesStore::new(target),
+        new_bookmarks.as_deref().unwrap_or_default(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+        None,
+        on_conflict,
+    )
+    .unwrap()
+}
+
+#[test]
+fn reuses_existing_origins() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan(&source, &target_conn, |conn| {
+        common::insert_link(conn, 8, 3, 0, "Docs", "https://example.com/docs");
+        common::insert_link(conn, 9, 3, 1, "Rust", "https://rust-lang.org/");
+    });
+
+    assert_eq!(plan.inserted_origins(), 1);
+    assert_eq!(plan.reused_origins(), 1);
+    assert_eq!(plan.inserted_places(), 2);
+    assert_eq!(plan.inserted_bookmarks(), 2);
+    assert_eq!(
+        plan.to_string(),
+        "Will insert 1 origins, 2 places, 2 bookmarks; reuse 1 existing origins"
+    );
+    assert!(plan.steps.contains(&SyncStep::ReuseOrigin {
+        source_id: 1,
+        target_id: 1
+    }));
+}
+
+#[test]
+fn planning_does_not_write() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+
+    let plan = plan(&source, &target_conn, |conn| {
+        common::insert_link(conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+    });
+    let count = |table: &str| -> i64 {
+        target_conn
+            .query_row(
+                &format!("select count(*) from {}", table),
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(count("moz_bookmarks"), 6);
+    assert_eq!(count("moz_places"), 0);
+
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+    assert_eq!(count("moz_bookmarks"), 7);
+    assert_eq!(count("moz_places"), 1);
+    assert_eq!(count("moz_origins"), 1);
+}
+
+#[test]
+fn remaps_ids_after_existing_rows() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let source_conn = common::create_places(source.path());
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+    common::insert_separator(&target_conn, 20, 3, 1);
+
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    common::insert_link(&source_conn, 8, 3, 0, "Docs", "https://example.com/docs");
+    common::insert_link(&source_conn, 9, 3, 1, "Rust", "https://rust-lang.org/");
+    let entries =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+
+    let remaps = sync::plan_id_remaps(&SqlitePlacesStore::new(&target_conn), &entries).unwrap();
+    let map = |pairs: &[(i64, i64)]| pairs.iter().cloned().collect::<HashMap<_, _>>();
+    assert_eq!(remaps.bookmarks, map(&[(8, 21), (9, 22)]));
+    assert_eq!(remaps.places, map(&[(1, 2), (2, 3)]));
+    // example.com exists in the target already
+    assert_eq!(remaps.origins, map(&[(1, 1), (2, 2)]));
+}
+
+#[test]
+fn reuses_place_with_the_same_url() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    // bookmarked in both profiles on its own, so guids of the places differ
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan(&source, &target_conn, |conn| {
+        common::insert_link(conn, 8, 3, 0, "Example", "https://example.com/");
+    });
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    assert_eq!(plan.inserted_places(), 0);
+    assert_eq!(plan.reused_places(), 1);
+    let places: i64 = target_conn
+        .query_row("select count(*) from moz_places", params![], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(places, 1);
+    let fk: i64 = target_conn
+        .query_row(
+            "select fk from moz_bookmarks where guid = 'bookmark0008'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let existing: i64 = target_conn
+        .query_row(
+            "select fk from moz_bookmarks where guid = 'bookmark0007'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(fk, existing);
+}
+
+#[test]
+fn foreign_count_matches_references_in_target() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+    target_conn
+        .execute_batch(
+            "create table moz_keywords (id integer primary key, keyword text, place_id integer);
+            insert into moz_keywords (keyword, place_id) values ('ex', 1);
+            update moz_places set foreign_count = 2;",
+        )
+        .unwrap();
+
+    let plan = plan(&source, &target_conn, |conn| {
+        common::insert_link(conn, 8, 3, 0, "Example", "https://example.com/");
+        common::insert_link(conn, 9, 3, 1, "Rust", "https://rust-lang.org/");
+        // bookmarked more often in the source than it will be in the target
+        conn.execute("update moz_places set foreign_count = 5", params![])
+            .unwrap();
+    });
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    let foreign_count = |url: &str| -> i64 {
+        target_conn
+            .query_row(
+                "select foreign_count from moz_places where url = ?1",
+                params![url],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    // two bookmarks and a keyword
+    assert_eq!(foreign_count("https://example.com/"), 3);
+    assert_eq!(foreign_count("https://rust-lang.org/"), 1);
+}
+
+#[test]
+fn origin_frecency_is_derived_from_places_in_target() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+    target_conn
+        .execute("update moz_places set frecency = 100", params![])
+        .unwrap();
+
+    let plan = plan(&source, &target_conn, |conn| {
+        common::insert_link(conn, 8, 3, 0, "Example", "https://example.com/b");
+        common::insert_link(conn, 9, 3, 1, "Rust", "https://rust-lang.org/");
+        common::insert_link(conn, 10, 3, 2, "Book", "https://rust-lang.org/book");
+        conn.execute_batch(
+            "update moz_places set frecency = 50 where url = 'https://example.com/b';
+            update moz_places set frecency = 200 where url = 'https://rust-lang.org/';
+            update moz_places set frecency = -1 where url = 'https://rust-lang.org/book';
+            update moz_origins set frecency = 999;",
+        )
+        .unwrap();
+    });
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    let frecency = |host: &str| -> i64 {
+        target_conn
+            .query_row(
+                "select frecency from moz_origins where host = ?1",
+                params![host],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(frecency("example.com"), 150);
+    // places not ranked yet don't count
+    assert_eq!(frecency("rust-lang.org"), 200);
+}
+
+// bookmark synced before, renamed and pointed to another url in the source
+fn edit_synced_bookmark(conn: &Connection) {
+    common::insert_link(conn, 7, 3, 0, "Homepage", "https://example.com/home");
+    // firefox creates a new place for the new url
+    conn.execute("update moz_places set guid = 'placehome000'", params![])
+        .unwrap();
+}
+
+fn bookmark_urls(conn: &Connection) -> Vec<(String, String)> {
+    let mut statement = conn
+        .prepare(
+            "select b.title, p.url from moz_bookmarks b join moz_places p on p.id = b.fk
+            order by b.id",
+        )
+        .unwrap();
+    statement
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+}
+
+#[test]
+fn conflicting_bookmark_is_skipped_by_default() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan(&source, &target_conn, edit_synced_bookmark);
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    assert_eq!(plan.reused_bookmarks(), 1);
+    assert_eq!(plan.conflicts.len(), 1);
+    let conflict = &plan.conflicts[0];
+    assert_eq!(conflict.guid, "bookmark0007");
+    assert_eq!(conflict.title.as_deref(), Some("Homepage"));
+    assert_eq!(conflict.target_title.as_deref(), Some("Home"));
+    assert_eq!(conflict.url.as_deref(), Some("https://example.com/home"));
+    assert_eq!(conflict.target_url.as_deref(), Some("https://example.com/"));
+    assert_eq!(
+        bookmark_urls(&target_conn),
+        vec![("Home".to_string(), "https://example.com/".to_string())]
+    );
+}
+
+#[test]
+fn unchanged_bookmark_is_not_a_conflict() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan_with(&source, &target_conn, ConflictPolicy::Overwrite, |conn| {
+        common::insert_link(conn, 7, 3, 0, "Home", "https://example.com/");
+    });
+
+    assert!(plan.conflicts.is_empty());
+    assert_eq!(plan.reused_bookmarks(), 1);
+}
+
+#[test]
+fn conflicting_bookmark_is_overwritten() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan_with(
+        &source,
+        &target_conn,
+        ConflictPolicy::Overwrite,
+        edit_synced_bookmark,
+    );
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    assert_eq!(plan.updated_bookmarks(), 1);
+    assert_eq!(plan.conflicts.len(), 1);
+    assert_eq!(
+        bookmark_urls(&target_conn),
+        vec![(
+            "Homepage".to_string(),
+            "https://example.com/home".to_string()
+        )]
+    );
+    let foreign_count: i64 = target_conn
+        .query_row(
+            "select foreign_count from moz_places where url = 'https://example.com/'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(foreign_count, 0);
+}
+
+#[test]
+fn conflicting_bookmark_is_kept_next_to_the_target_one() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let plan = plan_with(
+        &source,
+        &target_conn,
+        ConflictPolicy::KeepBoth,
+        edit_synced_bookmark,
+    );
+    sync::apply_sync_plan(&SqlitePlacesStore::new(&target_conn), &plan).unwrap();
+
+    assert_eq!(plan.inserted_bookmarks(), 1);
+    assert_eq!(plan.conflicts.len(), 1);
+    assert_eq!(
+        bookmark_urls(&target_conn),
+        vec![
+            ("Home".to_string(), "https://example.com/".to_string()),
+            (
+                "Homepage".to_string(),
+                "https://example.com/home".to_string()
+            ),
+        ]
+    );
+    let guids: i64 = target_conn
+        .query_row(
+            "select count(distinct guid) from moz_bookmarks",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(guids, 8);
+}
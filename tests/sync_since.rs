@@ -0,0 +1,68 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+use fftemplates::timestamps;
+
+fn set_date_added(conn: &Connection, id: i64, date: &str) {
+    let date_added = timestamps::parse_firefox_timestamp(date).unwrap();
+    conn.execute(
+        "update moz_bookmarks set dateAdded = ?1 where id = ?2",
+        params![date_added, id],
+    )
+    .unwrap();
+}
+
+#[test]
+fn syncs_only_bookmarks_added_since() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    let folder = profile.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(folder).unwrap().unwrap();
+
+    common::insert_link(&conn, 7, 3, 0, "Old", "https://old.example.com/");
+    common::insert_folder(&conn, 8, Some(3), 1, "Team", "teamfolder__");
+    common::insert_link(&conn, 9, 8, 0, "Docs", "https://docs.example.com/");
+    set_date_added(&conn, 7, "2023-06-01");
+    set_date_added(&conn, 8, "2023-06-01");
+    set_date_added(&conn, 9, "2024-02-01T08:00:00Z");
+
+    let filter = EntryFilter {
+        since: Some(timestamps::parse_firefox_timestamp("2024-01-01").unwrap()),
+        ..EntryFilter::default()
+    };
+    let (new_bookmarks, new_places, _) =
+        bookmarks::get_new_entries(folder, &latest, &filter).unwrap();
+
+    // folder is older but kept, the new bookmark is inside it
+    let ids: Vec<i64> = new_bookmarks.unwrap().iter().map(|b| b.id).collect();
+    assert_eq!(ids, vec![8, 9]);
+    let urls: Vec<String> = new_places
+        .unwrap()
+        .values()
+        .map(|p| p.url.clone().unwrap())
+        .collect();
+    assert_eq!(urls, vec!["https://docs.example.com/"]);
+}
+
+#[test]
+fn merges_nothing_when_all_bookmarks_are_older() {
+    let profile = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_link(&conn, 7, 3, 0, "Old", "https://old.example.com/");
+    conn.execute("update moz_bookmarks set dateAdded = 0", params![])
+        .unwrap();
+
+    let filter = EntryFilter {
+        since: Some(timestamps::parse_firefox_timestamp("2024-01-01").unwrap()),
+        ..EntryFilter::default()
+    };
+    let entries = bookmarks::get_all_entries(profile.path().to_str().unwrap(), &filter).unwrap();
+
+    assert_eq!(entries, (None, None, None));
+}
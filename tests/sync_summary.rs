@@ -0,0 +1,53 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// stdout of a run with bookmarks sync, browser exits right away
+fn run_with_sync(profile: &Path) -> String {
+    let config = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile)
+        .arg("--browser")
+        .arg("true")
+        .arg("-b")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn sync_without_new_bookmarks_says_so() {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    let conn = common::create_places(profile.path());
+    common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+    drop(conn);
+
+    let stdout = run_with_sync(profile.path());
+
+    assert!(stdout.contains("No new bookmarks to sync"), "{}", stdout);
+}
+
+#[test]
+fn sync_without_places_says_it_was_skipped() {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+
+    let stdout = run_with_sync(profile.path());
+
+    assert!(
+        stdout.contains("Bookmark sync skipped (no Places database)"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,75 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use fftemplates::session;
+
+// base profile with a session, and a target profile with another one
+fn create_profiles(root: &Path) -> (Vec<u8>, Vec<u8>) {
+    let base = root.join("abcd1234.base");
+    let target = root.join("efgh5678.target");
+    fs::create_dir_all(&base).unwrap();
+    fs::create_dir_all(&target).unwrap();
+    fs::write(base.join("prefs.js"), "").unwrap();
+    let base_session = session::compress_session(br#"{"windows":[{"tabs":[]}]}"#);
+    let target_session = session::compress_session(br#"{"windows":[]}"#);
+    fs::write(base.join(session::SESSIONSTORE_DEFAULT_NAME), &base_session).unwrap();
+    fs::write(
+        target.join(session::SESSIONSTORE_DEFAULT_NAME),
+        &target_session,
+    )
+    .unwrap();
+    (base_session, target_session)
+}
+
+fn launch(root: &Path, config: &Path, extra_args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("base")
+        .arg("--profile-root")
+        .arg(root)
+        .arg("--browser")
+        .arg("true")
+        .arg("--sync-target")
+        .arg("target")
+        .args(extra_args)
+        .env("XDG_CONFIG_HOME", config)
+        .env("XDG_CACHE_HOME", config)
+        .output()
+        .unwrap()
+}
+
+fn target_session(root: &Path) -> Vec<u8> {
+    fs::read(
+        root.join("efgh5678.target")
+            .join(session::SESSIONSTORE_DEFAULT_NAME),
+    )
+    .unwrap()
+}
+
+#[test]
+fn saved_session_goes_into_the_target() {
+    let root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let (base_session, _) = create_profiles(root.path());
+    let saved = config.path().join("saved.jsonlz4");
+
+    let output = launch(root.path(), config.path(), &["-s", saved.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read(&saved).unwrap(), base_session);
+    assert_eq!(target_session(root.path()), base_session);
+}
+
+#[test]
+fn target_session_is_kept_without_a_save() {
+    let root = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let (_, target_session_before) = create_profiles(root.path());
+
+    let output = launch(root.path(), config.path(), &[]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(target_session(root.path()), target_session_before);
+}
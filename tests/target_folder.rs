@@ -0,0 +1,99 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use fftemplates::bookmarks;
+use fftemplates::bookmarks::EntryFilter;
+use fftemplates::cli::ConflictPolicy;
+
+fn sync_into(source: &TempDir, target: &TempDir, add: impl Fn(&Connection)) {
+    let source_conn = common::create_places(source.path());
+    let source_folder = source.path().to_str().unwrap();
+    let latest = bookmarks::get_latest_bookmark(source_folder)
+        .unwrap()
+        .unwrap();
+    add(&source_conn);
+
+    let (new_bookmarks, new_places, new_origins) =
+        bookmarks::get_new_entries(source_folder, &latest, &EntryFilter::default()).unwrap();
+    bookmarks::insert_new_entries_into(
+        target.path().to_str().unwrap(),
+        new_bookmarks.as_deref(),
+        new_places.as_ref(),
+        new_origins.as_ref(),
+        Some("Imported"),
+        ConflictPolicy::Skip,
+    )
+    .unwrap();
+}
+
+// parent id and position of the bookmark with the title
+fn parent_and_position(conn: &Connection, title: &str) -> (i64, i64) {
+    conn.query_row(
+        "select parent, position from moz_bookmarks where title = ?1",
+        params![title],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn creates_folder_and_keeps_structure_under_it() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_link(&target_conn, 20, 2, 0, "Home", "https://example.com/");
+
+    sync_into(&source, &target, |conn| {
+        common::insert_link(conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+        common::insert_folder(conn, 8, Some(2), 0, "Team", "teamfolder__");
+        common::insert_link(conn, 9, 8, 0, "Wiki", "https://wiki.example.com/");
+    });
+
+    // menu already has Home, so the folder is after it
+    let (menu, position) = parent_and_position(&target_conn, "Imported");
+    assert_eq!((menu, position), (2, 1));
+    let folder_id: i64 = target_conn
+        .query_row(
+            "select id from moz_bookmarks where title = 'Imported'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(parent_and_position(&target_conn, "Docs"), (folder_id, 0));
+    assert_eq!(parent_and_position(&target_conn, "Team"), (folder_id, 1));
+    let team_id: i64 = target_conn
+        .query_row(
+            "select id from moz_bookmarks where guid = 'teamfolder__'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(parent_and_position(&target_conn, "Wiki"), (team_id, 0));
+}
+
+#[test]
+fn appends_to_existing_folder() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    let target_conn = common::create_places(target.path());
+    common::insert_folder(&target_conn, 7, Some(5), 0, "Imported", "imported____");
+    common::insert_link(&target_conn, 20, 7, 0, "Home", "https://example.com/");
+
+    sync_into(&source, &target, |conn| {
+        common::insert_link(conn, 7, 3, 0, "Docs", "https://docs.example.com/");
+    });
+
+    let folders: i64 = target_conn
+        .query_row(
+            "select count(*) from moz_bookmarks where title = 'Imported'",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(folders, 1);
+    assert_eq!(parent_and_position(&target_conn, "Docs"), (7, 1));
+}
@@ -0,0 +1,42 @@
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::temp_profile::TempProfile;
+
+#[test]
+fn temp_profile_is_removed_unless_kept() {
+    let temp_profile = TempProfile::new("profile").unwrap();
+    let path = temp_profile.path().to_path_buf();
+    fs::create_dir(&path).unwrap();
+    drop(temp_profile);
+    assert!(!path.exists());
+
+    let mut temp_profile = TempProfile::new("profile").unwrap();
+    let path = temp_profile.path().to_path_buf();
+    fs::create_dir(&path).unwrap();
+    temp_profile.keep();
+    drop(temp_profile);
+    assert!(path.exists());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn profile_at_path_is_removed_unless_kept() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("staged");
+
+    fs::create_dir(&path).unwrap();
+    drop(TempProfile::at(path.clone()));
+    assert!(!path.exists());
+
+    fs::create_dir(&path).unwrap();
+    TempProfile::at(path.clone()).close().unwrap();
+    assert!(!path.exists());
+
+    fs::create_dir(&path).unwrap();
+    let mut temp_profile = TempProfile::at(path.clone());
+    temp_profile.keep();
+    temp_profile.close().unwrap();
+    assert!(path.exists());
+}
@@ -0,0 +1,75 @@
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+use fftemplates::manifest;
+use fftemplates::manifest::Manifest;
+
+fn kept_temp_profile(source: &TempDir) -> TempDir {
+    let profile = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    let manifest = Manifest {
+        source_profile: source.path().to_path_buf(),
+        created: 0,
+        entries: vec!["prefs.js".to_string()],
+        adjusted_prefs: vec![],
+        session_file: None,
+        extension_paths_rewritten: false,
+        baseline_bookmark_id: None,
+        source_fingerprint: None,
+    };
+    manifest::write_manifest(profile.path(), &manifest).unwrap();
+    profile
+}
+
+fn dry_run(profile: &TempDir, extra_args: &[&str]) -> String {
+    let config = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--dry-run")
+        .args(extra_args)
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn sync_into_temp_profile_stops() {
+    let source = TempDir::new().unwrap();
+    let profile = kept_temp_profile(&source);
+
+    let stdout = dry_run(&profile, &["--sync-containers"]);
+
+    assert!(
+        stdout.contains("looks like a temp profile made from"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&source.path().display().to_string()),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("Would launch"), "{}", stdout);
+    assert_eq!(
+        manifest::temp_profile_source(profile.path()).unwrap(),
+        Some(source.path().to_path_buf())
+    );
+}
+
+#[test]
+fn launch_without_sync_is_not_checked() {
+    let source = TempDir::new().unwrap();
+    let profile = kept_temp_profile(&source);
+
+    let stdout = dry_run(&profile, &[]);
+
+    assert!(!stdout.contains("temp profile"), "{}", stdout);
+    assert!(stdout.contains("Would launch"), "{}", stdout);
+}
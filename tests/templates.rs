@@ -0,0 +1,71 @@
+use fftemplates::cli::into_config;
+use fftemplates::cli::Args;
+use fftemplates::cli::SessionMode;
+use fftemplates::config_file;
+
+const CONFIG: &str = r#"
+[template.work]
+profile = "work-base"
+ignore = ["storage"]
+extra_args = ["--new-window"]
+session = "work.jsonlz4"
+
+[template.work.env]
+MOZ_ENABLE_WAYLAND = "1"
+LANG = "de_DE.UTF-8"
+"#;
+
+#[test]
+fn resolves_named_template() {
+    let file_config = config_file::parse_file_config(CONFIG).unwrap();
+
+    let settings = config_file::resolve_template("work", &file_config);
+    assert_eq!(settings.profile_name, "work-base");
+    assert_eq!(settings.ignore, vec!["storage"]);
+    assert_eq!(settings.extra_args, vec!["--new-window"]);
+    assert_eq!(
+        settings.env,
+        vec![
+            ("LANG".to_string(), "de_DE.UTF-8".to_string()),
+            ("MOZ_ENABLE_WAYLAND".to_string(), "1".to_string()),
+        ]
+    );
+    assert_eq!(settings.session.as_deref(), Some("work.jsonlz4"));
+
+    // anything else is still a plain profile name
+    let settings = config_file::resolve_template("default", &file_config);
+    assert_eq!(settings.profile_name, "default");
+    assert!(settings.extra_args.is_empty());
+}
+
+#[test]
+fn command_line_takes_precedence() {
+    let file_config = config_file::parse_file_config(CONFIG).unwrap();
+    let mut config = into_config(Args {
+        base_profile: Some("work".to_string()),
+        load_session: Some("other.jsonlz4".to_string()),
+        env: vec!["LANG=C".to_string()],
+        ..Args::default()
+    })
+    .unwrap();
+
+    let settings = config_file::resolve_template(&config.profile_name, &file_config);
+    config_file::apply_template(&mut config, settings);
+
+    assert_eq!(config.profile_name, "work-base");
+    assert_eq!(config.extra_ignore, vec!["storage"]);
+    assert_eq!(
+        config.session_mode,
+        SessionMode::Load("other.jsonlz4".to_string())
+    );
+    // later variables override earlier ones when the browser is started
+    assert_eq!(
+        config.env.last().unwrap(),
+        &("LANG".to_string(), "C".to_string())
+    );
+}
+
+#[test]
+fn rejects_unknown_fields() {
+    assert!(config_file::parse_file_config("[template.work]\nprofil = \"work\"").is_err());
+}
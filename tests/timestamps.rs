@@ -0,0 +1,53 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use fftemplates::timestamps;
+
+#[test]
+fn parses_known_dates() {
+    let parse = |value| timestamps::parse_firefox_timestamp(value).unwrap();
+
+    assert_eq!(parse("1970-01-01"), 0);
+    assert_eq!(parse("2020-01-01T00:00:00Z"), 1_577_836_800_000_000);
+    assert_eq!(parse("2000-02-29"), 951_782_400_000_000);
+    assert_eq!(parse("2024-01-31T10:15"), 1_706_696_100_000_000);
+    assert_eq!(parse("2024-01-31T10:15:30.25Z"), 1_706_696_130_250_000);
+    assert_eq!(parse("2024-01-31T12:15:00+02:00"), 1_706_696_100_000_000);
+    assert_eq!(parse("2024-01-31T05:15:00-05:00"), 1_706_696_100_000_000);
+    assert_eq!(parse("1969-12-31T23:59:59Z"), -1_000_000);
+}
+
+#[test]
+fn rejects_invalid_dates() {
+    for value in &[
+        "",
+        "2024",
+        "2024-1-31",
+        "2023-02-29",
+        "2024-13-01",
+        "2024-01-31T25:00",
+        "2024-01-31T10",
+        "2024-01-31T10:00+0200",
+        "yesterday",
+    ] {
+        assert!(
+            timestamps::parse_firefox_timestamp(value).is_err(),
+            "{} was accepted",
+            value
+        );
+    }
+}
+
+#[test]
+fn converts_system_time() {
+    let time = UNIX_EPOCH + Duration::from_micros(1_577_836_800_123_456);
+
+    assert_eq!(
+        timestamps::to_firefox_timestamp(time),
+        1_577_836_800_123_456
+    );
+    assert_eq!(
+        timestamps::from_firefox_timestamp(1_577_836_800_123_456),
+        time
+    );
+    assert_eq!(timestamps::to_firefox_timestamp(UNIX_EPOCH), 0);
+}
@@ -0,0 +1,50 @@
+use filetime::FileTime;
+use tempfile::TempDir;
+
+use std::fs;
+
+use fftemplates::session;
+use fftemplates::session::SessionLoadMode;
+
+#[test]
+fn touches_copied_session_only() {
+    let source_dir = TempDir::new().unwrap();
+    let profile = TempDir::new().unwrap();
+    let source = source_dir.path().join("old.jsonlz4");
+    fs::write(&source, session::compress_session(b"{}")).unwrap();
+    let old = FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(&source, old).unwrap();
+
+    let target = session::add_sessionstore_file(
+        source.to_str().unwrap(),
+        profile.path().to_str().unwrap(),
+        None,
+        true,
+        SessionLoadMode::Replace,
+    )
+    .unwrap()
+    .unwrap();
+    session::touch_session(&target).unwrap();
+
+    assert_eq!(target, profile.path().join("sessionstore.jsonlz4"));
+    let touched = FileTime::from_last_modification_time(&fs::metadata(&target).unwrap());
+    assert!(touched > old);
+    let source_mtime = FileTime::from_last_modification_time(&fs::metadata(&source).unwrap());
+    assert_eq!(source_mtime, old);
+}
+
+#[test]
+fn missing_optional_session_is_skipped() {
+    let profile = TempDir::new().unwrap();
+
+    let target = session::add_sessionstore_file(
+        "missing.jsonlz4",
+        profile.path().to_str().unwrap(),
+        None,
+        false,
+        SessionLoadMode::Replace,
+    )
+    .unwrap();
+
+    assert_eq!(target, None);
+}
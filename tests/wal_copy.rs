@@ -0,0 +1,51 @@
+#![cfg(feature = "bookmarks")]
+
+mod common;
+
+use rusqlite::params;
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn pending_wal_changes_are_in_the_copy() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    let conn = common::create_places(profile.path());
+    conn.query_row("pragma journal_mode = wal", params![], |_| Ok(()))
+        .unwrap();
+    // keeps changes in the WAL while the connection is open
+    conn.execute_batch("pragma wal_autocheckpoint = 0").unwrap();
+    common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+    assert!(
+        fs::metadata(profile.path().join("places.sqlite-wal"))
+            .unwrap()
+            .len()
+            > 0
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--print-command")
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let copy = Path::new(stdout.trim().rsplit(' ').next().unwrap());
+
+    let copy_conn = rusqlite::Connection::open(copy.join("places.sqlite")).unwrap();
+    let title: String = copy_conn
+        .query_row(
+            "select title from moz_bookmarks where id = 7",
+            params![],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(title, "Home");
+    fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,88 @@
+#![cfg(all(unix, feature = "bookmarks"))]
+
+mod common;
+
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn count_bookmarks(profile: &Path, guid: &str) -> i64 {
+    let conn = Connection::open(profile.join("places.sqlite")).unwrap();
+    conn.query_row(
+        "select count(*) from moz_bookmarks where guid = ?1",
+        params![guid],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+// polls until the condition holds, gives up after the timeout
+fn wait_for(timeout: Duration, condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+#[test]
+fn bookmarks_are_synced_while_firefox_runs() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    common::create_places(profile.path());
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    // browser that tells which temp profile it got and keeps running for a while
+    let launched = config.path().join("launched");
+    let browser = config.path().join("firefox");
+    fs::write(
+        &browser,
+        format!("#!/bin/sh\necho \"$2\" > {}\nsleep 8\n", launched.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg(&browser)
+        .arg("-b")
+        .arg("--watch")
+        .arg("1")
+        .env("XDG_CONFIG_HOME", config.path())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    assert!(wait_for(Duration::from_secs(10), || fs::read_to_string(
+        &launched
+    )
+    .is_ok_and(|path| !path.trim().is_empty())));
+    let temp_profile = fs::read_to_string(&launched).unwrap();
+    // like firefox, new bookmark stays in the WAL of the open database
+    let conn = Connection::open(Path::new(temp_profile.trim()).join("places.sqlite")).unwrap();
+    conn.query_row("pragma journal_mode = wal", params![], |_| Ok(()))
+        .unwrap();
+    conn.execute_batch("pragma wal_autocheckpoint = 0").unwrap();
+    common::insert_link(&conn, 7, 3, 0, "Home", "https://example.com/");
+
+    let synced = wait_for(Duration::from_secs(5), || {
+        count_bookmarks(profile.path(), "bookmark0007") > 0
+    });
+    let running = child.try_wait().unwrap().is_none();
+    drop(conn);
+    assert!(child.wait().unwrap().success());
+
+    assert!(synced && running);
+    // sync at exit starts after the bookmarks synced by the watch
+    assert_eq!(count_bookmarks(profile.path(), "bookmark0007"), 1);
+}
@@ -0,0 +1,32 @@
+#![cfg(unix)]
+
+use tempfile::TempDir;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn browser_is_launched_under_wrapper() {
+    let profile = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    fs::write(profile.path().join("prefs.js"), "").unwrap();
+    let launched = config.path().join("launched");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fftemplates"))
+        .arg("--profile-path")
+        .arg(profile.path())
+        .arg("--browser")
+        .arg("true")
+        .arg("--wrapper")
+        .arg(format!(
+            "sh -c 'echo \"$@\" > \"$0\"' '{}'",
+            launched.display()
+        ))
+        .env("XDG_CONFIG_HOME", config.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let command = fs::read_to_string(&launched).unwrap();
+    assert!(command.starts_with("true --profile "), "{}", command);
+}
@@ -0,0 +1,34 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+use fftemplates::paths;
+
+#[test]
+fn replaces_content() {
+    let folder = TempDir::new().unwrap();
+    let file = folder.path().join("prefs.js");
+    fs::write(&file, "user_pref(\"a\", 1);\n").unwrap();
+
+    paths::write_atomic(&file, b"user_pref(\"b\", 2);\n").unwrap();
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "user_pref(\"b\", 2);\n");
+    assert_eq!(fs::read_dir(folder.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn failed_write_keeps_original() {
+    let folder = TempDir::new().unwrap();
+    let file = folder.path().join("extensions.json");
+    fs::write(&file, "{\"addons\":[]}").unwrap();
+
+    let result = paths::write_atomic_with(&file, |writer| {
+        writer.write_all(b"{\"addons\":[")?;
+        Err("interrupted")?
+    });
+
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "{\"addons\":[]}");
+    // temp file is removed as well
+    assert_eq!(fs::read_dir(folder.path()).unwrap().count(), 1);
+}